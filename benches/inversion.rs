@@ -0,0 +1,108 @@
+/*
+Matrix inversion benchmarks
+---------------------------
+Run with `cargo bench --bench inversion`.
+
+Compares the old cofactor inverse with the closed-form and affine paths,
+then times spheres::intersect, which inverts the sphere's transform once
+per ray, and finally the SIMD 4x4 multiply against the scalar loop.
+*/
+
+#![allow(dead_code)]
+#![allow(clippy::duplicate_mod)]
+
+use std::hint::black_box;
+use std::time::Instant;
+
+#[path = "../src/spheres.rs"]
+mod spheres;
+
+use spheres::matrix;
+
+fn time_per_iter<F: FnMut()>(name: &str, iterations: u32, mut f: F) -> f64 {
+    // warm up, then report the mean over `iterations` runs
+    for _ in 0..iterations / 10 {
+        f();
+    }
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    let ns = start.elapsed().as_nanos() as f64 / iterations as f64;
+    println!("{:<32} {:>10.1} ns/iter", name, ns);
+    ns
+}
+
+fn main() {
+    let iterations = 1_000_000;
+
+    let transform = matrix::multiply_matrix(
+        matrix::multiply_matrix(matrix::translation(1.0,-2.0,3.0), matrix::rotation_y(0.5)),
+        matrix::scaling(2.0,0.5,1.5)
+    );
+    let general = matrix::Matrix([
+        [-5.0,2.0,6.0,-8.0],
+        [1.0,-5.0,1.0,8.0],
+        [7.0,7.0,-6.0,-7.0],
+        [1.0,-3.0,7.0,4.0]
+    ]);
+
+    println!("4x4 inverse of an affine transform");
+    let cofactors = time_per_iter("invert_cofactors", iterations, || {
+        black_box(matrix::invert_cofactors(black_box(transform)));
+    });
+    time_per_iter("invert_general", iterations, || {
+        black_box(matrix::invert_general(black_box(transform)));
+    });
+    let affine = time_per_iter("invert_affine", iterations, || {
+        black_box(matrix::invert_affine(black_box(transform)));
+    });
+    let dispatch = time_per_iter("invert", iterations, || {
+        black_box(matrix::invert(black_box(transform)));
+    });
+    println!("speed-up (cofactors / invert): {:.1}x", cofactors / dispatch);
+    println!("speed-up (cofactors / affine): {:.1}x\n", cofactors / affine);
+
+    println!("4x4 inverse of a general matrix");
+    let cofactors = time_per_iter("invert_cofactors", iterations, || {
+        black_box(matrix::invert_cofactors(black_box(general)));
+    });
+    let dispatch = time_per_iter("invert", iterations, || {
+        black_box(matrix::invert(black_box(general)));
+    });
+    println!("speed-up (cofactors / invert): {:.1}x\n", cofactors / dispatch);
+
+    // Per-ray cost: the same sphere intersection, once with the old inverse
+    // and once through spheres::intersect.
+    let s = spheres::set_transform(spheres::sphere(), transform).expect("transform is invertible");
+    let ray = spheres::Ray {
+        origin: matrix::tuples::point(0.0,0.0,-5.0),
+        direction: matrix::tuples::vector(0.0,0.0,1.0)
+    };
+
+    println!("Per-ray sphere intersection");
+    let old = time_per_iter("transform_ray(invert_cofactors)", iterations, || {
+        let r = spheres::transform_ray(black_box(ray), matrix::invert_cofactors(black_box(s).transform()));
+        black_box(r);
+    });
+    let new = time_per_iter("transform_ray(invert)", iterations, || {
+        let r = spheres::transform_ray(black_box(ray), matrix::invert(black_box(s).transform()));
+        black_box(r);
+    });
+    time_per_iter("spheres::intersect", iterations, || {
+        black_box(spheres::intersect(black_box(s), black_box(ray)));
+    });
+    println!("per-ray inverse cost drop: {:.1} ns -> {:.1} ns\n", old, new);
+
+    println!("4x4 multiply (SIMD kernels enabled: {})", matrix::tuples::simd::ENABLED);
+    let scalar = time_per_iter("multiply_matrix_scalar", iterations, || {
+        black_box(matrix::multiply_matrix_scalar(black_box(transform), black_box(general)));
+    });
+    let simd = time_per_iter("multiply_matrix", iterations, || {
+        black_box(matrix::multiply_matrix(black_box(transform), black_box(general)));
+    });
+    time_per_iter("multiply_matrix_tup", iterations, || {
+        black_box(matrix::multiply_matrix_tup(black_box(transform), black_box(ray.origin)));
+    });
+    println!("speed-up (scalar / simd): {:.1}x", scalar / simd);
+}
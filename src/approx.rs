@@ -0,0 +1,245 @@
+/*
+Approximate equality
+--------------------
+A single absolute epsilon is wrong at both ends: it is too strict for
+results like rotation_x(pi/4) (off by a few ULPs, which can be bigger than
+EPSILON) and meaningless for large coordinates. A Tolerance accepts two
+floats if ANY of these hold:
+
+    |a - b| <= absolute
+    |a - b| <= relative * max(|a|, |b|)
+    a and b are at most `ulps` representable floats apart
+
+NaN is never equal to anything.
+
+Types implement ApproxEq by listing the components that don't match, which
+is what assert_approx_eq! prints when it fails. The macro calls a trait
+method, so ApproxEq has to be in scope wherever it is used.
+*/
+
+use std::fmt::Write;
+
+use super::{Float, Point3, Tuple, Vector3};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Tolerance {
+    pub absolute:Float,
+    pub relative:Float,
+    pub ulps:u32,
+}
+
+impl Tolerance {
+    pub fn new(absolute:Float, relative:Float, ulps:u32) -> Tolerance {
+        Tolerance{absolute, relative, ulps}
+    }
+
+    pub fn absolute(absolute:Float) -> Tolerance {
+        Tolerance::new(absolute, 0.0, 0)
+    }
+
+    pub fn relative(relative:Float) -> Tolerance {
+        Tolerance::new(0.0, relative, 0)
+    }
+
+    pub fn ulps(ulps:u32) -> Tolerance {
+        Tolerance::new(0.0, 0.0, ulps)
+    }
+}
+
+impl Default for Tolerance {
+    fn default() -> Tolerance {
+        // The book's EPSILON, plus enough slack for large values and
+        // last-bit rounding differences.
+        Tolerance::new(1e-5, 1e-5, 4)
+    }
+}
+
+// Float bits remapped so that adjacent floats are adjacent integers
+// (and -0.0 == +0.0).
+#[cfg(not(feature = "f64"))]
+fn ordered_bits(x:Float) -> i128 {
+    let bits = x.to_bits() as i32;
+    (if bits < 0 { i32::MIN - bits } else { bits }) as i128
+}
+
+#[cfg(feature = "f64")]
+fn ordered_bits(x:Float) -> i128 {
+    let bits = x.to_bits() as i64;
+    (if bits < 0 { i64::MIN - bits } else { bits }) as i128
+}
+
+pub fn ulps_between(a:Float, b:Float) -> u64 {
+    // u64::MAX if either is NaN
+    if a.is_nan() || b.is_nan() {
+        return u64::MAX;
+    }
+    (ordered_bits(a) - ordered_bits(b)).unsigned_abs().min(u64::MAX as u128) as u64
+}
+
+pub fn floats_equal(a:Float, b:Float, tol:Tolerance) -> bool {
+    if a == b {
+        // also covers equal infinities
+        return true;
+    }
+    if a.is_nan() || b.is_nan() {
+        return false;
+    }
+    let diff = (a - b).abs();
+    diff <= tol.absolute
+        || diff <= tol.relative * a.abs().max(b.abs())
+        || ulps_between(a, b) <= tol.ulps as u64
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    pub component:String,
+    pub left:Float,
+    pub right:Float,
+}
+
+pub trait ApproxEq {
+    // Every component of self that isn't within `tol` of other.
+    fn mismatches(&self, other:&Self, tol:Tolerance) -> Vec<Mismatch>;
+
+    fn approx_eq_with(&self, other:&Self, tol:Tolerance) -> bool {
+        self.mismatches(other, tol).is_empty()
+    }
+
+    fn approx_eq(&self, other:&Self) -> bool {
+        self.approx_eq_with(other, Tolerance::default())
+    }
+
+    #[track_caller]
+    fn assert_approx_eq(&self, other:&Self, tol:Tolerance, left_expr:&str, right_expr:&str)
+    where
+        Self: std::fmt::Debug,
+    {
+        let mismatches = self.mismatches(other, tol);
+        if !mismatches.is_empty() {
+            panic!("{}", describe_mismatches(left_expr, right_expr, self, other, tol, &mismatches));
+        }
+    }
+}
+
+pub fn describe_mismatches<T: std::fmt::Debug + ?Sized>(
+    left_expr:&str,
+    right_expr:&str,
+    left:&T,
+    right:&T,
+    tol:Tolerance,
+    mismatches:&[Mismatch]
+) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out, "assertion `{} ≈ {}` failed (absolute {:e}, relative {:e}, {} ulps)",
+        left_expr, right_expr, tol.absolute, tol.relative, tol.ulps
+    );
+    let _ = writeln!(out, "  left: {:?}", left);
+    let _ = writeln!(out, " right: {:?}", right);
+    let _ = writeln!(out, "{:>14} {:>16} {:>16} {:>12} {:>8}", "component", "left", "right", "diff", "ulps");
+    for m in mismatches {
+        let ulps = ulps_between(m.left, m.right);
+        let ulps = if ulps > 99_999_999 { String::from(">1e8") } else { ulps.to_string() };
+        let _ = writeln!(
+            out, "{:>14} {:>16.8e} {:>16.8e} {:>12.4e} {:>8}",
+            m.component, m.left, m.right, (m.left - m.right).abs(), ulps
+        );
+    }
+    out
+}
+
+// Helper for implementors: compares named components pairwise.
+pub fn compare_components(pairs:&[(&str, Float, Float)], tol:Tolerance) -> Vec<Mismatch> {
+    pairs
+        .iter()
+        .filter(|(_, l, r)| !floats_equal(*l, *r, tol))
+        .map(|(name, l, r)| Mismatch{component: name.to_string(), left: *l, right: *r})
+        .collect()
+}
+
+// assert_approx_eq!(left, right) or assert_approx_eq!(left, right, tolerance)
+macro_rules! assert_approx_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        ($left).assert_approx_eq(&$right, Default::default(), stringify!($left), stringify!($right))
+    };
+    ($left:expr, $right:expr, $tol:expr $(,)?) => {
+        ($left).assert_approx_eq(&$right, $tol, stringify!($left), stringify!($right))
+    };
+}
+
+#[allow(unused_imports)]
+pub(crate) use assert_approx_eq;
+
+impl ApproxEq for Float {
+    fn mismatches(&self, other:&Float, tol:Tolerance) -> Vec<Mismatch> {
+        compare_components(&[("value", *self, *other)], tol)
+    }
+
+    fn approx_eq_with(&self, other:&Float, tol:Tolerance) -> bool {
+        floats_equal(*self, *other, tol)
+    }
+}
+
+impl ApproxEq for Tuple {
+    fn mismatches(&self, other:&Tuple, tol:Tolerance) -> Vec<Mismatch> {
+        compare_components(&[
+            ("x", self.x, other.x),
+            ("y", self.y, other.y),
+            ("z", self.z, other.z),
+            ("w", self.w, other.w),
+        ], tol)
+    }
+}
+
+impl ApproxEq for Point3 {
+    fn mismatches(&self, other:&Point3, tol:Tolerance) -> Vec<Mismatch> {
+        compare_components(&[("x", self.x, other.x), ("y", self.y, other.y), ("z", self.z, other.z)], tol)
+    }
+}
+
+impl ApproxEq for Vector3 {
+    fn mismatches(&self, other:&Vector3, tol:Tolerance) -> Vec<Mismatch> {
+        compare_components(&[("x", self.x, other.x), ("y", self.y, other.y), ("z", self.z, other.z)], tol)
+    }
+}
+
+#[allow(dead_code)]
+pub fn approx_tests() {
+    // Tolerance kinds
+    assert!(floats_equal(1.0, 1.0 + 5e-6, Tolerance::absolute(1e-5)));
+    assert!(!floats_equal(1.0, 1.0 + 5e-5, Tolerance::absolute(1e-5)));
+    assert!(floats_equal(1.0e6, 1.0e6 + 5.0, Tolerance::relative(1e-5)));
+    assert!(!floats_equal(1.0e6, 1.0e6 + 50.0, Tolerance::relative(1e-5)));
+    assert!(floats_equal(1.0, 1.0, Tolerance::ulps(0)));
+    assert_eq!(ulps_between(0.0, -0.0), 0);
+    assert_eq!(ulps_between(1.0, Float::from_bits((1.0 as Float).to_bits() + 3)), 3);
+    assert!(floats_equal(1.0, Float::from_bits((1.0 as Float).to_bits() + 3), Tolerance::ulps(4)));
+    assert!(!floats_equal(Float::NAN, Float::NAN, Tolerance::default()));
+    assert!(floats_equal(Float::INFINITY, Float::INFINITY, Tolerance::absolute(0.0)));
+    assert!(ulps_between(-1.0, 1.0) > 1_000_000);
+
+    // The default tolerance accepts the classic rotation result
+    let half = super::consts::FRAC_1_SQRT_2;
+    let (s, c) = (super::consts::PI / 4.0).sin_cos();
+    assert!(s.approx_eq(&half) && c.approx_eq(&half));
+    assert!(!(0.1 as Float).approx_eq(&0.1001));
+
+    // Tuples and typed points/vectors
+    let t1 = super::point(1.0, 2.0, 3.0);
+    let t2 = super::point(1.0, 2.0 + 1e-6, 3.0);
+    assert!(t1.approx_eq(&t2));
+    assert!(!t1.approx_eq_with(&t2, Tolerance::ulps(1)));
+    assert_eq!(t1.mismatches(&super::vector(1.0, 2.5, 3.0), Tolerance::default()).len(), 2);
+    assert_approx_eq!(super::point3(0.1, 0.2, 0.3), super::point3(0.1, 0.2, 0.3 + 1e-7));
+    assert_approx_eq!(super::vector3(1.0, 0.0, 0.0), super::vector3(1.0, 0.0, 1e-3), Tolerance::absolute(1e-2));
+
+    // Failure messages name the differing components
+    let message = describe_mismatches(
+        "a", "b", &t1, &super::vector(1.0, 2.0, 4.0), Tolerance::default(),
+        &t1.mismatches(&super::vector(1.0, 2.0, 4.0), Tolerance::default())
+    );
+    assert!(message.contains("assertion `a ≈ b` failed"));
+    assert!(message.contains("             z") && message.contains("             w"));
+    assert!(!message.contains("             x"));
+    assert_eq!(message.lines().count(), 4 + 2);
+}
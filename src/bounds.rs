@@ -0,0 +1,177 @@
+/*
+Axis-aligned bounding boxes
+---------------------------
+An Aabb is the box min <= p <= max on every axis. Aabb::empty() has
+min = +inf and max = -inf, so it contains nothing and is the identity for
+union, which makes folding a list of boxes straightforward:
+
+    objects.iter().fold(Aabb::empty(), |b, o| b.union(bounds(*o)))
+
+transform() maps all 8 corners and boxes the result, which is the tightest
+axis-aligned box around the transformed box (not necessarily around the
+transformed object: a rotated sphere gets a looser box this way, see
+spheres::bounds). Like Matrix * Point3 it assumes an affine matrix.
+*/
+
+use super::tuples::{self, Float, Point3, Vector3};
+use super::Matrix;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min:Point3,
+    pub max:Point3,
+}
+
+impl Aabb {
+    pub fn new(a:Point3, b:Point3) -> Aabb {
+        // any two opposite corners
+        Aabb {
+            min: tuples::point3(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: tuples::point3(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    pub fn empty() -> Aabb {
+        let inf = Float::INFINITY;
+        Aabb {
+            min: tuples::point3(inf, inf, inf),
+            max: tuples::point3(-inf, -inf, -inf),
+        }
+    }
+
+    pub fn from_points(points:&[Point3]) -> Aabb {
+        points.iter().fold(Aabb::empty(), |b, p| b.add_point(*p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn add_point(self, p:Point3) -> Aabb {
+        self.union(Aabb { min: p, max: p })
+    }
+
+    pub fn union(self, other:Aabb) -> Aabb {
+        let (a, b) = (self, other);
+        Aabb {
+            min: tuples::point3(a.min.x.min(b.min.x), a.min.y.min(b.min.y), a.min.z.min(b.min.z)),
+            max: tuples::point3(a.max.x.max(b.max.x), a.max.y.max(b.max.y), a.max.z.max(b.max.z)),
+        }
+    }
+
+    pub fn contains_point(&self, p:Point3) -> bool {
+        // boundary counts as inside
+        (0..3).all(|i| self.min[i] <= p[i] && p[i] <= self.max[i])
+    }
+
+    pub fn contains(&self, other:Aabb) -> bool {
+        // the empty box fits inside anything
+        other.is_empty() || (self.contains_point(other.min) && self.contains_point(other.max))
+    }
+
+    pub fn center(&self) -> Point3 {
+        self.min + (self.max - self.min) / 2.0
+    }
+
+    pub fn size(&self) -> Vector3 {
+        self.max - self.min
+    }
+
+    pub fn corners(&self) -> [Point3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            tuples::point3(a.x, a.y, a.z),
+            tuples::point3(a.x, a.y, b.z),
+            tuples::point3(a.x, b.y, a.z),
+            tuples::point3(a.x, b.y, b.z),
+            tuples::point3(b.x, a.y, a.z),
+            tuples::point3(b.x, a.y, b.z),
+            tuples::point3(b.x, b.y, a.z),
+            tuples::point3(b.x, b.y, b.z),
+        ]
+    }
+
+    pub fn transform(self, m:Matrix) -> Aabb {
+        if self.is_empty() {
+            return self;
+        }
+        self.corners().iter().fold(Aabb::empty(), |b, c| b.add_point(m * *c))
+    }
+
+    pub fn intersect_ray(&self, origin:Point3, direction:Vector3) -> Option<(Float, Float)> {
+        // Slab test: clip the ray's t range against each pair of planes.
+        // Returns the (t_enter, t_exit) interval, which can start behind the
+        // origin; None if the ray misses. A ray parallel to a slab either
+        // lies between its planes (no constraint) or misses outright.
+        let mut t_min = -Float::INFINITY;
+        let mut t_max = Float::INFINITY;
+        for i in 0..3 {
+            if direction[i] == 0.0 {
+                if origin[i] < self.min[i] || origin[i] > self.max[i] {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (self.min[i] - origin[i]) / direction[i];
+            let t1 = (self.max[i] - origin[i]) / direction[i];
+            t_min = t_min.max(t0.min(t1));
+            t_max = t_max.min(t0.max(t1));
+        }
+        if t_min <= t_max && !self.is_empty() {
+            Some((t_min, t_max))
+        } else {
+            None
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn bounds_tests() {
+    let p = tuples::point3;
+    let v = tuples::vector3;
+
+    // Construction and basic queries
+    let b = Aabb::new(p(1.0,-2.0,3.0), p(-1.0,2.0,0.0));
+    assert_eq!(b, Aabb { min: p(-1.0,-2.0,0.0), max: p(1.0,2.0,3.0) });
+    assert_eq!(b.center(), p(0.0,0.0,1.5));
+    assert_eq!(b.size(), v(2.0,4.0,3.0));
+    assert!(Aabb::empty().is_empty() && !b.is_empty());
+    assert_eq!(Aabb::from_points(&[p(0.0,0.0,0.0), p(2.0,-1.0,5.0), p(1.0,3.0,1.0)]), Aabb::new(p(0.0,-1.0,0.0), p(2.0,3.0,5.0)));
+
+    // Union
+    let c = Aabb::new(p(5.0,5.0,5.0), p(6.0,7.0,8.0));
+    assert_eq!(b.union(c), Aabb::new(p(-1.0,-2.0,0.0), p(6.0,7.0,8.0)));
+    assert_eq!(b.union(Aabb::empty()), b);
+    assert_eq!(Aabb::empty().union(b), b);
+
+    // Containment
+    assert!(b.contains_point(p(0.0,0.0,0.0)) && b.contains_point(p(1.0,2.0,3.0)));
+    assert!(!b.contains_point(p(0.0,0.0,3.1)));
+    assert!(b.contains(Aabb::new(p(0.0,0.0,1.0), p(0.5,0.5,2.0))));
+    assert!(!b.contains(c) && b.union(c).contains(c) && b.contains(Aabb::empty()));
+
+    // Ray slab test
+    let unit = Aabb::new(p(-1.0,-1.0,-1.0), p(1.0,1.0,1.0));
+    assert_eq!(unit.intersect_ray(p(5.0,0.5,0.0), v(-1.0,0.0,0.0)), Some((4.0, 6.0)));
+    assert_eq!(unit.intersect_ray(p(0.0,0.0,0.0), v(0.0,0.0,1.0)), Some((-1.0, 1.0)));
+    assert_eq!(unit.intersect_ray(p(-2.0,0.0,0.0), v(2.0,4.0,6.0)), None);
+    assert_eq!(unit.intersect_ray(p(2.0,2.0,0.0), v(0.0,0.0,1.0)), None);
+    // grazing an edge, and starting on a face with a parallel direction
+    assert_eq!(unit.intersect_ray(p(1.0,1.0,-5.0), v(0.0,0.0,1.0)), Some((4.0, 6.0)));
+    assert!(unit.intersect_ray(p(1.0,0.0,0.0), v(0.0,1.0,0.0)).is_some());
+    // behind the ray: the interval is negative, callers decide what counts
+    assert_eq!(unit.intersect_ray(p(0.0,0.0,5.0), v(0.0,0.0,1.0)), Some((-6.0, -4.0)));
+    assert_eq!(Aabb::empty().intersect_ray(p(0.0,0.0,0.0), v(1.0,0.0,0.0)), None);
+
+    // Transforms
+    let moved = unit.transform(super::multiply_matrix(super::translation(1.0,2.0,3.0), super::scaling(2.0,1.0,0.5)));
+    assert_eq!(moved, Aabb::new(p(-1.0,1.0,2.5), p(3.0,3.0,3.5)));
+    let turned = unit.transform(super::rotation_z(tuples::consts::FRAC_PI_4));
+    let r = tuples::consts::SQRT_2;
+    assert!((turned.max.x - r).abs() < 1e-5 && (turned.min.y + r).abs() < 1e-5 && turned.max.z == 1.0);
+    assert!(Aabb::empty().transform(super::scaling(2.0,2.0,2.0)).is_empty());
+    // every transformed corner is inside the new box
+    let m = super::Transform::identity().rotate(v(1.0,1.0,0.0), 0.7).shear(0.5,0.0,0.0,0.0,0.0,0.2).translate(-3.0,0.0,1.0).build();
+    let boxed = b.transform(m);
+    assert!(b.corners().iter().all(|c| boxed.contains_point(m * *c)));
+}
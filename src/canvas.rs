@@ -5,18 +5,52 @@ The Ray Tracer Challenge (Jamis Buck)
 Chapter 2: Drawing on a Canvas
 */
 
+use std::fmt;
 use std::fs;
+use std::io;
+use std::ops::{Add, AddAssign, Sub, Mul, Div};
+use std::path::Path;
 #[path = "tuples.rs"]
 mod tuples;
 
+#[path = "ppm.rs"]
+pub mod ppm;
+
+#[path = "png.rs"]
+pub mod png;
+
+#[path = "hdr.rs"]
+pub mod hdr;
+
+#[path = "tonemap.rs"]
+pub mod tonemap;
+
+#[path = "colorspace.rs"]
+pub mod colorspace;
+
+#[path = "compare.rs"]
+pub mod compare;
+
+#[path = "draw.rs"]
+pub mod draw;
+
+#[path = "font.rs"]
+pub mod font;
+
+#[path = "plot.rs"]
+pub mod plot;
+
+use tuples::Float;
+use tonemap::DisplayTransform;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Color {
-    pub red: f32,
-    pub green: f32,
-    pub blue: f32
+    pub red: Float,
+    pub green: Float,
+    pub blue: Float
 }
 
-pub fn color(r: f32, g:f32, b:f32) -> Color {
+pub fn color(r: Float, g:Float, b:Float) -> Color {
     Color{red: r, green: g, blue:b}
 }
 
@@ -27,6 +61,16 @@ pub fn equal_colors(c1:Color, c2:Color) -> bool {
     && tuples::float_equal(c1.blue, c2.blue)
 }
 
+impl tuples::approx::ApproxEq for Color {
+    fn mismatches(&self, other:&Color, tol:tuples::approx::Tolerance) -> Vec<tuples::approx::Mismatch> {
+        tuples::approx::compare_components(&[
+            ("red", self.red, other.red),
+            ("green", self.green, other.green),
+            ("blue", self.blue, other.blue),
+        ], tol)
+    }
+}
+
 #[allow(dead_code)]
 pub fn add(col1:Color, col2:Color) -> Color {
     Color{red: col1.red + col2.red, 
@@ -44,7 +88,7 @@ pub fn subtract(col1:Color, col2:Color) -> Color {
 }
 
 #[allow(dead_code)]
-pub fn multiply(scalar:f32, col1:Color) -> Color {
+pub fn multiply(scalar:Float, col1:Color) -> Color {
     Color{red: col1.red * scalar, 
         green: col1.green * scalar, 
         blue: col1.blue * scalar
@@ -60,18 +104,195 @@ pub fn gavkujo_product(col1: Color, col2: Color) -> Color {
     }
 }
 
-pub fn canvas(width:usize, height:usize) -> Vec<Vec<Color>> {
-    // let mut pixeled: Vec<Vec<Color>> = vec![];
-    vec![vec![color(0.0,0.0,0.0); width]; height]
+impl Add for Color {
+    type Output = Color;
+    fn add(self, c:Color) -> Color {
+        color(self.red + c.red, self.green + c.green, self.blue + c.blue)
+    }
 }
 
-pub fn write_pixel(
-    mut canvas1: Vec<Vec<Color>>, 
-    x:usize, 
-    y:usize, 
-    colour: Color) -> Vec<Vec<Color>> {
-    canvas1[y][x] = colour;
-    canvas1
+impl AddAssign for Color {
+    fn add_assign(&mut self, c:Color) {
+        *self = *self + c;
+    }
+}
+
+impl Sub for Color {
+    type Output = Color;
+    fn sub(self, c:Color) -> Color {
+        color(self.red - c.red, self.green - c.green, self.blue - c.blue)
+    }
+}
+
+impl Mul<Float> for Color {
+    type Output = Color;
+    fn mul(self, scalar:Float) -> Color {
+        color(self.red * scalar, self.green * scalar, self.blue * scalar)
+    }
+}
+
+impl Mul<Color> for Float {
+    type Output = Color;
+    fn mul(self, c:Color) -> Color {
+        c * self
+    }
+}
+
+impl Mul for Color {
+    // component-wise, i.e. gavkujo_product
+    type Output = Color;
+    fn mul(self, c:Color) -> Color {
+        color(self.red * c.red, self.green * c.green, self.blue * c.blue)
+    }
+}
+
+impl Div<Float> for Color {
+    type Output = Color;
+    fn div(self, scalar:Float) -> Color {
+        color(self.red / scalar, self.green / scalar, self.blue / scalar)
+    }
+}
+
+impl std::iter::Sum for Color {
+    fn sum<I: Iterator<Item = Color>>(iter:I) -> Color {
+        iter.fold(color(0.0, 0.0, 0.0), Add::add)
+    }
+}
+
+/*
+Canvas
+------
+width x height pixels stored row by row in one Vec, so pixel (x, y) is
+pixels[y * width + x] and a row is a contiguous slice. (0, 0) is the top
+left corner.
+
+pixel_at/set_pixel panic on coordinates outside the canvas, like slice
+indexing. The _checked variants return None/Err instead, and
+set_pixel_clipped takes signed coordinates and silently drops anything
+off-canvas, which is what plotting code usually wants.
+*/
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    width:usize,
+    height:usize,
+    pixels:Vec<Color>
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OutOfBounds {
+    pub x:usize,
+    pub y:usize,
+    pub width:usize,
+    pub height:usize
+}
+
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Pixel ({}, {}) is outside the {}x{} canvas", self.x, self.y, self.width, self.height)
+    }
+}
+
+impl std::error::Error for OutOfBounds {}
+
+pub fn canvas(width:usize, height:usize) -> Canvas {
+    // every pixel starts black
+    Canvas {
+        width,
+        height,
+        pixels: vec![color(0.0,0.0,0.0); width * height]
+    }
+}
+
+impl Canvas {
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    fn index(&self, x:usize, y:usize) -> Option<usize> {
+        if x < self.width && y < self.height {
+            Some(y * self.width + x)
+        } else {
+            None
+        }
+    }
+
+    fn expect_index(&self, x:usize, y:usize) -> usize {
+        match self.index(x, y) {
+            Some(i) => i,
+            None => panic!("{}", OutOfBounds{x, y, width: self.width, height: self.height})
+        }
+    }
+
+    pub fn pixel_at(&self, x:usize, y:usize) -> Color {
+        self.pixels[self.expect_index(x, y)]
+    }
+
+    pub fn pixel_mut(&mut self, x:usize, y:usize) -> &mut Color {
+        let i = self.expect_index(x, y);
+        &mut self.pixels[i]
+    }
+
+    pub fn set_pixel(&mut self, x:usize, y:usize, colour:Color) {
+        *self.pixel_mut(x, y) = colour;
+    }
+
+    pub fn pixel_at_checked(&self, x:usize, y:usize) -> Option<Color> {
+        self.index(x, y).map(|i| self.pixels[i])
+    }
+
+    pub fn set_pixel_checked(&mut self, x:usize, y:usize, colour:Color) -> Result<(), OutOfBounds> {
+        match self.index(x, y) {
+            Some(i) => {
+                self.pixels[i] = colour;
+                Ok(())
+            }
+            None => Err(OutOfBounds{x, y, width: self.width, height: self.height})
+        }
+    }
+
+    pub fn set_pixel_clipped(&mut self, x:isize, y:isize, colour:Color) -> bool {
+        // true if the pixel landed on the canvas
+        if x < 0 || y < 0 {
+            return false;
+        }
+        self.set_pixel_checked(x as usize, y as usize, colour).is_ok()
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = &[Color]> {
+        // top to bottom; max(1) keeps chunks() happy on a zero-width canvas
+        self.pixels.chunks(self.width.max(1))
+    }
+
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Color]> {
+        self.pixels.chunks_mut(self.width.max(1))
+    }
+
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize, Color)> + '_ {
+        // (x, y, colour) in row order
+        let width = self.width;
+        self.pixels.iter().enumerate().map(move |(i, c)| (i % width, i / width, *c))
+    }
+
+    pub fn pixels_mut(&mut self) -> impl Iterator<Item = &mut Color> {
+        self.pixels.iter_mut()
+    }
+
+    pub fn as_slice(&self) -> &[Color] {
+        &self.pixels
+    }
+
+    pub fn fill(&mut self, colour:Color) {
+        self.pixels.fill(colour);
+    }
+
+    pub fn clear(&mut self) {
+        self.fill(color(0.0,0.0,0.0));
+    }
 }
 
 pub fn header(width:usize,height:usize) -> String {
@@ -79,42 +300,353 @@ pub fn header(width:usize,height:usize) -> String {
     // "P3\n{width} {height}\n255"
     let mut header = String::from("P3\n");
     header.push_str(&width.to_string());
-    header.push_str(" ");
+    header.push(' ');
     header.push_str(&height.to_string());
     header.push_str("\n255\n");
     header
 }
 
+/*
+PPM export
+----------
+Plain (P3) PPM as the book describes it: the header from header(), then
+one line of "r g b" values per canvas row. Channels are clamped to 0..1
+and scaled to 0..255 with rounding (NaN becomes 0). Rows longer than 70
+characters are wrapped between values, since some readers reject longer
+lines, and the file always ends with a newline.
+
+Binary (P6) PPM has the same header with P6 and the maxval (255 or 65535),
+then the raw samples: one byte each, or two big-endian bytes for
+Binary16. It is a fraction of the size and much faster to write and read.
+
+Every format streams one row at a time; save_ppm buffers the file, so
+nothing builds the whole image in memory.
+*/
+
+const PPM_LINE_LIMIT:usize = 70;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PpmFormat {
+    // P3, ASCII, 8 bits per sample
+    Plain,
+    // P6, 8 bits per sample
+    Binary8,
+    // P6, 16 bits per sample
+    Binary16,
+}
+
+fn quantize(c:Float, maxval:Float) -> u16 {
+    // clamp passes NaN straight through, so map it to 0 first
+    if c.is_nan() {
+        return 0;
+    }
+    (c.clamp(0.0, 1.0) * maxval).round() as u16
+}
+
+// one of these is a no-op, depending on what Float is
+#[allow(clippy::unnecessary_cast)]
+fn to_f32(c:Float) -> f32 {
+    c as f32
+}
+
+#[allow(clippy::unnecessary_cast)]
+fn to_f64(c:Float) -> f64 {
+    c as f64
+}
+
+fn channel_to_byte(c:Float) -> u8 {
+    quantize(c, 255.0) as u8
+}
+
+impl Canvas {
+    pub fn write_ppm<W: io::Write>(&self, w:&mut W) -> io::Result<()> {
+        self.write_ppm_as(w, PpmFormat::Plain)
+    }
+
+    pub fn write_ppm_as<W: io::Write>(&self, w:&mut W, format:PpmFormat) -> io::Result<()> {
+        self.write_ppm_with(w, format, &DisplayTransform::identity())
+    }
+
+    pub fn write_ppm_with<W: io::Write>(&self, w:&mut W, format:PpmFormat, display:&DisplayTransform) -> io::Result<()> {
+        // display maps each channel to 0..1 first; see tonemap.rs
+        let channel = |c:Float| display.apply_channel(c);
+        match format {
+            PpmFormat::Plain => self.write_plain_ppm(w, display),
+            PpmFormat::Binary8 => {
+                write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+                let mut bytes = Vec::with_capacity(self.width * 3);
+                for row in self.rows() {
+                    bytes.clear();
+                    for pixel in row {
+                        bytes.extend([pixel.red, pixel.green, pixel.blue].map(|c| channel_to_byte(channel(c))));
+                    }
+                    w.write_all(&bytes)?;
+                }
+                Ok(())
+            }
+            PpmFormat::Binary16 => {
+                write!(w, "P6\n{} {}\n65535\n", self.width, self.height)?;
+                let mut bytes = Vec::with_capacity(self.width * 6);
+                for row in self.rows() {
+                    bytes.clear();
+                    for pixel in row {
+                        for c in [pixel.red, pixel.green, pixel.blue] {
+                            bytes.extend(quantize(channel(c), 65535.0).to_be_bytes());
+                        }
+                    }
+                    w.write_all(&bytes)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn save_ppm<P: AsRef<Path>>(&self, path:P, format:PpmFormat) -> io::Result<()> {
+        self.save_ppm_with(path, format, &DisplayTransform::identity())
+    }
+
+    pub fn save_ppm_with<P: AsRef<Path>>(&self, path:P, format:PpmFormat, display:&DisplayTransform) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        self.write_ppm_with(&mut file, format, display)?;
+        io::Write::flush(&mut file)
+    }
+
+    fn write_plain_ppm<W: io::Write>(&self, w:&mut W, display:&DisplayTransform) -> io::Result<()> {
+        w.write_all(header(self.width, self.height).as_bytes())?;
+        let mut line = String::with_capacity(PPM_LINE_LIMIT + 1);
+        for row in self.rows() {
+            line.clear();
+            let mut line_start = 0;
+            for pixel in row {
+                for c in [pixel.red, pixel.green, pixel.blue] {
+                    let value = channel_to_byte(display.apply_channel(c)).to_string();
+                    if line.len() > line_start {
+                        if line.len() - line_start + 1 + value.len() > PPM_LINE_LIMIT {
+                            line.push('\n');
+                            line_start = line.len();
+                        } else {
+                            line.push(' ');
+                        }
+                    }
+                    line.push_str(&value);
+                }
+            }
+            line.push('\n');
+            w.write_all(line.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn to_ppm(&self) -> String {
+        let mut out = Vec::new();
+        self.write_ppm(&mut out).expect("writing to a Vec can't fail");
+        String::from_utf8(out).expect("PPM output is ASCII")
+    }
+}
+
 #[allow(dead_code)]
-pub fn canvas_tests() {
-    // println!("{}", header(32, 32));
-    // let trial_header = "P3\n5 3\n255\n255 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n0 0 0 0 0 0 0 128 0 0 0 0 0 0 0\n0 0 0 0 0 0 0 0 0 0 0 0 0 0 255";
-    // fs::write("canvas.ppm", trial_header).expect("can't read file");
-    
+pub fn demo_canvas() -> Canvas {
     let mut canvas1 = canvas(5,3);
     let red = Color{red:1.0, green:0.0, blue:0.0};
     let green = Color{red:0.0, green:1.0, blue:0.0};
     let blue = Color{red:0.0, green:0.0, blue:1.0};
-    canvas1 = write_pixel(canvas1, 0, 0, red);
-    canvas1 = write_pixel(canvas1, 2, 1, green);
-    canvas1 = write_pixel(canvas1, 4, 2, blue);
-
-    let mut ppm_string = header(5,3);
-
-    for i in canvas1 {
-        for j in i {
-            ppm_string.push_str(&((j.red * 255.0).ceil()).to_string());
-            ppm_string.push_str(" ");
-            ppm_string.push_str(&((j.green * 255.0).ceil()).to_string());
-            ppm_string.push_str(" ");
-            ppm_string.push_str(&((j.blue * 255.0).ceil()).to_string());
-            ppm_string.push_str(" ");
-        }
-        ppm_string.push_str("\n");
-    }
+    canvas1.set_pixel(0, 0, red);
+    canvas1.set_pixel(2, 1, green);
+    canvas1.set_pixel(4, 2, blue);
+    canvas1
+}
 
+#[allow(dead_code)]
+pub fn canvas_tests() {
+    // println!("{}", header(32, 32));
+    // let trial_header = "P3\n5 3\n255\n255 0 0 0 0 0 0 0 0 0 0 0 0 0 0\n0 0 0 0 0 0 0 128 0 0 0 0 0 0 0\n0 0 0 0 0 0 0 0 0 0 0 0 0 0 255";
+    // fs::write("canvas.ppm", trial_header).expect("can't read file");
+
+    let canvas1 = demo_canvas();
     // fs::write("canvas.ppm", header(5,3));
-    fs::write("renders/canvas.ppm", ppm_string).expect("");
+    canvas1.save_ppm("renders/canvas.ppm", PpmFormat::Plain).expect("");
+    canvas1.save_png("renders/canvas.png", png::PngFormat::Rgb8).expect("");
 
     // println!("{}", write_string);
-}
\ No newline at end of file
+}
+
+#[allow(dead_code)]
+pub fn color_tests() {
+    use tuples::approx::{assert_approx_eq, ApproxEq, Tolerance};
+
+    let c1 = color(0.9, 0.6, 0.75);
+    let c2 = color(0.7, 0.1, 0.25);
+    assert!(equal_colors(add(c1, c2), color(1.6, 0.7, 1.0)));
+    assert!(equal_colors(subtract(c1, c2), color(0.2, 0.5, 0.5)));
+    assert!(equal_colors(multiply(2.0, color(0.2, 0.3, 0.4)), color(0.4, 0.6, 0.8)));
+    assert!(equal_colors(gavkujo_product(color(1.0, 0.2, 0.4), color(0.9, 1.0, 0.1)), color(0.9, 0.2, 0.04)));
+    assert_approx_eq!(subtract(c1, c2), color(0.2, 0.5, 0.5));
+    assert!(!c1.approx_eq(&c2));
+    assert!(c1.approx_eq_with(&color(0.91, 0.6, 0.75), Tolerance::absolute(0.02)));
+    let wrong = c1.mismatches(&color(0.9, 0.6, 0.8), Tolerance::default());
+    assert_eq!(wrong.len(), 1);
+    assert_eq!(wrong[0].component, "blue");
+
+    // Operators agree with the functions
+    assert_approx_eq!(c1 + c2, add(c1, c2));
+    assert_approx_eq!(c1 - c2, subtract(c1, c2));
+    assert_eq!(color(0.2, 0.3, 0.4) * 2.0, multiply(2.0, color(0.2, 0.3, 0.4)));
+    assert_eq!(2.0 * color(0.2, 0.3, 0.4), color(0.4, 0.6, 0.8));
+    assert_eq!(color(1.0, 0.2, 0.4) * color(0.9, 1.0, 0.1), gavkujo_product(color(1.0, 0.2, 0.4), color(0.9, 1.0, 0.1)));
+    assert_eq!(color(0.4, 0.6, 0.8) / 2.0, color(0.2, 0.3, 0.4));
+    let mut total = color(0.0, 0.0, 0.0);
+    total += c1;
+    total += c2;
+    assert_approx_eq!(total, [c1, c2].into_iter().sum::<Color>());
+}
+
+#[allow(dead_code)]
+pub fn canvas_struct_tests() {
+    let black = color(0.0, 0.0, 0.0);
+    let red = color(1.0, 0.0, 0.0);
+
+    // Every pixel starts black
+    let mut c = canvas(10, 20);
+    assert_eq!((c.width(), c.height()), (10, 20));
+    assert_eq!(c.as_slice().len(), 200);
+    assert!(c.pixels().all(|(_, _, p)| p == black));
+
+    // Writing in place
+    c.set_pixel(2, 3, red);
+    assert_eq!(c.pixel_at(2, 3), red);
+    assert_eq!(c.as_slice()[3 * 10 + 2], red);
+    c.pixel_mut(9, 19).green = 0.5;
+    assert_eq!(c.pixel_at(9, 19), color(0.0, 0.5, 0.0));
+
+    // Checked and clipped access
+    assert_eq!(c.pixel_at_checked(2, 3), Some(red));
+    assert_eq!(c.pixel_at_checked(10, 0), None);
+    assert_eq!(c.set_pixel_checked(0, 20, red), Err(OutOfBounds{x: 0, y: 20, width: 10, height: 20}));
+    assert_eq!(c.set_pixel_checked(0, 19, red), Ok(()));
+    assert!(c.set_pixel_clipped(1, 1, red));
+    assert!(!c.set_pixel_clipped(-1, 5, red) && !c.set_pixel_clipped(5, 20, red));
+    assert_eq!(c.pixels().filter(|(_, _, p)| *p == red).count(), 3);
+    assert_eq!(OutOfBounds{x: 0, y: 20, width: 10, height: 20}.to_string(), "Pixel (0, 20) is outside the 10x20 canvas");
+
+    // Rows and pixels come out in row order
+    let mut small = canvas(3, 2);
+    for y in 0..small.height() {
+        for x in 0..small.width() {
+            small.set_pixel(x, y, color(x as Float, y as Float, 0.0));
+        }
+    }
+    let rows: Vec<&[Color]> = small.rows().collect();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[1], &[color(0.0, 1.0, 0.0), color(1.0, 1.0, 0.0), color(2.0, 1.0, 0.0)][..]);
+    for row in small.rows_mut() {
+        row[0] = red;
+    }
+    assert_eq!(small.pixel_at(0, 1), red);
+    for p in small.pixels_mut() {
+        p.blue = 1.0;
+    }
+    assert_eq!(small.pixel_at(2, 0), color(2.0, 0.0, 1.0));
+
+    // fill and clear
+    small.fill(red);
+    assert!(small.pixels().all(|(_, _, p)| p == red));
+    small.clear();
+    assert_eq!(small, canvas(3, 2));
+    assert_eq!(canvas(0, 0).rows().count(), 0);
+}
+
+#[allow(dead_code)]
+pub fn ppm_tests() {
+    // Header
+    let c = canvas(5, 3);
+    assert!(c.to_ppm().starts_with("P3\n5 3\n255\n"));
+
+    // Pixel data is clamped and rounded
+    let mut c = canvas(5, 3);
+    c.set_pixel(0, 0, color(1.5, 0.0, 0.0));
+    c.set_pixel(2, 1, color(0.0, 0.5, 0.0));
+    c.set_pixel(4, 2, color(-0.5, 0.0, 1.0));
+    let ppm = c.to_ppm();
+    let lines: Vec<&str> = ppm.lines().skip(3).collect();
+    assert_eq!(lines, vec![
+        "255 0 0 0 0 0 0 0 0 0 0 0 0 0 0",
+        "0 0 0 0 0 0 0 128 0 0 0 0 0 0 0",
+        "0 0 0 0 0 0 0 0 0 0 0 0 0 0 255",
+    ]);
+    assert_eq!(channel_to_byte(Float::NAN), 0);
+    assert_eq!(channel_to_byte(0.2), 51);
+    assert_eq!(channel_to_byte(Float::INFINITY), 255);
+
+    // Long rows are split so no line passes 70 characters
+    let mut c = canvas(10, 2);
+    c.fill(color(1.0, 0.8, 0.6));
+    let ppm = c.to_ppm();
+    let lines: Vec<&str> = ppm.lines().skip(3).collect();
+    assert_eq!(lines, vec![
+        "255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204",
+        "153 255 204 153 255 204 153 255 204 153 255 204 153",
+        "255 204 153 255 204 153 255 204 153 255 204 153 255 204 153 255 204",
+        "153 255 204 153 255 204 153 255 204 153 255 204 153",
+    ]);
+    let mut wide = canvas(200, 1);
+    wide.fill(color(1.0, 1.0, 1.0));
+    assert!(wide.to_ppm().lines().all(|l| l.len() <= 70));
+
+    // Always ends with a newline, and io::Write targets get the same bytes
+    assert!(ppm.ends_with('\n'));
+    let mut buffer = Vec::new();
+    c.write_ppm(&mut buffer).expect("writing to a Vec can't fail");
+    assert_eq!(buffer, ppm.as_bytes());
+    assert_eq!(canvas(0, 0).to_ppm(), "P3\n0 0\n255\n");
+}
+
+#[allow(dead_code)]
+pub fn binary_ppm_tests() {
+    let mut c = canvas(2, 2);
+    c.set_pixel(0, 0, color(1.0, 0.5, 0.0));
+    c.set_pixel(1, 0, color(0.2, 2.0, -1.0));
+    c.set_pixel(1, 1, color(Float::NAN, 1.0 / 65535.0, 0.75));
+
+    // 8-bit samples
+    let mut bytes = Vec::new();
+    c.write_ppm_as(&mut bytes, PpmFormat::Binary8).expect("writing to a Vec can't fail");
+    let header_len = "P6\n2 2\n255\n".len();
+    assert_eq!(&bytes[..header_len], b"P6\n2 2\n255\n");
+    assert_eq!(&bytes[header_len..], &[255, 128, 0, 51, 255, 0, 0, 0, 0, 0, 0, 191][..]);
+
+    // 16-bit samples, big-endian
+    let mut bytes = Vec::new();
+    c.write_ppm_as(&mut bytes, PpmFormat::Binary16).expect("writing to a Vec can't fail");
+    let header_len = "P6\n2 2\n65535\n".len();
+    assert_eq!(&bytes[..header_len], b"P6\n2 2\n65535\n");
+    assert_eq!(bytes.len(), header_len + 2 * 2 * 3 * 2);
+    assert_eq!(&bytes[header_len..header_len + 6], &[0xff, 0xff, 0x80, 0x00, 0x00, 0x00][..]);
+    assert_eq!(&bytes[bytes.len() - 6..], &[0x00, 0x00, 0x00, 0x01, 0xbf, 0xff][..]);
+
+    // Plain is the existing P3 output
+    let mut plain = Vec::new();
+    c.write_ppm_as(&mut plain, PpmFormat::Plain).expect("writing to a Vec can't fail");
+    assert_eq!(plain, c.to_ppm().into_bytes());
+
+    // Every format reads back to the same image, within its precision
+    let dir = std::env::temp_dir();
+    for (format, step) in [(PpmFormat::Plain, 1.0 / 255.0), (PpmFormat::Binary8, 1.0 / 255.0), (PpmFormat::Binary16, 1.0 / 65535.0)] {
+        let path = dir.join(format!("hyperion_binary_ppm_{:?}.ppm", format));
+        let mut gradient = canvas(300, 7);
+        for y in 0..gradient.height() {
+            for x in 0..gradient.width() {
+                gradient.set_pixel(x, y, color(x as Float / 299.0, y as Float / 6.0, 0.3));
+            }
+        }
+        gradient.save_ppm(&path, format).expect("temp dir is writable");
+        let back = ppm::load_ppm(&path).expect("saved PPM parses");
+        let _ = fs::remove_file(&path);
+        assert_eq!((back.width(), back.height()), (300, 7));
+        for ((_, _, a), (_, _, b)) in gradient.pixels().zip(back.pixels()) {
+            for (p, q) in [(a.red, b.red), (a.green, b.green), (a.blue, b.blue)] {
+                assert!((p - q).abs() <= step / 2.0 + 1e-6);
+            }
+        }
+    }
+    assert!(canvas(1, 1).save_ppm("no_such_dir/x.ppm", PpmFormat::Binary8).is_err());
+}
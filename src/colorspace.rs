@@ -0,0 +1,339 @@
+/*
+Colour spaces
+-------------
+A Color is always linear RGB with the sRGB / Rec. 709 primaries and a D65
+white point; that is what the renderer adds and multiplies. Everything in
+here converts to and from that:
+
+    sRGB        the gamma-encoded values in image files and hex codes
+    HSV, HSL    hue in degrees (0..360), the rest 0..1, computed from the
+                sRGB-encoded values, the way colour pickers do it
+    CIE XYZ     D65, Y = luminance
+    CIE Lab     D65 reference white, L 0..100
+    blackbody   colour of an ideal radiator at a temperature in Kelvin
+
+Conversions go through From, e.g. Color::from(Hsv{h: 30.0, s: 0.8, v: 1.0})
+or Lab::from(c). Out-of-gamut results (a Lab colour no RGB display can
+show, say) come back with channels outside 0..1; nothing is clamped.
+*/
+
+use super::tonemap::{linear_to_srgb, srgb_to_linear};
+use super::tuples::Float;
+use super::{color, to_f64, Color};
+
+// linear sRGB <-> XYZ, D65
+const RGB_TO_XYZ:[[Float; 3]; 3] = [
+    [0.412_456_4, 0.357_576_1, 0.180_437_5],
+    [0.212_672_9, 0.715_152_2, 0.072_175],
+    [0.019_333_9, 0.119_192, 0.950_304_1],
+];
+const XYZ_TO_RGB:[[Float; 3]; 3] = [
+    [3.240_454_2, -1.537_138_5, -0.498_531_4],
+    [-0.969_266, 1.876_010_8, 0.041_556],
+    [0.055_643_4, -0.204_025_9, 1.057_225_2],
+];
+const D65:[Float; 3] = [0.950_47, 1.0, 1.088_83];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hsv {
+    pub h:Float,
+    pub s:Float,
+    pub v:Float,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hsl {
+    pub h:Float,
+    pub s:Float,
+    pub l:Float,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Xyz {
+    pub x:Float,
+    pub y:Float,
+    pub z:Float,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Lab {
+    pub l:Float,
+    pub a:Float,
+    pub b:Float,
+}
+
+impl Color {
+    pub fn luminance(self) -> Float {
+        // relative luminance (the Y of XYZ) of linear Rec. 709 RGB
+        0.212_672_9 * self.red + 0.715_152_2 * self.green + 0.072_175 * self.blue
+    }
+
+    pub fn to_srgb(self) -> Color {
+        // linear -> gamma encoded, channel by channel
+        color(linear_to_srgb(self.red), linear_to_srgb(self.green), linear_to_srgb(self.blue))
+    }
+
+    pub fn from_srgb(encoded:Color) -> Color {
+        color(srgb_to_linear(encoded.red), srgb_to_linear(encoded.green), srgb_to_linear(encoded.blue))
+    }
+
+    pub fn from_srgb8(r:u8, g:u8, b:u8) -> Color {
+        Color::from_srgb(color(r as Float / 255.0, g as Float / 255.0, b as Float / 255.0))
+    }
+
+    pub fn from_hex(hex:&str) -> Option<Color> {
+        // "#rrggbb" or "rrggbb", sRGB encoded
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        if digits.len() != 6 || !digits.is_ascii() {
+            return None;
+        }
+        let byte = |i:usize| u8::from_str_radix(&digits[i..i + 2], 16).ok();
+        Some(Color::from_srgb8(byte(0)?, byte(2)?, byte(4)?))
+    }
+}
+
+fn hue(rgb:Color, max:Float, chroma:Float) -> Float {
+    // degrees around the hexagon: red 0, green 120, blue 240
+    if chroma == 0.0 {
+        return 0.0;
+    }
+    let sector = if max == rgb.red {
+        ((rgb.green - rgb.blue) / chroma).rem_euclid(6.0)
+    } else if max == rgb.green {
+        (rgb.blue - rgb.red) / chroma + 2.0
+    } else {
+        (rgb.red - rgb.green) / chroma + 4.0
+    };
+    60.0 * sector
+}
+
+fn from_hue(h:Float, chroma:Float, m:Float) -> Color {
+    let sector = h.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector.rem_euclid(2.0) - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    color(r + m, g + m, b + m)
+}
+
+impl From<Color> for Hsv {
+    fn from(c:Color) -> Hsv {
+        let rgb = c.to_srgb();
+        let max = rgb.red.max(rgb.green).max(rgb.blue);
+        let min = rgb.red.min(rgb.green).min(rgb.blue);
+        let chroma = max - min;
+        Hsv{h: hue(rgb, max, chroma), s: if max == 0.0 { 0.0 } else { chroma / max }, v: max}
+    }
+}
+
+impl From<Hsv> for Color {
+    fn from(hsv:Hsv) -> Color {
+        let chroma = hsv.v * hsv.s;
+        Color::from_srgb(from_hue(hsv.h, chroma, hsv.v - chroma))
+    }
+}
+
+impl From<Color> for Hsl {
+    fn from(c:Color) -> Hsl {
+        let rgb = c.to_srgb();
+        let max = rgb.red.max(rgb.green).max(rgb.blue);
+        let min = rgb.red.min(rgb.green).min(rgb.blue);
+        let chroma = max - min;
+        let l = (max + min) / 2.0;
+        let s = if chroma == 0.0 { 0.0 } else { chroma / (1.0 - (2.0 * l - 1.0).abs()) };
+        Hsl{h: hue(rgb, max, chroma), s, l}
+    }
+}
+
+impl From<Hsl> for Color {
+    fn from(hsl:Hsl) -> Color {
+        let chroma = (1.0 - (2.0 * hsl.l - 1.0).abs()) * hsl.s;
+        Color::from_srgb(from_hue(hsl.h, chroma, hsl.l - chroma / 2.0))
+    }
+}
+
+fn mat3(m:&[[Float; 3]; 3], v:[Float; 3]) -> [Float; 3] {
+    m.map(|row| row[0] * v[0] + row[1] * v[1] + row[2] * v[2])
+}
+
+impl From<Color> for Xyz {
+    fn from(c:Color) -> Xyz {
+        let [x, y, z] = mat3(&RGB_TO_XYZ, [c.red, c.green, c.blue]);
+        Xyz{x, y, z}
+    }
+}
+
+impl From<Xyz> for Color {
+    fn from(xyz:Xyz) -> Color {
+        let [r, g, b] = mat3(&XYZ_TO_RGB, [xyz.x, xyz.y, xyz.z]);
+        color(r, g, b)
+    }
+}
+
+// Lab's cube root with a linear segment near black, and its inverse
+const DELTA:Float = 6.0 / 29.0;
+
+fn lab_f(t:Float) -> Float {
+    if t > DELTA * DELTA * DELTA { t.cbrt() } else { t / (3.0 * DELTA * DELTA) + 4.0 / 29.0 }
+}
+
+fn lab_f_inverse(t:Float) -> Float {
+    if t > DELTA { t * t * t } else { 3.0 * DELTA * DELTA * (t - 4.0 / 29.0) }
+}
+
+impl From<Xyz> for Lab {
+    fn from(xyz:Xyz) -> Lab {
+        let (fx, fy, fz) = (lab_f(xyz.x / D65[0]), lab_f(xyz.y / D65[1]), lab_f(xyz.z / D65[2]));
+        Lab{l: 116.0 * fy - 16.0, a: 500.0 * (fx - fy), b: 200.0 * (fy - fz)}
+    }
+}
+
+impl From<Lab> for Xyz {
+    fn from(lab:Lab) -> Xyz {
+        let fy = (lab.l + 16.0) / 116.0;
+        Xyz {
+            x: D65[0] * lab_f_inverse(fy + lab.a / 500.0),
+            y: D65[1] * lab_f_inverse(fy),
+            z: D65[2] * lab_f_inverse(fy - lab.b / 200.0),
+        }
+    }
+}
+
+impl From<Color> for Lab {
+    fn from(c:Color) -> Lab {
+        Lab::from(Xyz::from(c))
+    }
+}
+
+impl From<Lab> for Color {
+    fn from(lab:Lab) -> Color {
+        Color::from(Xyz::from(lab))
+    }
+}
+
+fn lobe(wavelength:f64, mean:f64, below:f64, above:f64) -> f64 {
+    let t = (wavelength - mean) / if wavelength < mean { below } else { above };
+    (-0.5 * t * t).exp()
+}
+
+fn cie_observer(wavelength:f64) -> [f64; 3] {
+    // CIE 1931 2-degree colour matching functions, as the multi-lobe
+    // Gaussian fit of Wyman, Sloan and Shirley (2013); wavelength in nm
+    let w = wavelength;
+    [
+        1.056 * lobe(w, 599.8, 37.9, 31.0) + 0.362 * lobe(w, 442.0, 16.0, 26.7) - 0.065 * lobe(w, 501.1, 20.4, 26.2),
+        0.821 * lobe(w, 568.8, 46.9, 40.5) + 0.286 * lobe(w, 530.9, 16.3, 31.1),
+        1.217 * lobe(w, 437.0, 11.8, 36.0) + 0.681 * lobe(w, 459.0, 26.0, 13.8),
+    ]
+}
+
+pub fn blackbody(kelvin:Float) -> Color {
+    // Planck's law integrated against the observer over 380..780 nm, then
+    // scaled so the brightest channel is 1. Below about 1900 K the result
+    // is outside the sRGB gamut; negative channels are set to 0. Anything
+    // under 100 K (invisible anyway) is treated as 100 K.
+    let kelvin = to_f64(kelvin).max(100.0);
+    let second_radiation = 1.438_776_877e-2; // hc/k, in m K
+    let mut xyz = [0.0f64; 3];
+    for step in 0..=80 {
+        let nm = 380.0 + 5.0 * step as f64;
+        let metres = nm * 1e-9;
+        let radiance = 1.0 / (metres.powi(5) * ((second_radiation / (metres * kelvin)).exp_m1()));
+        for (total, cmf) in xyz.iter_mut().zip(cie_observer(nm)) {
+            *total += radiance * cmf;
+        }
+    }
+    let c = Color::from(Xyz{x: (xyz[0] / xyz[1]) as Float, y: 1.0, z: (xyz[2] / xyz[1]) as Float});
+    let c = color(c.red.max(0.0), c.green.max(0.0), c.blue.max(0.0));
+    c / c.red.max(c.green).max(c.blue)
+}
+
+#[allow(dead_code)]
+pub fn colorspace_tests() {
+    use super::tuples::approx::{assert_approx_eq, ApproxEq, Tolerance};
+    let close = |a:Float, b:Float, tol:Float| (a - b).abs() <= tol;
+    let white = color(1.0, 1.0, 1.0);
+    let black = color(0.0, 0.0, 0.0);
+
+    // Luminance
+    assert!(close(white.luminance(), 1.0, 1e-5));
+    assert_eq!(black.luminance(), 0.0);
+    assert!(color(0.0, 1.0, 0.0).luminance() > color(1.0, 0.0, 0.0).luminance());
+    assert!(color(1.0, 0.0, 0.0).luminance() > color(0.0, 0.0, 1.0).luminance());
+
+    // sRGB and hex codes
+    assert_approx_eq!(color(0.5, 0.0, 1.0).to_srgb(), color(0.735_356_7, 0.0, 1.0));
+    assert_approx_eq!(Color::from_srgb(color(0.735_356_7, 0.0, 1.0)), color(0.5, 0.0, 1.0));
+    assert_approx_eq!(Color::from_hex("#ffffff").unwrap(), white);
+    assert_approx_eq!(Color::from_hex("000000").unwrap(), black);
+    assert_approx_eq!(Color::from_hex("#80ff00").unwrap(), color(0.215_860_5, 1.0, 0.0));
+    assert_eq!(Color::from_hex("#FF8000"), Color::from_hex("#ff8000"));
+    assert_eq!(Color::from_hex("#fff"), None);
+    assert_eq!(Color::from_hex("#gg0000"), None);
+    assert_eq!(Color::from_hex("#ff00é"), None);
+
+    // HSV and HSL of the primaries and secondaries
+    let hsv_cases = [
+        (color(1.0, 0.0, 0.0), 0.0),
+        (color(1.0, 1.0, 0.0), 60.0),
+        (color(0.0, 1.0, 0.0), 120.0),
+        (color(0.0, 1.0, 1.0), 180.0),
+        (color(0.0, 0.0, 1.0), 240.0),
+        (color(1.0, 0.0, 1.0), 300.0),
+    ];
+    for (c, h) in hsv_cases {
+        let hsv = Hsv::from(c);
+        assert!(close(hsv.h, h, 1e-3) && close(hsv.s, 1.0, 1e-5) && close(hsv.v, 1.0, 1e-5), "{:?}", hsv);
+        assert_approx_eq!(Color::from(hsv), c);
+        let hsl = Hsl::from(c);
+        assert!(close(hsl.h, h, 1e-3) && close(hsl.s, 1.0, 1e-5) && close(hsl.l, 0.5, 1e-5), "{:?}", hsl);
+        assert_approx_eq!(Color::from(hsl), c);
+    }
+    // greys have no hue or saturation; sRGB 50% grey is v = l = 0.5
+    let grey = Color::from_srgb(color(0.5, 0.5, 0.5));
+    assert_eq!(Hsv::from(grey).s, 0.0);
+    assert!(close(Hsv::from(grey).v, 0.5, 1e-5) && close(Hsl::from(grey).l, 0.5, 1e-5));
+    // an artist's orange: #ff8000 is hue 30, full saturation
+    let orange = Color::from_hex("#ff8000").unwrap();
+    assert!(close(Hsv::from(orange).h, 30.0, 0.3));
+    assert_approx_eq!(Color::from(Hsv{h: 390.0, s: 1.0, v: 1.0}), Color::from(Hsv{h: 30.0, s: 1.0, v: 1.0}));
+    for i in 0..50 {
+        let c = color((i as Float * 0.37).fract(), (i as Float * 0.71).fract(), (i as Float * 0.13).fract());
+        assert!(Color::from(Hsv::from(c)).approx_eq_with(&c, Tolerance::absolute(1e-4)));
+        assert!(Color::from(Hsl::from(c)).approx_eq_with(&c, Tolerance::absolute(1e-4)));
+    }
+
+    // XYZ and Lab
+    let xyz = Xyz::from(white);
+    assert!(close(xyz.x, D65[0], 1e-4) && close(xyz.y, 1.0, 1e-4) && close(xyz.z, D65[2], 1e-4));
+    assert_approx_eq!(Color::from(Xyz::from(color(0.2, 0.5, 0.9))), color(0.2, 0.5, 0.9));
+    let lab = Lab::from(white);
+    assert!(close(lab.l, 100.0, 1e-2) && close(lab.a, 0.0, 1e-2) && close(lab.b, 0.0, 1e-2));
+    assert!(close(Lab::from(black).l, 0.0, 1e-4));
+    // sRGB red is L 53.24, a 80.09, b 67.20
+    let red = Lab::from(color(1.0, 0.0, 0.0));
+    assert!(close(red.l, 53.24, 0.05) && close(red.a, 80.09, 0.05) && close(red.b, 67.20, 0.05), "{:?}", red);
+    for c in [color(0.2, 0.5, 0.9), color(0.001, 0.002, 0.0), color(0.9, 0.9, 0.1)] {
+        assert!(Color::from(Lab::from(c)).approx_eq_with(&c, Tolerance::absolute(1e-4)));
+    }
+
+    // Blackbody
+    assert_eq!(blackbody(6500.0).red.max(blackbody(6500.0).green).max(blackbody(6500.0).blue), 1.0);
+    let d65ish = blackbody(6504.0);
+    assert!(d65ish.red > 0.85 && d65ish.green > 0.85 && d65ish.blue > 0.85, "{:?}", d65ish);
+    let candle = blackbody(1900.0);
+    assert!(candle.red == 1.0 && candle.green < 0.6 && candle.blue < 0.1, "{:?}", candle);
+    let sky = blackbody(12000.0);
+    assert!(sky.blue == 1.0 && sky.red < 0.9, "{:?}", sky);
+    // warmer is redder all the way along
+    let temps:Vec<Color> = (10..=40).map(|k| blackbody(k as Float * 250.0)).collect();
+    assert!(temps.windows(2).all(|w| w[1].blue / w[1].red >= w[0].blue / w[0].red));
+    assert_eq!(blackbody(-5.0), blackbody(100.0));
+    let cold = blackbody(100.0);
+    assert!(cold.red.is_finite() && cold.green.is_finite() && cold.blue.is_finite());
+}
@@ -0,0 +1,389 @@
+/*
+Comparing canvases
+------------------
+Numbers for "did this change alter the render": compare() two canvases of
+the same size and get a Comparison with
+
+    max_error   largest absolute difference of any channel
+    mean_error  mean absolute difference over all channels
+    rmse        root mean square difference over all channels
+    psnr        10 log10(1 / mse) in dB, with 1.0 as the peak value
+                (infinite for identical images)
+    ssim        structural similarity of the luminance, averaged over 8x8
+                windows every 4 pixels (1.0 for identical images)
+
+plus how many pixels differ at all and where the worst one is. Channels
+are compared as stored, except SSIM, which clamps to 0..1 first since it
+models what a viewer would see. A NaN against a number counts as an
+infinite error, so broken pixels can't hide.
+
+Thresholds turn a Comparison into pass/fail. Thresholds::exact() allows
+nothing; Thresholds::eight_bit() allows the rounding of an 8-bit file,
+which is what comparing a fresh render against renders/ needs:
+
+    compare::assert_similar(&fresh, &load_ppm("renders/x.ppm")?, &Thresholds::eight_bit());
+
+heat_map() shows where two images differ: black where they agree, then
+red, yellow and white as the error approaches the given scale. A scale
+of 0 (say cmp.max_error of identical images) or a non-finite one makes
+any difference white.
+*/
+
+use std::fmt;
+
+use super::tuples::Float;
+use super::{canvas, color, Canvas, Color};
+
+const SSIM_WINDOW:usize = 8;
+const SSIM_STEP:usize = 4;
+// stabilising constants for a dynamic range of 1
+const SSIM_C1:Float = 0.01 * 0.01;
+const SSIM_C2:Float = 0.03 * 0.03;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SizeMismatch {
+    pub expected:(usize, usize),
+    pub found:(usize, usize),
+}
+
+impl fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Can't compare a {}x{} canvas with a {}x{} one",
+            self.found.0, self.found.1, self.expected.0, self.expected.1)
+    }
+}
+
+impl std::error::Error for SizeMismatch {}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Comparison {
+    pub max_error:Float,
+    pub mean_error:Float,
+    pub rmse:Float,
+    pub psnr:Float,
+    pub ssim:Float,
+    pub differing_pixels:usize,
+    // (x, y) of the pixel with the largest error
+    pub worst:(usize, usize),
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "max error {:.6} at {:?}, mean {:.6}, RMSE {:.6}, PSNR {:.2} dB, SSIM {:.5}, {} pixels differ",
+            self.max_error, self.worst, self.mean_error, self.rmse, self.psnr, self.ssim, self.differing_pixels)
+    }
+}
+
+fn channel_error(a:Float, b:Float) -> Float {
+    match (a.is_nan(), b.is_nan()) {
+        (false, false) => (a - b).abs(),
+        (true, true) => 0.0,
+        _ => Float::INFINITY,
+    }
+}
+
+fn pixel_errors(a:Color, b:Color) -> [Float; 3] {
+    [channel_error(a.red, b.red), channel_error(a.green, b.green), channel_error(a.blue, b.blue)]
+}
+
+fn check_sizes(actual:&Canvas, expected:&Canvas) -> Result<(), SizeMismatch> {
+    let (found, expected) = ((actual.width(), actual.height()), (expected.width(), expected.height()));
+    if found == expected { Ok(()) } else { Err(SizeMismatch{expected, found}) }
+}
+
+fn display_luminance(c:Color) -> Float {
+    let clamp = |v:Float| if v.is_nan() { 0.0 } else { v.clamp(0.0, 1.0) };
+    color(clamp(c.red), clamp(c.green), clamp(c.blue)).luminance()
+}
+
+fn ssim(a:&Canvas, b:&Canvas) -> Float {
+    let (width, height) = (a.width(), a.height());
+    if width == 0 || height == 0 {
+        return 1.0;
+    }
+    let la:Vec<Float> = a.as_slice().iter().map(|c| display_luminance(*c)).collect();
+    let lb:Vec<Float> = b.as_slice().iter().map(|c| display_luminance(*c)).collect();
+    // windows shrink to fit images smaller than one window
+    let (ww, wh) = (SSIM_WINDOW.min(width), SSIM_WINDOW.min(height));
+    let starts = |size:usize, window:usize| {
+        // every SSIM_STEP pixels, plus one flush with the far edge
+        let mut starts:Vec<usize> = (0..=size - window).step_by(SSIM_STEP).collect();
+        if starts.last() != Some(&(size - window)) {
+            starts.push(size - window);
+        }
+        starts
+    };
+
+    let mut total = 0.0;
+    let mut windows = 0;
+    for y0 in starts(height, wh) {
+        for x0 in starts(width, ww) {
+            let n = (ww * wh) as Float;
+            let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for y in y0..y0 + wh {
+                for i in y * width + x0..y * width + x0 + ww {
+                    let (p, q) = (la[i], lb[i]);
+                    sa += p;
+                    sb += q;
+                    saa += p * p;
+                    sbb += q * q;
+                    sab += p * q;
+                }
+            }
+            let (ma, mb) = (sa / n, sb / n);
+            let (va, vb, cov) = (saa / n - ma * ma, sbb / n - mb * mb, sab / n - ma * mb);
+            total += ((2.0 * ma * mb + SSIM_C1) * (2.0 * cov + SSIM_C2))
+                / ((ma * ma + mb * mb + SSIM_C1) * (va + vb + SSIM_C2));
+            windows += 1;
+        }
+    }
+    total / windows as Float
+}
+
+pub fn compare(actual:&Canvas, expected:&Canvas) -> Result<Comparison, SizeMismatch> {
+    check_sizes(actual, expected)?;
+    let mut max_error = 0.0;
+    let mut worst = (0, 0);
+    let (mut sum, mut sum_squares) = (0.0, 0.0);
+    let mut differing_pixels = 0;
+    for ((x, y, a), b) in actual.pixels().zip(expected.as_slice()) {
+        let errors = pixel_errors(a, *b);
+        for e in errors {
+            sum += e;
+            sum_squares += e * e;
+        }
+        let pixel_max = errors[0].max(errors[1]).max(errors[2]);
+        if pixel_max > 0.0 {
+            differing_pixels += 1;
+        }
+        if pixel_max > max_error {
+            max_error = pixel_max;
+            worst = (x, y);
+        }
+    }
+    let samples = (actual.as_slice().len() * 3).max(1) as Float;
+    let mse = sum_squares / samples;
+    Ok(Comparison {
+        max_error,
+        mean_error: sum / samples,
+        rmse: mse.sqrt(),
+        psnr: if mse == 0.0 { Float::INFINITY } else { -10.0 * mse.log10() },
+        ssim: ssim(actual, expected),
+        differing_pixels,
+        worst,
+    })
+}
+
+pub fn difference(actual:&Canvas, expected:&Canvas) -> Result<Canvas, SizeMismatch> {
+    // |actual - expected| per channel
+    check_sizes(actual, expected)?;
+    let mut out = canvas(actual.width(), actual.height());
+    for (pixel, (a, b)) in out.pixels_mut().zip(actual.as_slice().iter().zip(expected.as_slice())) {
+        let [r, g, b] = pixel_errors(*a, *b);
+        *pixel = color(r, g, b);
+    }
+    Ok(out)
+}
+
+pub fn heat_map(actual:&Canvas, expected:&Canvas, scale:Float) -> Result<Canvas, SizeMismatch> {
+    // each pixel's largest channel error over scale, as black -> red ->
+    // yellow -> white
+    let mut out = difference(actual, expected)?;
+    let usable = scale > 0.0 && scale.is_finite();
+    for pixel in out.pixels_mut() {
+        let error = pixel.red.max(pixel.green).max(pixel.blue);
+        let t = if usable {
+            (error / scale).clamp(0.0, 1.0) * 3.0
+        } else if error > 0.0 {
+            3.0
+        } else {
+            0.0
+        };
+        *pixel = color(t.min(1.0), (t - 1.0).clamp(0.0, 1.0), (t - 2.0).clamp(0.0, 1.0));
+    }
+    Ok(out)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Thresholds {
+    pub max_error:Float,
+    pub rmse:Float,
+    pub min_psnr:Float,
+    pub min_ssim:Float,
+}
+
+impl Thresholds {
+    pub fn exact() -> Thresholds {
+        Thresholds{max_error: 0.0, rmse: 0.0, min_psnr: Float::INFINITY, min_ssim: 1.0}
+    }
+
+    pub fn eight_bit() -> Thresholds {
+        // rounding to 8 bits moves a channel by at most half a step
+        let step = 1.0 / 255.0;
+        Thresholds{max_error: step / 2.0 + 1e-6, rmse: step / 2.0, min_psnr: 0.0, min_ssim: 0.999}
+    }
+
+    pub fn max_error(self, max_error:Float) -> Thresholds {
+        Thresholds{max_error, ..self}
+    }
+
+    pub fn rmse(self, rmse:Float) -> Thresholds {
+        Thresholds{rmse, ..self}
+    }
+
+    pub fn min_psnr(self, min_psnr:Float) -> Thresholds {
+        Thresholds{min_psnr, ..self}
+    }
+
+    pub fn min_ssim(self, min_ssim:Float) -> Thresholds {
+        Thresholds{min_ssim, ..self}
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThresholdFailure {
+    pub comparison:Comparison,
+    // one line per metric over its limit
+    pub failures:Vec<String>,
+}
+
+impl fmt::Display for ThresholdFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Canvases differ: {} ({})", self.failures.join(", "), self.comparison)
+    }
+}
+
+impl std::error::Error for ThresholdFailure {}
+
+impl Comparison {
+    #[allow(clippy::neg_cmp_op_on_partial_ord)]
+    pub fn check(&self, limits:&Thresholds) -> Result<(), ThresholdFailure> {
+        let mut failures = Vec::new();
+        // written so that NaN metrics fail too
+        if !(self.max_error <= limits.max_error) {
+            failures.push(format!("max error {} > {}", self.max_error, limits.max_error));
+        }
+        if !(self.rmse <= limits.rmse) {
+            failures.push(format!("RMSE {} > {}", self.rmse, limits.rmse));
+        }
+        if !(self.psnr >= limits.min_psnr) {
+            failures.push(format!("PSNR {} dB < {} dB", self.psnr, limits.min_psnr));
+        }
+        if !(self.ssim >= limits.min_ssim) {
+            failures.push(format!("SSIM {} < {}", self.ssim, limits.min_ssim));
+        }
+        if failures.is_empty() { Ok(()) } else { Err(ThresholdFailure{comparison: *self, failures}) }
+    }
+
+    pub fn passes(&self, limits:&Thresholds) -> bool {
+        self.check(limits).is_ok()
+    }
+}
+
+pub fn assert_similar(actual:&Canvas, expected:&Canvas, limits:&Thresholds) {
+    // panics with every failing metric, for use in the *_tests functions
+    match compare(actual, expected) {
+        Ok(comparison) => {
+            if let Err(failure) = comparison.check(limits) {
+                panic!("{}", failure);
+            }
+        }
+        Err(mismatch) => panic!("{}", mismatch),
+    }
+}
+
+#[allow(dead_code)]
+pub fn compare_tests() {
+    let close = |a:Float, b:Float| (a - b).abs() < 1e-4;
+
+    // A test image: gradients with some detail
+    let mut image = canvas(32, 24);
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let (u, v) = (x as Float / 31.0, y as Float / 23.0);
+            image.set_pixel(x, y, color(u, v, if (x / 4 + y / 4) % 2 == 0 { 0.8 } else { 0.2 }));
+        }
+    }
+
+    // Identical
+    let same = compare(&image, &image.clone()).unwrap();
+    assert_eq!((same.max_error, same.mean_error, same.rmse, same.differing_pixels), (0.0, 0.0, 0.0, 0));
+    assert_eq!(same.psnr, Float::INFINITY);
+    assert!(close(same.ssim, 1.0));
+    assert!(same.passes(&Thresholds::exact()));
+    assert_similar(&image, &image, &Thresholds::exact());
+
+    // A uniform offset
+    let (mut black, mut grey) = (canvas(4, 4), canvas(4, 4));
+    black.fill(color(0.0, 0.0, 0.0));
+    grey.fill(color(0.5, 0.5, 0.5));
+    let offset = compare(&black, &grey).unwrap();
+    assert_eq!((offset.max_error, offset.mean_error, offset.rmse, offset.differing_pixels), (0.5, 0.5, 0.5, 16));
+    assert!(close(offset.psnr, 6.0206));
+    assert!(offset.ssim < 0.5);
+
+    // One bad pixel
+    let mut edited = image.clone();
+    edited.set_pixel(7, 5, color(1.0, 0.0, 1.0));
+    let before = image.pixel_at(7, 5);
+    let one = compare(&edited, &image).unwrap();
+    assert_eq!((one.differing_pixels, one.worst), (1, (7, 5)));
+    let errors = [1.0 - before.red, before.green, 1.0 - before.blue];
+    assert!(close(one.max_error, errors[0].max(errors[1]).max(errors[2])));
+    assert!(close(one.rmse, (errors.iter().map(|e| e * e).sum::<Float>() / (32.0 * 24.0 * 3.0)).sqrt()));
+    assert!(one.ssim < 1.0 && one.ssim > 0.9);
+    assert!(!one.passes(&Thresholds::exact()) && one.passes(&Thresholds::exact().max_error(1.0).rmse(0.1).min_psnr(20.0).min_ssim(0.9)));
+
+    // SSIM notices structure that RMSE weighs the same: noise vs a shift
+    let mut noisy = image.clone();
+    let mut brighter = image.clone();
+    for (i, (n, b)) in noisy.pixels_mut().zip(brighter.pixels_mut()).enumerate() {
+        let d = if i % 2 == 0 { 0.1 } else { -0.1 };
+        *n = color(n.red + d, n.green + d, n.blue + d);
+        *b = color(b.red + 0.1, b.green + 0.1, b.blue + 0.1);
+    }
+    let (n, b) = (compare(&noisy, &image).unwrap(), compare(&brighter, &image).unwrap());
+    assert!(close(n.rmse, b.rmse));
+    assert!(n.ssim < b.ssim, "{} vs {}", n.ssim, b.ssim);
+    // windows shrink for tiny images
+    assert!(close(compare(&canvas(3, 2), &canvas(3, 2)).unwrap().ssim, 1.0));
+
+    // NaN can't hide
+    let mut broken = image.clone();
+    broken.set_pixel(0, 0, color(Float::NAN, 0.0, 0.0));
+    let nan = compare(&broken, &image).unwrap();
+    assert_eq!(nan.max_error, Float::INFINITY);
+    assert!(!nan.passes(&Thresholds::exact().max_error(Float::INFINITY)));
+    assert!(compare(&broken, &broken).unwrap().passes(&Thresholds::exact()));
+
+    // Difference and heat map
+    let diff = difference(&edited, &image).unwrap();
+    assert_eq!(diff.pixel_at(7, 5), color(errors[0], errors[1], errors[2]));
+    assert_eq!(diff.pixel_at(0, 0), color(0.0, 0.0, 0.0));
+    let heat = heat_map(&edited, &image, one.max_error).unwrap();
+    assert_eq!(heat.pixel_at(7, 5), color(1.0, 1.0, 1.0));
+    assert_eq!(heat.pixel_at(8, 5), color(0.0, 0.0, 0.0));
+    let half = heat_map(&black, &grey, 1.0).unwrap();
+    assert!(close(half.pixel_at(0, 0).red, 1.0) && close(half.pixel_at(0, 0).green, 0.5) && half.pixel_at(0, 0).blue == 0.0);
+    // no usable scale: black where equal, white wherever they differ
+    let flat = heat_map(&image, &image, same.max_error).unwrap();
+    assert!(flat.pixels().all(|(_, _, p)| p == color(0.0, 0.0, 0.0)));
+    for scale in [0.0, -1.0, Float::NAN, Float::INFINITY] {
+        let heat = heat_map(&edited, &image, scale).unwrap();
+        assert_eq!(heat.pixel_at(7, 5), color(1.0, 1.0, 1.0));
+        assert_eq!(heat.pixel_at(8, 5), color(0.0, 0.0, 0.0));
+    }
+
+    // Against an 8-bit file, as a regression test would
+    let reloaded = super::ppm::parse_ppm(image.to_ppm().as_bytes()).unwrap();
+    assert_similar(&image, &reloaded, &Thresholds::eight_bit());
+    assert!(!compare(&image, &reloaded).unwrap().passes(&Thresholds::exact()));
+
+    // Failures say what went wrong
+    let failure = one.check(&Thresholds::eight_bit()).unwrap_err();
+    assert!(failure.failures.len() >= 2 && failure.failures[0].starts_with("max error"));
+    let mismatch = compare(&image, &canvas(3, 3)).unwrap_err();
+    assert_eq!(mismatch, SizeMismatch{expected: (3, 3), found: (32, 24)});
+    assert!(heat_map(&image, &canvas(3, 3), 1.0).is_err());
+    assert!(failure.to_string().starts_with("Canvases differ: max error"));
+}
@@ -0,0 +1,188 @@
+/*
+Matrix decomposition
+--------------------
+Splits an affine transform back into the pieces it could have been built
+from:
+
+    M = translation * rotation * shear * scaling
+
+where shear = shearing(xy, xz, 0.0, yz, 0.0, 0.0). The upper-left 3x3
+block is split with Gram-Schmidt on its columns, i.e. a QR decomposition
+(Q = rotation, R = shear * scale). Every invertible affine matrix has
+exactly one such split with a proper rotation. A mirror shows up as a
+negative z scale.
+
+Round trip: decompose(m)?.recompose() == m up to rounding, whatever order
+the original translation/rotation/scaling/shearing calls were composed in.
+If m was built in the order above, the individual factors come back too.
+*/
+
+use std::fmt;
+
+use super::quaternion::Quaternion;
+use super::tuples::{self, Float, Vector3};
+use super::{multiply_matrix, scaling, shearing, translation, Matrix};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Shear {
+    pub xy:Float,
+    pub xz:Float,
+    pub yz:Float,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Decomposition {
+    pub translation:Vector3,
+    pub rotation:Matrix,
+    pub quaternion:Quaternion,
+    pub scale:Vector3,
+    pub shear:Shear,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DecomposeError {
+    // bottom row isn't (0, 0, 0, 1), so there is a projective part
+    NotAffine,
+    // some axis is scaled to (nearly) zero
+    Singular,
+}
+
+impl fmt::Display for DecomposeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecomposeError::NotAffine => write!(f, "Matrix is not affine (bottom row is not 0 0 0 1)"),
+            DecomposeError::Singular => write!(f, "Matrix is singular (an axis is scaled to zero)"),
+        }
+    }
+}
+
+impl std::error::Error for DecomposeError {}
+
+pub fn decompose(m:Matrix) -> Result<Decomposition, DecomposeError> {
+    if !super::is_affine(m) {
+        return Err(DecomposeError::NotAffine);
+    }
+    let a = m.0;
+
+    let column = |j:usize| tuples::vector3(a[0][j], a[1][j], a[2][j]);
+    let (c0, c1, c2) = (column(0), column(1), column(2));
+    let tiny = Float::EPSILON * (c0.magnitude() + c1.magnitude() + c2.magnitude());
+
+    // Gram-Schmidt, keeping the projections as the shear terms
+    let sx = c0.magnitude();
+    if sx <= tiny {
+        return Err(DecomposeError::Singular);
+    }
+    let r0 = c0 / sx;
+
+    let u01 = r0.dot(c1);
+    let c1_perp = c1 - u01 * r0;
+    let sy = c1_perp.magnitude();
+    if sy <= tiny {
+        return Err(DecomposeError::Singular);
+    }
+    let r1 = c1_perp / sy;
+
+    let u02 = r0.dot(c2);
+    let u12 = r1.dot(c2);
+    let c2_perp = c2 - u02 * r0 - u12 * r1;
+    let mut sz = c2_perp.magnitude();
+    if sz <= tiny {
+        return Err(DecomposeError::Singular);
+    }
+    let mut r2 = c2_perp / sz;
+
+    // keep the rotation proper; the reflection moves into the z scale
+    if r0.cross(r1).dot(r2) < 0.0 {
+        r2 = -r2;
+        sz = -sz;
+    }
+
+    let rotation = Matrix([
+        [r0.x,r1.x,r2.x,0.0],
+        [r0.y,r1.y,r2.y,0.0],
+        [r0.z,r1.z,r2.z,0.0],
+        [0.0,0.0,0.0,1.0]
+    ]);
+
+    Ok(Decomposition {
+        translation: tuples::vector3(a[0][3], a[1][3], a[2][3]),
+        rotation,
+        quaternion: Quaternion::from_matrix(rotation),
+        scale: tuples::vector3(sx, sy, sz),
+        shear: Shear { xy: u01 / sy, xz: u02 / sz, yz: u12 / sz },
+    })
+}
+
+impl Decomposition {
+    pub fn shear_matrix(&self) -> Matrix {
+        shearing(self.shear.xy, self.shear.xz, 0.0, self.shear.yz, 0.0, 0.0)
+    }
+
+    pub fn recompose(&self) -> Matrix {
+        let t = self.translation;
+        let s = self.scale;
+        multiply_matrix(
+            multiply_matrix(translation(t.x, t.y, t.z), self.rotation),
+            multiply_matrix(self.shear_matrix(), scaling(s.x, s.y, s.z))
+        )
+    }
+}
+
+#[allow(dead_code)]
+pub fn decompose_tests() {
+    use super::tuples::approx::{assert_approx_eq, ApproxEq, Tolerance};
+    let tol = Tolerance::absolute(1e-4);
+
+    // Built in canonical order: every factor comes back
+    let t = translation(1.0,-2.0,3.0);
+    let r = super::rotation(tuples::vector3(1.0,2.0,-1.0), 0.8);
+    let h = shearing(0.5,-0.25,0.0,0.75,0.0,0.0);
+    let s = scaling(2.0,3.0,0.5);
+    let m = multiply_matrix(multiply_matrix(t, r), multiply_matrix(h, s));
+    let d = decompose(m).expect("m is affine and invertible");
+    assert_approx_eq!(d.translation, tuples::vector3(1.0,-2.0,3.0), tol);
+    assert_approx_eq!(d.rotation, r, tol);
+    assert_approx_eq!(d.quaternion.to_matrix(), r, tol);
+    assert_approx_eq!(d.scale, tuples::vector3(2.0,3.0,0.5), tol);
+    assert!((d.shear.xy - 0.5).abs() < 1e-4);
+    assert!((d.shear.xz + 0.25).abs() < 1e-4);
+    assert!((d.shear.yz - 0.75).abs() < 1e-4);
+    assert_approx_eq!(d.shear_matrix(), h, tol);
+    assert_approx_eq!(d.recompose(), m, tol);
+
+    // Simple transforms decompose to themselves
+    let d = decompose(translation(5.0,6.0,7.0)).unwrap();
+    assert_approx_eq!(d.rotation, super::identity(), tol);
+    assert_approx_eq!(d.translation, tuples::vector3(5.0,6.0,7.0), tol);
+    let d = decompose(super::rotation_y(1.0)).unwrap();
+    assert_approx_eq!(d.rotation, super::rotation_y(1.0), tol);
+    assert_approx_eq!(d.scale, tuples::vector3(1.0,1.0,1.0), tol);
+
+    // Any composition order round-trips, even when the factors differ
+    let compositions = [
+        multiply_matrix(s, r),
+        multiply_matrix(multiply_matrix(r, t), s),
+        multiply_matrix(multiply_matrix(shearing(1.0,0.0,0.5,0.0,0.2,1.0), super::rotation_x(2.0)), t),
+        multiply_matrix(multiply_matrix(scaling(-1.0,1.0,1.0), super::rotation_z(0.3)), t),
+        super::Transform::identity().rotate_x(0.4).scale(1.0,4.0,2.0).shear(0.0,1.0,0.0,0.0,0.3,0.0).translate(9.0,0.0,-1.0).build(),
+    ];
+    for m in compositions {
+        let d = decompose(m).expect("composition is affine and invertible");
+        assert_approx_eq!(d.recompose(), m, tol);
+        // rotation really is a rotation
+        assert_approx_eq!(multiply_matrix(d.rotation, super::transpose(d.rotation)), super::identity(), tol);
+        assert!((super::determinant(d.rotation) - 1.0).abs() < 1e-4);
+    }
+
+    // A mirror becomes a negative z scale
+    let d = decompose(scaling(1.0,1.0,-2.0)).unwrap();
+    assert_approx_eq!(d.scale, tuples::vector3(1.0,1.0,-2.0), tol);
+
+    // Errors
+    assert_eq!(decompose(scaling(1.0,0.0,1.0)), Err(DecomposeError::Singular));
+    let mut projective = super::identity();
+    projective.0[3][2] = 1.0;
+    assert_eq!(decompose(projective), Err(DecomposeError::NotAffine));
+    assert_eq!(DecomposeError::Singular.to_string(), "Matrix is singular (an axis is scaled to zero)");
+}
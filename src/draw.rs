@@ -0,0 +1,511 @@
+/*
+2D drawing
+----------
+Lines, circles, ellipses, rectangles and polygons on a Canvas, for plots
+and overlays. Every primitive clips against the canvas: any coordinates
+are fine, including ones far outside it (long lines are clipped before
+they are rasterised, so they cost what their visible part costs). Float
+coordinates that are NaN or infinite have no place to clip to, so a shape
+with any of them draws nothing.
+
+Integer coordinates name pixels, (0, 0) top left. Float coordinates put
+pixel centres on whole numbers, so (3.0, 2.0) is the middle of pixel
+(3, 2) and pixel (x, y) covers x-0.5..x+0.5.
+
+    draw_line         Bresenham, one pixel wide, both ends included
+    draw_line_aa      Xiaolin Wu's anti-aliased line, blended over
+    draw_thick_line   a filled rectangle around the segment (butt ends)
+    draw_rect         outline of w x h pixels; fill_rect fills them
+    fill_ellipse      pixels whose centres are inside the ellipse with
+                      radii rx + 1/2, ry + 1/2; fill_circle for rx = ry
+    draw_ellipse      the edge of that: filled pixels with a 4-neighbour
+                      outside, 8-connected; draw_circle for rx = ry
+    draw_polygon      closed outline through the points
+    fill_polygon      even-odd fill: a pixel is inside if its centre is
+
+Nothing but blend_pixel and draw_line_aa blends; everything else
+overwrites pixels with the colour.
+*/
+
+use super::tuples::Float;
+use super::{to_f64, Canvas, Color};
+
+pub(super) fn clip_segment(p0:(f64, f64), p1:(f64, f64), min:(f64, f64), max:(f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+    // Liang-Barsky: the part of p0..p1 inside the box, if any
+    let (dx, dy) = (p1.0 - p0.0, p1.1 - p0.1);
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    for (p, q) in [(-dx, p0.0 - min.0), (dx, max.0 - p0.0), (-dy, p0.1 - min.1), (dy, max.1 - p0.1)] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let t = q / p;
+            if p < 0.0 {
+                t0 = t0.max(t);
+            } else {
+                t1 = t1.min(t);
+            }
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+    Some(((p0.0 + t0 * dx, p0.1 + t0 * dy), (p0.0 + t1 * dx, p0.1 + t1 * dy)))
+}
+
+fn span(rx:usize, ry:usize, dy:isize) -> Option<isize> {
+    // half-width of the ellipse's row dy, or None past the top or bottom;
+    // pixels are in if their centre is within (rx + 1/2, ry + 1/2)
+    let dy = dy.unsigned_abs();
+    if dy > ry {
+        return None;
+    }
+    let t = dy as f64 / (ry as f64 + 0.5);
+    let half = ((rx as f64 + 0.5) * (1.0 - t * t).sqrt()).floor();
+    // rounding can't take it past rx
+    Some((half as usize).min(rx).min(isize::MAX as usize) as isize)
+}
+
+fn last(start:isize, len:usize) -> isize {
+    // start + len - 1 for len > 0, saturating
+    start.saturating_add(isize::try_from(len - 1).unwrap_or(isize::MAX))
+}
+
+fn all_finite(points:&[(Float, Float)]) -> bool {
+    points.iter().all(|(x, y)| x.is_finite() && y.is_finite())
+}
+
+impl Canvas {
+    pub fn blend_pixel(&mut self, x:isize, y:isize, colour:Color, alpha:Float) -> bool {
+        // colour over the pixel with coverage alpha (clamped to 0..1, and
+        // NaN counts as no coverage)
+        if x < 0 || y < 0 {
+            return false;
+        }
+        let alpha = if alpha.is_nan() { 0.0 } else { alpha.clamp(0.0, 1.0) };
+        match self.pixel_at_checked(x as usize, y as usize) {
+            Some(old) => self.set_pixel_clipped(x, y, old * (1.0 - alpha) + colour * alpha),
+            None => false,
+        }
+    }
+
+    fn hline(&mut self, x0:isize, x1:isize, y:isize, colour:Color) {
+        // x0..=x1 on row y, clipped
+        if y < 0 || y >= self.height() as isize {
+            return;
+        }
+        let (a, b) = (x0.min(x1).max(0), x0.max(x1).min(self.width() as isize - 1));
+        for x in a..=b {
+            self.set_pixel(x as usize, y as usize, colour);
+        }
+    }
+
+    pub fn draw_line(&mut self, x0:isize, y0:isize, x1:isize, y1:isize, colour:Color) {
+        let (w, h) = (self.width() as isize, self.height() as isize);
+        let inside = |x:isize, y:isize| x >= 0 && y >= 0 && x < w && y < h;
+        let (mut x0, mut y0, mut x1, mut y1) = (x0 as i64, y0 as i64, x1 as i64, y1 as i64);
+        if !inside(x0 as isize, y0 as isize) || !inside(x1 as isize, y1 as isize) {
+            // clip to a pixel beyond the edges; rounding keeps the slope
+            // close enough that the visible part lands on the same pixels
+            let margin = (-1.0, -1.0);
+            let far = (w as f64, h as f64);
+            match clip_segment((x0 as f64, y0 as f64), (x1 as f64, y1 as f64), margin, far) {
+                Some((a, b)) => {
+                    (x0, y0, x1, y1) = (a.0.round() as i64, a.1.round() as i64, b.0.round() as i64, b.1.round() as i64);
+                }
+                None => return,
+            }
+        }
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (sx, sy) = (if x0 < x1 { 1 } else { -1 }, if y0 < y1 { 1 } else { -1 });
+        let mut err = dx + dy;
+        loop {
+            self.set_pixel_clipped(x0 as isize, y0 as isize, colour);
+            if x0 == x1 && y0 == y1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x0 += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y0 += sy;
+            }
+        }
+    }
+
+    pub fn draw_line_aa(&mut self, x0:Float, y0:Float, x1:Float, y1:Float, colour:Color) {
+        if ![x0, y0, x1, y1].iter().all(|v| v.is_finite()) {
+            return;
+        }
+        let (w, h) = (self.width() as f64, self.height() as f64);
+        let clipped = clip_segment((to_f64(x0), to_f64(y0)), (to_f64(x1), to_f64(y1)), (-2.0, -2.0), (w + 1.0, h + 1.0));
+        let Some(((mut x0, mut y0), (mut x1, mut y1))) = clipped else {
+            return;
+        };
+
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            (x0, y0, x1, y1) = (y0, x0, y1, x1);
+        }
+        if x0 > x1 {
+            (x0, y0, x1, y1) = (x1, y1, x0, y0);
+        }
+        let plot = |canvas:&mut Canvas, x:f64, y:f64, coverage:f64| {
+            let (px, py) = if steep { (y, x) } else { (x, y) };
+            canvas.blend_pixel(px as isize, py as isize, colour, coverage as Float);
+        };
+        let gradient = if x1 - x0 == 0.0 { 1.0 } else { (y1 - y0) / (x1 - x0) };
+
+        // the two ends, weighted by how much of their pixel they cover
+        let mut ends = [(0.0, 0.0); 2];
+        for (i, (x, y)) in [(x0, y0), (x1, y1)].into_iter().enumerate() {
+            let x_end = x.round();
+            let y_end = y + gradient * (x_end - x);
+            let gap = if i == 0 { 1.0 - (x + 0.5).fract() } else { (x + 0.5).fract() };
+            plot(self, x_end, y_end.floor(), (1.0 - y_end.fract()) * gap);
+            plot(self, x_end, y_end.floor() + 1.0, y_end.fract() * gap);
+            ends[i] = (x_end, y_end);
+        }
+
+        let mut y = ends[0].1 + gradient;
+        let mut x = ends[0].0 + 1.0;
+        while x < ends[1].0 {
+            plot(self, x, y.floor(), 1.0 - (y - y.floor()));
+            plot(self, x, y.floor() + 1.0, y - y.floor());
+            y += gradient;
+            x += 1.0;
+        }
+    }
+
+    pub fn draw_thick_line(&mut self, x0:Float, y0:Float, x1:Float, y1:Float, width:Float, colour:Color) {
+        if ![x0, y0, x1, y1, width].iter().all(|v| v.is_finite()) {
+            return;
+        }
+        let (dx, dy) = (x1 - x0, y1 - y0);
+        let length = (dx * dx + dy * dy).sqrt();
+        let half = width / 2.0;
+        // a zero-length line is a width x width square
+        let (nx, ny) = if length == 0.0 { (0.0, half) } else { (-dy / length * half, dx / length * half) };
+        let (ex, ey) = if length == 0.0 { (half, 0.0) } else { (0.0, 0.0) };
+        self.fill_polygon(&[
+            (x0 + nx - ex, y0 + ny - ey),
+            (x1 + nx + ex, y1 + ny + ey),
+            (x1 - nx + ex, y1 - ny + ey),
+            (x0 - nx - ex, y0 - ny - ey),
+        ], colour);
+    }
+
+    fn visible_rows(&self, top:isize, bottom:isize) -> std::ops::Range<isize> {
+        // top..=bottom clamped to the canvas
+        top.max(0)..bottom.min(self.height() as isize - 1).saturating_add(1)
+    }
+
+    pub fn draw_rect(&mut self, x:isize, y:isize, w:usize, h:usize, colour:Color) {
+        if w == 0 || h == 0 {
+            return;
+        }
+        let (x1, y1) = (last(x, w), last(y, h));
+        self.hline(x, x1, y, colour);
+        self.hline(x, x1, y1, colour);
+        for row in self.visible_rows(y, y1) {
+            self.set_pixel_clipped(x, row, colour);
+            self.set_pixel_clipped(x1, row, colour);
+        }
+    }
+
+    pub fn fill_rect(&mut self, x:isize, y:isize, w:usize, h:usize, colour:Color) {
+        if w == 0 || h == 0 {
+            return;
+        }
+        for row in self.visible_rows(y, last(y, h)) {
+            self.hline(x, last(x, w), row, colour);
+        }
+    }
+
+    pub fn draw_circle(&mut self, cx:isize, cy:isize, r:usize, colour:Color) {
+        self.draw_ellipse(cx, cy, r, r, colour);
+    }
+
+    pub fn fill_circle(&mut self, cx:isize, cy:isize, r:usize, colour:Color) {
+        self.fill_ellipse(cx, cy, r, r, colour);
+    }
+
+    pub fn draw_ellipse(&mut self, cx:isize, cy:isize, rx:usize, ry:usize, colour:Color) {
+        // the pixels of fill_ellipse with a 4-neighbour outside it: every
+        // pixel past the narrower of the rows above and below, plus the ends
+        let ry_i = isize::try_from(ry).unwrap_or(isize::MAX);
+        for row in self.visible_rows(cy.saturating_sub(ry_i), cy.saturating_add(ry_i)) {
+            let dy = row.saturating_sub(cy);
+            let Some(half) = span(rx, ry, dy) else { continue };
+            let neighbour = |dy:isize| span(rx, ry, dy).unwrap_or(-1);
+            let inner = neighbour(dy.saturating_sub(1)).min(neighbour(dy.saturating_add(1)));
+            let from = inner.saturating_add(1).min(half);
+            if from == 0 {
+                self.hline(cx.saturating_sub(half), cx.saturating_add(half), row, colour);
+            } else {
+                self.hline(cx.saturating_sub(half), cx.saturating_sub(from), row, colour);
+                self.hline(cx.saturating_add(from), cx.saturating_add(half), row, colour);
+            }
+        }
+    }
+
+    pub fn fill_ellipse(&mut self, cx:isize, cy:isize, rx:usize, ry:usize, colour:Color) {
+        let ry_i = isize::try_from(ry).unwrap_or(isize::MAX);
+        for row in self.visible_rows(cy.saturating_sub(ry_i), cy.saturating_add(ry_i)) {
+            if let Some(half) = span(rx, ry, row.saturating_sub(cy)) {
+                self.hline(cx.saturating_sub(half), cx.saturating_add(half), row, colour);
+            }
+        }
+    }
+
+    pub fn draw_polygon(&mut self, points:&[(Float, Float)], colour:Color) {
+        if !all_finite(points) {
+            return;
+        }
+        let round = |(x, y):(Float, Float)| (x.round() as isize, y.round() as isize);
+        for (i, p) in points.iter().enumerate() {
+            let (a, b) = (round(*p), round(points[(i + 1) % points.len()]));
+            self.draw_line(a.0, a.1, b.0, b.1, colour);
+        }
+    }
+
+    pub fn fill_polygon(&mut self, points:&[(Float, Float)], colour:Color) {
+        if points.len() < 3 || !all_finite(points) {
+            return;
+        }
+        let points:Vec<(f64, f64)> = points.iter().map(|(x, y)| (to_f64(*x), to_f64(*y))).collect();
+        let top = points.iter().map(|p| p.1).fold(f64::INFINITY, f64::min).ceil().max(0.0);
+        let bottom = points.iter().map(|p| p.1).fold(-f64::INFINITY, f64::max).min(self.height() as f64 - 1.0);
+        if top.is_nan() || bottom.is_nan() || top > bottom {
+            return;
+        }
+        let mut crossings = Vec::new();
+        for row in top as isize..=bottom as isize {
+            // where the edges cross this row's pixel centres, half-open in
+            // y so a vertex shared by two edges counts once
+            let yc = row as f64;
+            crossings.clear();
+            for (i, &(x0, y0)) in points.iter().enumerate() {
+                let (x1, y1) = points[(i + 1) % points.len()];
+                if (y0 <= yc && yc < y1) || (y1 <= yc && yc < y0) {
+                    crossings.push(x0 + (yc - y0) * (x1 - x0) / (y1 - y0));
+                }
+            }
+            crossings.sort_by(|a, b| a.total_cmp(b));
+            for pair in crossings.chunks_exact(2) {
+                // pixel centres in [left, right)
+                let (left, right) = (pair[0].ceil(), (pair[1].ceil() - 1.0));
+                if left <= right {
+                    let clamp = |v:f64| v.clamp(-1.0, self.width() as f64) as isize;
+                    self.hline(clamp(left), clamp(right), row, colour);
+                }
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn draw_tests() {
+    use super::tuples::consts::PI;
+    use super::{canvas, color};
+    let black = color(0.0, 0.0, 0.0);
+    let white = color(1.0, 1.0, 1.0);
+    let lit = |c:&Canvas| -> Vec<(usize, usize)> {
+        c.pixels().filter(|(_, _, p)| *p != black).map(|(x, y, _)| (x, y)).collect()
+    };
+    let count = |c:&Canvas| lit(c).len();
+
+    // Bresenham
+    let mut c = canvas(10, 10);
+    c.draw_line(0, 0, 5, 2, white);
+    assert_eq!(lit(&c), vec![(0, 0), (1, 0), (2, 1), (3, 1), (4, 2), (5, 2)]);
+    let mut d = canvas(10, 10);
+    d.draw_line(5, 2, 0, 0, white);
+    assert_eq!(count(&d), 6);
+    let mut c = canvas(10, 10);
+    c.draw_line(3, 1, 3, 8, white);
+    c.draw_line(1, 9, 8, 9, white);
+    c.draw_line(5, 5, 5, 5, white);
+    assert_eq!(count(&c), 8 + 8 + 1);
+    // clipped: off-canvas endpoints, far away ones, and lines that miss
+    let mut c = canvas(10, 10);
+    c.draw_line(-5, 4, 20, 4, white);
+    assert_eq!(lit(&c), (0..10).map(|x| (x, 4)).collect::<Vec<_>>());
+    let mut c = canvas(10, 10);
+    c.draw_line(-1_000_000_000, 5, 1_000_000_000, 5, white);
+    c.draw_line(-3, -3, 12, 12, white);
+    assert_eq!(count(&c), 10 + 9);
+    let mut c = canvas(10, 10);
+    c.draw_line(-5, -5, -1, 20, white);
+    c.draw_line(isize::MIN / 2, 3, isize::MIN / 2 + 5, 3, white);
+    assert_eq!(count(&c), 0);
+
+    // Wu
+    let mut c = canvas(10, 5);
+    c.draw_line_aa(1.0, 2.0, 8.0, 2.0, white);
+    assert!((2..8).all(|x| c.pixel_at(x, 2) == white));
+    assert!(c.pixel_at(1, 2).red > 0.0 && c.pixel_at(1, 2).red <= 1.0);
+    assert!((0..10).all(|x| c.pixel_at(x, 1) == black && c.pixel_at(x, 3) == black));
+    let mut c = canvas(20, 20);
+    c.draw_line_aa(2.0, 3.0, 17.0, 11.0, white);
+    let coverage:Float = c.pixels().map(|(_, _, p)| p.red).sum();
+    let length = ((15.0 * 15.0 + 8.0 * 8.0) as Float).sqrt();
+    // about one pixel of ink per pixel of x, and partial pixels on the edge
+    assert!((coverage - 16.0).abs() < 1.5, "{} ink for a {} long line", coverage, length);
+    assert!(c.pixels().any(|(_, _, p)| p.red > 0.1 && p.red < 0.9));
+    let mut c = canvas(20, 20);
+    c.draw_line_aa(10.0, 1.0, 12.0, 18.0, white);
+    assert!((2..18).all(|y| c.pixel_at(10, y).red + c.pixel_at(11, y).red + c.pixel_at(12, y).red > 0.9));
+    let mut c = canvas(10, 10);
+    c.draw_line_aa(-1e9, -1e9, 1e9, 1e9, white);
+    assert!((1..9).all(|i| c.pixel_at(i, i) == white));
+    c.draw_line_aa(-50.0, 3.0, -10.0, 8.0, white);
+    // NaN and infinite ends draw nothing rather than NaN pixels
+    let mut c = canvas(10, 10);
+    let (inf, nan) = (Float::INFINITY, Float::NAN);
+    c.draw_line_aa(0.0, 0.0, inf, 5.0, white);
+    c.draw_line_aa(nan, 0.0, 5.0, 5.0, white);
+    c.draw_line_aa(-inf, -inf, inf, inf, white);
+    c.draw_thick_line(0.0, 0.0, 5.0, nan, 2.0, white);
+    c.draw_thick_line(0.0, 0.0, 5.0, 5.0, inf, white);
+    c.draw_polygon(&[(1.0, 1.0), (nan, 4.0), (6.0, 6.0)], white);
+    c.fill_polygon(&[(1.0, 1.0), (inf, 4.0), (6.0, 6.0)], white);
+    assert!(c.pixels().all(|(_, _, p)| p == black));
+    // blending keeps what was there
+    let mut c = canvas(3, 1);
+    c.fill(color(0.0, 0.0, 1.0));
+    assert!(c.blend_pixel(1, 0, color(1.0, 0.0, 0.0), 0.25));
+    assert_eq!(c.pixel_at(1, 0), color(0.25, 0.0, 0.75));
+    assert!(!c.blend_pixel(-1, 0, white, 1.0) && !c.blend_pixel(3, 0, white, 1.0));
+    assert!(c.blend_pixel(2, 0, white, Float::NAN));
+    assert_eq!(c.pixel_at(2, 0), color(0.0, 0.0, 1.0));
+
+    // Rectangles
+    let mut c = canvas(10, 10);
+    c.draw_rect(2, 3, 4, 3, white);
+    assert_eq!(count(&c), 2 * 4 + 2 * 3 - 4);
+    assert_eq!(c.pixel_at(3, 4), black);
+    let mut c = canvas(10, 10);
+    c.fill_rect(2, 3, 4, 3, white);
+    assert_eq!(count(&c), 12);
+    c.fill_rect(-5, -5, 7, 6, white);
+    assert_eq!(count(&c), 12 + 2);
+    c.draw_rect(8, 8, 100, 100, white);
+    c.fill_rect(0, 0, 0, 5, white);
+    assert_eq!(c.pixel_at(9, 8), white);
+
+    // Circles and ellipses
+    let mut c = canvas(21, 21);
+    c.draw_circle(10, 10, 0, white);
+    assert_eq!(lit(&c), vec![(10, 10)]);
+    let mut c = canvas(21, 21);
+    c.draw_circle(10, 10, 7, white);
+    for (x, y) in [(17, 10), (3, 10), (10, 17), (10, 3)] {
+        assert_eq!(c.pixel_at(x, y), white);
+    }
+    // symmetric in both axes, and nothing inside
+    assert!(lit(&c).iter().all(|&(x, y)| c.pixel_at(20 - x, y) == white && c.pixel_at(x, 20 - y) == white));
+    assert_eq!(c.pixel_at(10, 10), black);
+    let mut c = canvas(41, 41);
+    c.fill_circle(20, 20, 15, white);
+    // the outline is part of the disc, so it's about r + 1/2 across
+    let area = count(&c) as Float;
+    assert!((area / (PI * 15.5 * 15.5) - 1.0).abs() < 0.03, "{}", area);
+    let mut c = canvas(41, 41);
+    c.fill_ellipse(20, 20, 18, 6, white);
+    let area = count(&c) as Float;
+    assert!((area / (PI * 18.5 * 6.5) - 1.0).abs() < 0.05, "{}", area);
+    let mut c = canvas(41, 41);
+    c.draw_ellipse(20, 20, 18, 6, white);
+    for (x, y) in [(38, 20), (2, 20), (20, 26), (20, 14)] {
+        assert_eq!(c.pixel_at(x, y), white);
+    }
+    // outline is connected: every lit pixel has a lit 8-neighbour
+    let outline = lit(&c);
+    assert!(outline.iter().all(|&(x, y)| outline.iter().any(|&(u, v)| (u, v) != (x, y) && u.abs_diff(x) <= 1 && v.abs_diff(y) <= 1)));
+    let mut c = canvas(41, 41);
+    c.draw_ellipse(20, 20, 5, 0, white);
+    assert_eq!(count(&c), 11);
+    let mut c = canvas(41, 41);
+    c.draw_ellipse(20, 20, 0, 3, white);
+    assert_eq!(lit(&c), (17..24).map(|y| (20, y)).collect::<Vec<_>>());
+    // outline and fill agree: the outline is the filled disc's edge
+    for (rx, ry) in [(0, 0), (1, 1), (2, 2), (7, 7), (15, 4), (3, 12)] {
+        let (mut outline, mut disc) = (canvas(41, 41), canvas(41, 41));
+        outline.draw_ellipse(20, 20, rx, ry, white);
+        disc.fill_ellipse(20, 20, rx, ry, white);
+        let on = |c:&Canvas, x:usize, y:usize| c.pixel_at_checked(x, y) == Some(white);
+        for (x, y, p) in disc.pixels() {
+            let edge = p == white && [(x + 1, y), (x - 1, y), (x, y + 1), (x, y - 1)].iter().any(|&(u, v)| !on(&disc, u, v));
+            assert_eq!(on(&outline, x, y), edge, "({}, {}) of {}x{}", x, y, rx, ry);
+        }
+    }
+    let mut c = canvas(9, 9);
+    c.fill_circle(4, 4, 2, white);
+    assert_eq!(count(&c), 21);
+    // mostly off the canvas
+    let mut c = canvas(10, 10);
+    c.draw_circle(-3, 5, 6, white);
+    c.fill_circle(12, 12, 5, white);
+    c.fill_ellipse(5, -100, 50, 103, white);
+    assert!(count(&c) > 0);
+    // huge shapes cost what their visible rows cost, and don't overflow
+    let mut c = canvas(10, 10);
+    c.draw_circle(5, 5, usize::MAX, white);
+    c.draw_circle(isize::MIN, isize::MAX, 4_000_000_000, white);
+    c.draw_ellipse(5, 5, 8_000_000_000, 3_000_000_000, white);
+    c.draw_rect(isize::MAX - 2, -5, usize::MAX, usize::MAX, white);
+    c.fill_rect(isize::MIN, isize::MAX, usize::MAX, 3, white);
+    assert_eq!(count(&c), 0);
+    c.fill_circle(5, 5, usize::MAX, white);
+    assert_eq!(count(&c), 100);
+    let mut c = canvas(10, 10);
+    c.draw_rect(-1_000_000_000_000, 2, usize::MAX, 1_000_000_000_000, white);
+    assert_eq!(lit(&c), (0..10).map(|x| (x, 2)).collect::<Vec<_>>());
+
+    // Polygons
+    let mut c = canvas(10, 10);
+    c.fill_polygon(&[(1.0, 1.0), (5.0, 1.0), (5.0, 4.0), (1.0, 4.0)], white);
+    assert_eq!(lit(&c), (1..4).flat_map(|y| (1..5).map(move |x| (x, y))).collect::<Vec<_>>());
+    // concave: a U shape leaves its notch empty
+    let mut c = canvas(10, 10);
+    let u = [(0.0, 0.0), (9.0, 0.0), (9.0, 9.0), (6.0, 9.0), (6.0, 3.0), (3.0, 3.0), (3.0, 9.0), (0.0, 9.0)];
+    c.fill_polygon(&u, white);
+    assert_eq!(c.pixel_at(1, 6), white);
+    assert_eq!(c.pixel_at(4, 6), black);
+    assert_eq!(c.pixel_at(4, 1), white);
+    // a triangle fills about its area; neighbours sharing an edge don't overlap
+    let mut c = canvas(30, 30);
+    c.fill_polygon(&[(2.0, 2.0), (27.0, 5.0), (9.0, 26.0)], white);
+    let area = count(&c) as Float;
+    assert!((area - 286.5).abs() < 15.0, "{}", area);
+    let mut c = canvas(10, 10);
+    c.fill_polygon(&[(0.0, 0.0), (8.0, 0.0), (0.0, 8.0)], color(1.0, 0.0, 0.0));
+    c.fill_polygon(&[(8.0, 0.0), (8.0, 8.0), (0.0, 8.0)], color(0.0, 1.0, 0.0));
+    assert_eq!(count(&c), 64);
+    let mut c = canvas(10, 10);
+    c.draw_polygon(&[(1.0, 1.0), (8.0, 1.0), (8.0, 8.0)], white);
+    assert_eq!(c.pixel_at(1, 1), white);
+    assert_eq!(c.pixel_at(4, 4), white);
+    c.fill_polygon(&[(1.0, 1.0), (2.0, 2.0)], white);
+    c.fill_polygon(&[(-1e9, -1e9), (1e9, -1e9), (1e9, 1e9)], white);
+    assert_eq!(c.pixel_at(9, 1), white);
+    c.fill_polygon(&[(0.0, Float::NAN), (1.0, 1.0), (2.0, 0.0)], white);
+
+    // Thick lines
+    let mut c = canvas(20, 12);
+    c.draw_thick_line(2.0, 5.0, 10.0, 5.0, 3.0, white);
+    assert_eq!(lit(&c), (4..7).flat_map(|y| (2..10).map(move |x| (x, y))).collect::<Vec<_>>());
+    let mut c = canvas(30, 30);
+    c.draw_thick_line(3.0, 3.0, 25.0, 20.0, 4.0, white);
+    let area = count(&c) as Float;
+    let expected = ((22.0 * 22.0 + 17.0 * 17.0) as Float).sqrt() * 4.0;
+    assert!((area - expected).abs() < expected * 0.1, "{} vs {}", area, expected);
+    let mut c = canvas(10, 10);
+    c.draw_thick_line(5.0, 5.0, 5.0, 5.0, 2.0, white);
+    assert_eq!(count(&c), 4);
+}
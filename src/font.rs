@@ -0,0 +1,153 @@
+/*
+Bitmap font
+-----------
+A 3x5 pixel font for labelling plots and debug images: digits, A-Z
+(lower case is drawn as upper case) and enough punctuation for numbers
+and short labels. Anything else comes out as '?'.
+
+Each glyph is five rows of three bits, the high bit on the left. Glyphs
+advance 4 pixels (3 plus a gap) and lines 6, all times the scale, and
+draw_text takes the top left corner of the first glyph. Text clips like
+everything else in draw.rs.
+*/
+
+use super::{Canvas, Color};
+
+pub const GLYPH_WIDTH:usize = 3;
+pub const GLYPH_HEIGHT:usize = 5;
+pub const ADVANCE:usize = GLYPH_WIDTH + 1;
+pub const LINE_HEIGHT:usize = GLYPH_HEIGHT + 1;
+
+pub fn glyph(c:char) -> [u8; GLYPH_HEIGHT] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        ' ' => [0, 0, 0, 0, 0],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '+' => [0b000, 0b010, 0b111, 0b010, 0b000],
+        '=' => [0b000, 0b111, 0b000, 0b111, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '(' => [0b001, 0b010, 0b010, 0b010, 0b001],
+        ')' => [0b100, 0b010, 0b010, 0b010, 0b100],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '\'' => [0b010, 0b010, 0b000, 0b000, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        _ => [0b111, 0b001, 0b010, 0b000, 0b010],
+    }
+}
+
+pub fn text_width(text:&str, scale:usize) -> usize {
+    // in pixels, without the gap after the last glyph
+    (text.chars().count() * ADVANCE).saturating_sub(1) * scale
+}
+
+impl Canvas {
+    pub fn draw_text(&mut self, x:isize, y:isize, text:&str, colour:Color, scale:usize) {
+        // saturating, like the shapes in draw.rs: far off the canvas is
+        // still just off the canvas
+        let s = isize::try_from(scale).unwrap_or(isize::MAX);
+        let offset = |start:isize, n:usize| start.saturating_add(isize::try_from(n).unwrap_or(isize::MAX).saturating_mul(s));
+        for (i, c) in text.chars().enumerate() {
+            let left = offset(x, i * ADVANCE);
+            if left >= self.width() as isize {
+                break;
+            }
+            for (row, bits) in glyph(c).iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits & (0b100 >> col) != 0 {
+                        self.fill_rect(offset(left, col), offset(y, row), scale, scale, colour);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn font_tests() {
+    use super::{canvas, color};
+    let white = color(1.0, 1.0, 1.0);
+    let lit = |c:&Canvas| c.pixels().filter(|(_, _, p)| *p == white).count();
+
+    // every glyph the font has is distinct, and unknown ones are '?'
+    let chars = "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ -+=.,:/()%_'!";
+    for (i, a) in chars.chars().enumerate() {
+        for b in chars.chars().skip(i + 1) {
+            assert_ne!(glyph(a), glyph(b), "{:?} and {:?} look the same", a, b);
+        }
+        assert!(glyph(a).iter().all(|row| *row < 8));
+    }
+    assert_eq!(glyph('a'), glyph('A'));
+    assert_eq!(glyph('~'), glyph('?'));
+    assert_eq!(glyph('é'), glyph('?'));
+
+    assert_eq!(text_width("", 1), 0);
+    assert_eq!(text_width("1", 1), 3);
+    assert_eq!(text_width("10.5", 1), 15);
+    assert_eq!(text_width("10.5", 2), 30);
+
+    // "1" at (2, 1): its pixels and nothing else
+    let mut c = canvas(8, 8);
+    c.draw_text(2, 1, "1", white, 1);
+    assert_eq!(lit(&c), 8);
+    assert_eq!(c.pixel_at(3, 1), white);
+    assert_eq!(c.pixel_at(2, 2), white);
+    assert_eq!(c.pixel_at(2, 1), color(0.0, 0.0, 0.0));
+    assert!((2..=4).all(|x| c.pixel_at(x, 5) == white));
+    let mut c = canvas(20, 20);
+    c.draw_text(0, 0, "1", white, 3);
+    assert_eq!(lit(&c), 8 * 9);
+    // spaces advance without drawing; text off the canvas clips
+    let mut c = canvas(12, 6);
+    c.draw_text(0, 0, "- -", white, 1);
+    assert_eq!(lit(&c), 6);
+    assert_eq!(c.pixel_at(8, 2), white);
+    c.draw_text(-6, -3, "HELLO, WORLD", white, 2);
+    c.draw_text(100, 100, "8", white, 1);
+    // positions and scales at the ends of isize don't overflow
+    let mut c = canvas(8, 8);
+    c.draw_text(isize::MAX - 2, 0, "AB", white, 1);
+    c.draw_text(isize::MIN, isize::MIN, "AB", white, 3);
+    c.draw_text(-4, 0, "-1", white, usize::MAX);
+    assert_eq!(lit(&c), 0);
+    c.draw_text(0, 0, "8", white, usize::MAX);
+    assert_eq!(lit(&c), 64);
+}
@@ -0,0 +1,543 @@
+/*
+HDR images
+----------
+Lossless-ish export and import for colours above 1.0, for post-processing
+renders and for HDR environment maps. Nothing here clamps to 0..1.
+
+Portable FloatMap (PFM): a text header "PF", "width height" and a scale,
+then 32-bit floats, three per pixel, with the rows stored bottom to top.
+A negative scale means little-endian samples, positive big-endian; the
+magnitude is ignored on read and written as 1. "Pf" (greyscale) files
+are read as grey. Exact for the f32 build; the f64 build rounds to f32.
+
+Radiance RGBE (.hdr): a "#?RADIANCE" header of KEY=value lines, a blank
+line, "-Y height +X width", then one shared-exponent pixel per 4 bytes
+(Greg Ward's format, about 1% precision over a huge range). Scanlines are
+written with the new-style run-length encoding (each component run
+length encoded separately) when the width allows it. Reading accepts
+flat, old-style and new-style RLE scanlines and divides by any EXPOSURE
+in the header. RGBE has no sign, so negative and NaN channels become 0.
+*/
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::tuples::Float;
+use super::{canvas, color, to_f32, to_f64, Canvas, Color};
+
+// widths the new-style RLE can describe
+const RLE_MIN_WIDTH:usize = 8;
+const RLE_MAX_WIDTH:usize = 0x7fff;
+
+#[derive(Debug)]
+pub enum HdrError {
+    Io(io::Error),
+    BadMagic(String),
+    BadHeader(String),
+    Unsupported(String),
+    // pixel data ends before the last row
+    Truncated,
+    // a run-length encoded scanline that doesn't add up to the width
+    BadScanline{row:usize},
+}
+
+impl fmt::Display for HdrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HdrError::Io(e) => write!(f, "Can't read HDR image: {}", e),
+            HdrError::BadMagic(magic) => write!(f, "Not a PFM or Radiance file (starts with {:?})", magic),
+            HdrError::BadHeader(why) => write!(f, "Bad HDR header: {}", why),
+            HdrError::Unsupported(what) => write!(f, "Unsupported HDR image: {}", what),
+            HdrError::Truncated => write!(f, "HDR pixel data ends early"),
+            HdrError::BadScanline{row} => write!(f, "Bad run-length encoding in row {}", row),
+        }
+    }
+}
+
+impl std::error::Error for HdrError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            HdrError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for HdrError {
+    fn from(e:io::Error) -> HdrError {
+        HdrError::Io(e)
+    }
+}
+
+fn lossy(bytes:&[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+fn size(width:usize, height:usize, bytes_per_pixel:usize) -> Result<usize, HdrError> {
+    width.checked_mul(height).and_then(|n| n.checked_mul(bytes_per_pixel))
+        .ok_or_else(|| HdrError::BadHeader(format!("{}x{} image is too large", width, height)))
+}
+
+// ------------------------------------------------------------------- PFM
+
+impl Canvas {
+    pub fn write_pfm<W: io::Write>(&self, w:&mut W) -> io::Result<()> {
+        // negative scale: little-endian
+        write!(w, "PF\n{} {}\n-1.0\n", self.width(), self.height())?;
+        let mut bytes = Vec::with_capacity(self.width() * 12);
+        for row in self.as_slice().chunks(self.width().max(1)).rev() {
+            bytes.clear();
+            for pixel in row {
+                for c in [pixel.red, pixel.green, pixel.blue] {
+                    bytes.extend(to_f32(c).to_le_bytes());
+                }
+            }
+            w.write_all(&bytes)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_pfm<P: AsRef<Path>>(&self, path:P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        self.write_pfm(&mut file)?;
+        io::Write::flush(&mut file)
+    }
+}
+
+pub fn parse_pfm(data:&[u8]) -> Result<Canvas, HdrError> {
+    // four whitespace-separated tokens, then exactly one whitespace byte
+    let mut tokens = Vec::with_capacity(4);
+    let mut pos = 0;
+    while tokens.len() < 4 {
+        while pos < data.len() && data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        let start = pos;
+        while pos < data.len() && !data[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+        if pos == start {
+            break;
+        }
+        tokens.push(&data[start..pos]);
+        if tokens.len() == 1 && tokens[0] != b"PF" && tokens[0] != b"Pf" {
+            return Err(HdrError::BadMagic(lossy(tokens[0])));
+        }
+    }
+    if tokens.is_empty() {
+        return Err(HdrError::BadMagic(String::new()));
+    }
+    if tokens.len() < 4 {
+        return Err(HdrError::BadHeader(String::from("expected width, height and scale")));
+    }
+
+    let channels = if tokens[0] == b"PF" { 3 } else { 1 };
+    let number = |token:&[u8], field:&str| {
+        std::str::from_utf8(token).ok().and_then(|s| s.parse::<usize>().ok())
+            .ok_or_else(|| HdrError::BadHeader(format!("bad {} {:?}", field, lossy(token))))
+    };
+    let width = number(tokens[1], "width")?;
+    let height = number(tokens[2], "height")?;
+    let scale = std::str::from_utf8(tokens[3]).ok().and_then(|s| s.parse::<f64>().ok())
+        .filter(|s| *s != 0.0 && s.is_finite())
+        .ok_or_else(|| HdrError::BadHeader(format!("bad scale {:?}", lossy(tokens[3]))))?;
+    let little_endian = scale < 0.0;
+
+    let raster = data.get(pos + 1..).unwrap_or(&[]);
+    let needed = size(width, height, channels * 4)?;
+    if raster.len() < needed {
+        return Err(HdrError::Truncated);
+    }
+    let samples:Vec<Float> = raster[..needed].chunks_exact(4).map(|b| {
+        let b = [b[0], b[1], b[2], b[3]];
+        (if little_endian { f32::from_le_bytes(b) } else { f32::from_be_bytes(b) }) as Float
+    }).collect();
+
+    let mut image = canvas(width, height);
+    for (y, row) in samples.chunks_exact((width * channels).max(1)).take(height).enumerate() {
+        // file rows run bottom to top
+        for (x, s) in row.chunks_exact(channels).enumerate() {
+            let c = if channels == 3 { color(s[0], s[1], s[2]) } else { color(s[0], s[0], s[0]) };
+            image.set_pixel(x, height - 1 - y, c);
+        }
+    }
+    Ok(image)
+}
+
+pub fn load_pfm<P: AsRef<Path>>(path:P) -> Result<Canvas, HdrError> {
+    parse_pfm(&fs::read(path)?)
+}
+
+// ------------------------------------------------------------------ RGBE
+
+pub fn to_rgbe(c:Color) -> [u8; 4] {
+    // shared exponent: the largest channel sets it, mantissas are 8 bits
+    let channel = |v:Float| if v > 0.0 { to_f64(v) } else { 0.0 };
+    let (r, g, b) = (channel(c.red), channel(c.green), channel(c.blue));
+    let v = r.max(g).max(b);
+    if v < 1e-32 {
+        return [0, 0, 0, 0];
+    }
+    if !v.is_finite() {
+        return [255, 255, 255, 255];
+    }
+    // v = m * 2^e with m in [0.5, 1)
+    let mut e = v.log2().floor() as i32 + 1;
+    let mut m = v / 2f64.powi(e);
+    if m >= 1.0 {
+        m /= 2.0;
+        e += 1;
+    } else if m < 0.5 {
+        m *= 2.0;
+        e -= 1;
+    }
+    if e > 127 {
+        return [255, 255, 255, 255];
+    }
+    let scale = m * 256.0 / v;
+    [(r * scale) as u8, (g * scale) as u8, (b * scale) as u8, (e + 128) as u8]
+}
+
+pub fn from_rgbe(rgbe:[u8; 4]) -> Color {
+    if rgbe[3] == 0 {
+        return color(0.0, 0.0, 0.0);
+    }
+    let f = 2f64.powi(rgbe[3] as i32 - (128 + 8));
+    let channel = |v:u8| (v as f64 * f) as Float;
+    color(channel(rgbe[0]), channel(rgbe[1]), channel(rgbe[2]))
+}
+
+fn rle_component(data:&[u8], out:&mut Vec<u8>) {
+    // runs of 4+ equal bytes become (128 + n, byte), n <= 127;
+    // everything else goes out as literals (n, bytes...), n <= 128
+    let run_at = |i:usize| data[i..].iter().take(127).take_while(|b| **b == data[i]).count();
+    let mut i = 0;
+    while i < data.len() {
+        let run = run_at(i);
+        if run >= 4 {
+            out.extend([128 + run as u8, data[i]]);
+            i += run;
+            continue;
+        }
+        let start = i;
+        while i < data.len() && i - start < 128 && run_at(i) < 4 {
+            i += 1;
+        }
+        out.push((i - start) as u8);
+        out.extend_from_slice(&data[start..i]);
+    }
+}
+
+impl Canvas {
+    pub fn write_hdr<W: io::Write>(&self, w:&mut W) -> io::Result<()> {
+        let (width, height) = (self.width(), self.height());
+        write!(w, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width)?;
+        let rle = (RLE_MIN_WIDTH..=RLE_MAX_WIDTH).contains(&width);
+        let mut pixels = Vec::with_capacity(width * 4);
+        let mut bytes = Vec::with_capacity(width * 4 + 4);
+        let mut component = Vec::with_capacity(width);
+        for row in self.rows().take(height) {
+            pixels.clear();
+            pixels.extend(row.iter().flat_map(|c| to_rgbe(*c)));
+            if !rle {
+                w.write_all(&pixels)?;
+                continue;
+            }
+            bytes.clear();
+            bytes.extend([2, 2, (width >> 8) as u8, (width & 0xff) as u8]);
+            for i in 0..4 {
+                component.clear();
+                component.extend(pixels.iter().skip(i).step_by(4));
+                rle_component(&component, &mut bytes);
+            }
+            w.write_all(&bytes)?;
+        }
+        Ok(())
+    }
+
+    pub fn save_hdr<P: AsRef<Path>>(&self, path:P) -> io::Result<()> {
+        let mut file = io::BufWriter::new(fs::File::create(path)?);
+        self.write_hdr(&mut file)?;
+        io::Write::flush(&mut file)
+    }
+}
+
+fn read_scanline(data:&[u8], pos:&mut usize, width:usize, row:usize, out:&mut Vec<[u8; 4]>) -> Result<(), HdrError> {
+    out.clear();
+    let byte = |i:usize| data.get(i).copied().ok_or(HdrError::Truncated);
+    let new_style = (RLE_MIN_WIDTH..=RLE_MAX_WIDTH).contains(&width)
+        && byte(*pos)? == 2 && byte(*pos + 1)? == 2 && byte(*pos + 2)? & 0x80 == 0;
+
+    if new_style {
+        if ((byte(*pos + 2)? as usize) << 8 | byte(*pos + 3)? as usize) != width {
+            return Err(HdrError::BadScanline{row});
+        }
+        *pos += 4;
+        out.resize(width, [0; 4]);
+        for i in 0..4 {
+            let mut x = 0;
+            while x < width {
+                let count = byte(*pos)? as usize;
+                *pos += 1;
+                if count > 128 {
+                    let run = count - 128;
+                    if x + run > width {
+                        return Err(HdrError::BadScanline{row});
+                    }
+                    let value = byte(*pos)?;
+                    *pos += 1;
+                    out[x..x + run].iter_mut().for_each(|p| p[i] = value);
+                    x += run;
+                } else {
+                    if count == 0 || x + count > width {
+                        return Err(HdrError::BadScanline{row});
+                    }
+                    let literal = data.get(*pos..*pos + count).ok_or(HdrError::Truncated)?;
+                    *pos += count;
+                    out[x..x + count].iter_mut().zip(literal).for_each(|(p, v)| p[i] = *v);
+                    x += count;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // flat pixels, where (1, 1, 1, n) repeats the previous pixel n times,
+    // with consecutive repeats shifting n up by 8 bits each
+    let mut shift = 0;
+    while out.len() < width {
+        let p = data.get(*pos..*pos + 4).ok_or(HdrError::Truncated)?;
+        *pos += 4;
+        if p[0] == 1 && p[1] == 1 && p[2] == 1 {
+            let previous = *out.last().ok_or(HdrError::BadScanline{row})?;
+            let count = (p[3] as usize).checked_shl(shift).ok_or(HdrError::BadScanline{row})?;
+            if out.len() + count > width {
+                return Err(HdrError::BadScanline{row});
+            }
+            out.extend(std::iter::repeat_n(previous, count));
+            shift += 8;
+        } else {
+            out.push([p[0], p[1], p[2], p[3]]);
+            shift = 0;
+        }
+    }
+    Ok(())
+}
+
+fn min_scanline_bytes(width:usize) -> usize {
+    // the fewest bytes any scanline encoding can spend on a row: flat is
+    // one pixel then (1, 1, 1, n) repeats, each worth 8 more bits of n
+    let (mut flat, mut covered, mut shift) = (4usize, 1usize, 0u32);
+    while covered < width {
+        covered = covered.saturating_add(255usize.checked_shl(shift).unwrap_or(usize::MAX));
+        flat += 4;
+        shift += 8;
+    }
+    if (RLE_MIN_WIDTH..=RLE_MAX_WIDTH).contains(&width) {
+        flat.min(4 + 4 * 2 * width.div_ceil(127))
+    } else {
+        flat
+    }
+}
+
+pub fn parse_hdr(data:&[u8]) -> Result<Canvas, HdrError> {
+    if !data.starts_with(b"#?") {
+        return Err(HdrError::BadMagic(lossy(&data[..data.len().min(10)])));
+    }
+
+    // header lines up to the first blank one, then the resolution line
+    let mut pos = 0;
+    let mut line = || {
+        let start = pos;
+        let end = data[start..].iter().position(|b| *b == b'\n').map(|n| start + n)
+            .ok_or_else(|| HdrError::BadHeader(String::from("header never ends")))?;
+        pos = end + 1;
+        Ok::<_, HdrError>((lossy(&data[start..end]), pos))
+    };
+    line()?;
+    let mut exposure = 1.0;
+    loop {
+        let (text, _) = line()?;
+        let text = text.trim();
+        if text.is_empty() {
+            break;
+        }
+        if let Some(format) = text.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(HdrError::Unsupported(format!("pixel format {}", format)));
+            }
+        } else if let Some(value) = text.strip_prefix("EXPOSURE=") {
+            let value:f64 = value.trim().parse().ok().filter(|v:&f64| *v > 0.0)
+                .ok_or_else(|| HdrError::BadHeader(format!("bad exposure {:?}", value)))?;
+            exposure *= value;
+        }
+    }
+    let (resolution, raster_start) = line()?;
+    let fields:Vec<&str> = resolution.split_whitespace().collect();
+    let (height, width) = match fields[..] {
+        ["-Y", h, "+X", w] => {
+            let number = |s:&str| s.parse::<usize>().ok()
+                .ok_or_else(|| HdrError::BadHeader(format!("bad resolution {:?}", resolution)));
+            (number(h)?, number(w)?)
+        }
+        [_, _, _, _] => return Err(HdrError::Unsupported(format!("orientation {:?}", resolution))),
+        _ => return Err(HdrError::BadHeader(format!("bad resolution {:?}", resolution))),
+    };
+    size(width, height, 4)?;
+    if width == 0 || height == 0 {
+        // no scanlines to read, however many rows of nothing are promised
+        return Ok(Canvas{width, height, pixels: Vec::new()});
+    }
+    // a header can promise any size; don't allocate for rows that can't
+    // be there, and grow with the rows that are
+    let raster = data.len().saturating_sub(raster_start);
+    if raster / min_scanline_bytes(width) < height {
+        return Err(HdrError::Truncated);
+    }
+
+    let mut pixels = Vec::new();
+    let mut pos = raster_start;
+    let mut scanline = Vec::with_capacity(width);
+    let scale = (1.0 / exposure) as Float;
+    for y in 0..height {
+        read_scanline(data, &mut pos, width, y, &mut scanline)?;
+        pixels.extend(scanline.iter().map(|rgbe| {
+            let c = from_rgbe(*rgbe);
+            color(c.red * scale, c.green * scale, c.blue * scale)
+        }));
+    }
+    Ok(Canvas{width, height, pixels})
+}
+
+pub fn load_hdr<P: AsRef<Path>>(path:P) -> Result<Canvas, HdrError> {
+    parse_hdr(&fs::read(path)?)
+}
+
+#[allow(dead_code)]
+pub fn hdr_tests() {
+    use super::tuples::approx::{assert_approx_eq, ApproxEq};
+
+    // A render with highlights far above 1.0, and some negative noise
+    let mut image = canvas(23, 9);
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let (u, v) = (x as Float, y as Float);
+            image.set_pixel(x, y, color(u * 3.7, 1.0 / (1.0 + v), (u - v) * 0.01));
+        }
+    }
+    image.set_pixel(4, 4, color(5000.0, 0.001, 12.5));
+    for x in 10..23 {
+        image.set_pixel(x, 2, color(0.75, 0.75, 0.75));
+    }
+
+    // PFM: exact for f32 samples, negatives included
+    let mut pfm = Vec::new();
+    image.write_pfm(&mut pfm).expect("writing to a Vec can't fail");
+    assert!(pfm.starts_with(b"PF\n23 9\n-1.0\n"));
+    assert_eq!(pfm.len(), 13 + 23 * 9 * 12);
+    let back = parse_pfm(&pfm).expect("own output parses");
+    for ((_, _, a), (_, _, b)) in image.pixels().zip(back.pixels()) {
+        let f = |c:Float| to_f32(c) as Float;
+        assert_eq!(b, color(f(a.red), f(a.green), f(a.blue)));
+    }
+    // bottom row first in the file
+    let bottom_left = image.pixel_at(0, 8);
+    let expected:Vec<u8> = [bottom_left.red, bottom_left.green, bottom_left.blue].iter().flat_map(|c| to_f32(*c).to_le_bytes()).collect();
+    assert_eq!(pfm[13..25], expected[..]);
+
+    // Big-endian greyscale, written by hand: bottom row 0.5, top row 2.0
+    let mut pf = b"Pf\n1 2\n1.0\n".to_vec();
+    pf.extend(0.5f32.to_be_bytes());
+    pf.extend(2.0f32.to_be_bytes());
+    let grey = parse_pfm(&pf).expect("valid greyscale PFM");
+    assert_eq!(grey.pixel_at(0, 0), color(2.0, 2.0, 2.0));
+    assert_eq!(grey.pixel_at(0, 1), color(0.5, 0.5, 0.5));
+
+    // RGBE encoding
+    assert_eq!(to_rgbe(color(1.0, 1.0, 1.0)), [128, 128, 128, 129]);
+    assert_eq!(to_rgbe(color(0.5, 0.25, 0.0)), [128, 64, 0, 128]);
+    assert_eq!(to_rgbe(color(0.0, -3.0, Float::NAN)), [0, 0, 0, 0]);
+    assert_eq!(from_rgbe([128, 128, 128, 129]), color(1.0, 1.0, 1.0));
+    assert_eq!(from_rgbe([0, 0, 0, 0]), color(0.0, 0.0, 0.0));
+    for v in [1e-20, 0.001, 0.3, 1.0, 7.5, 1000.0, 1e30] {
+        let c = color(v, v * 0.5, v * 0.01);
+        let back = from_rgbe(to_rgbe(c));
+        // each channel is within one mantissa step of the largest
+        for (a, b) in [(c.red, back.red), (c.green, back.green), (c.blue, back.blue)] {
+            assert!(b <= a && a - b <= v / 128.0 + v * 1e-6, "{} -> {}", a, b);
+        }
+    }
+
+    // Radiance files: RLE rows for wide images, flat ones for narrow
+    let mut hdr = Vec::new();
+    image.write_hdr(&mut hdr).expect("writing to a Vec can't fail");
+    assert!(hdr.starts_with(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 9 +X 23\n"));
+    let back = parse_hdr(&hdr).expect("own output parses");
+    for ((_, _, a), (_, _, b)) in image.pixels().zip(back.pixels()) {
+        assert_eq!(b, from_rgbe(to_rgbe(a)));
+    }
+    let mut flat = canvas(200, 50);
+    flat.fill(color(3.0, 2.0, 1.0));
+    let mut rle = Vec::new();
+    flat.write_hdr(&mut rle).unwrap();
+    assert!(rle.len() < 200 * 50);
+    assert_eq!(parse_hdr(&rle).unwrap(), flat);
+    let narrow = canvas(3, 2);
+    let mut bytes = Vec::new();
+    narrow.write_hdr(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), 45 + 3 * 2 * 4);
+    assert_eq!(parse_hdr(&bytes).unwrap(), narrow);
+
+    // Hand-made files from other writers: new-style RLE with a run and a
+    // literal, and old-style repeats, with extra header lines
+    let mut new_style = b"#?RGBE\n# made by hand\nGAMMA=1.0\n\n-Y 1 +X 8\n".to_vec();
+    new_style.extend([2, 2, 0, 8]);
+    new_style.extend([136, 128]);
+    new_style.extend([8, 0, 16, 32, 48, 64, 80, 96, 112]);
+    new_style.extend([136, 0, 136, 129]);
+    let img = parse_hdr(&new_style).expect("valid new-style RLE");
+    assert_eq!(img.pixel_at(0, 0), color(1.0, 0.0, 0.0));
+    assert_eq!(img.pixel_at(7, 0), color(1.0, 112.0 / 128.0, 0.0));
+    let mut old_style = b"#?RADIANCE\nEXPOSURE=2\n\n-Y 1 +X 4\n".to_vec();
+    old_style.extend([64, 128, 0, 130, 1, 1, 1, 3]);
+    let img = parse_hdr(&old_style).expect("valid old-style RLE");
+    assert!((0..4).all(|x| img.pixel_at(x, 0) == color(0.5, 1.0, 0.0)));
+
+    // Files
+    let dir = std::env::temp_dir();
+    image.save_pfm(dir.join("hyperion_test.pfm")).expect("temp dir is writable");
+    image.save_hdr(dir.join("hyperion_test.hdr")).expect("temp dir is writable");
+    assert_eq!(load_pfm(dir.join("hyperion_test.pfm")).unwrap(), parse_pfm(&pfm).unwrap());
+    assert_approx_eq!(load_hdr(dir.join("hyperion_test.hdr")).unwrap().pixel_at(3, 3), from_rgbe(to_rgbe(image.pixel_at(3, 3))));
+    let _ = fs::remove_file(dir.join("hyperion_test.pfm"));
+    let _ = fs::remove_file(dir.join("hyperion_test.hdr"));
+    assert!(matches!(load_hdr("renders/no_such_file.hdr"), Err(HdrError::Io(_))));
+
+    // Errors
+    assert!(matches!(parse_pfm(b"P6\n1 1\n255\n"), Err(HdrError::BadMagic(m)) if m == "P6"));
+    assert!(matches!(parse_pfm(b"PF\n1 1\n0\n"), Err(HdrError::BadHeader(_))));
+    assert!(matches!(parse_pfm(b"PF\n1 x\n-1\n"), Err(HdrError::BadHeader(_))));
+    assert!(matches!(parse_pfm(b"PF\n1 1\n-1\n\x00\x00"), Err(HdrError::Truncated)));
+    assert!(matches!(parse_hdr(b"P3\n"), Err(HdrError::BadMagic(_))));
+    assert!(matches!(parse_hdr(b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n"), Err(HdrError::Unsupported(_))));
+    assert!(matches!(parse_hdr(b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0"), Err(HdrError::Unsupported(_))));
+    assert!(matches!(parse_hdr(b"#?RADIANCE\n\n-Y 1\n"), Err(HdrError::BadHeader(_))));
+    assert!(matches!(parse_hdr(&hdr[..hdr.len() - 3]), Err(HdrError::Truncated)));
+    // a huge resolution with no data behind it fails before allocating
+    assert!(matches!(parse_hdr(b"#?RADIANCE\n\n-Y 100000 +X 100000\n\x02\x02"), Err(HdrError::Truncated)));
+    assert!(matches!(parse_hdr(b"#?RADIANCE\n\n-Y 100000 +X 3\n"), Err(HdrError::Truncated)));
+    assert_eq!(min_scanline_bytes(1), 4);
+    assert_eq!(min_scanline_bytes(100), 8);
+    assert_eq!(min_scanline_bytes(100_000), 4 + 4 * 3);
+    let blank = parse_hdr(b"#?RADIANCE\n\n-Y 3 +X 0\n").unwrap();
+    assert_eq!((blank.width(), blank.height()), (0, 3));
+    let endless = parse_hdr(b"#?RADIANCE\n\n-Y 18446744073709551615 +X 0\n").unwrap();
+    assert_eq!((endless.width(), endless.height()), (0, usize::MAX));
+    assert!(parse_hdr(b"#?RADIANCE\n\n-Y 0 +X 18446744073709551615\n").unwrap().as_slice().is_empty());
+    let mut overrun = b"#?RADIANCE\n\n-Y 1 +X 8\n".to_vec();
+    overrun.extend([2, 2, 0, 8, 137, 0, 0, 0]);
+    assert!(matches!(parse_hdr(&overrun), Err(HdrError::BadScanline{row: 0})));
+    assert_eq!(parse_hdr(&overrun).unwrap_err().to_string(), "Bad run-length encoding in row 0");
+}
@@ -1,3 +1,6 @@
+// Each demo pulls in its own copy of the math modules through #[path].
+#![allow(clippy::duplicate_mod)]
+
 mod tuples;
 mod canvas;
 mod projectile;
@@ -5,13 +8,50 @@ mod matrix;
 mod clock;
 mod spheres;
 mod sphere_shadows;
+mod regress;
 
 fn main() {
+    // `regress [names]` checks the demos against renders/, `bless [names]`
+    // rewrites the references; see regress.rs
+    let args:Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("regress") => std::process::exit(if regress::run(&args[1..], false) { 0 } else { 1 }),
+        Some("bless") => std::process::exit(if regress::run(&args[1..], true) { 0 } else { 1 }),
+        Some(other) => {
+            eprintln!("unknown command {:?}; expected regress or bless", other);
+            std::process::exit(2);
+        }
+        None => {}
+    }
+
     // tuples::tuple_tests();
+    // tuples::typed_tuple_tests();
+    // tuples::vector_toolkit_tests();
+    // tuples::approx::approx_tests();
+    // tuples::simd::simd_tests();
     // canvas::canvas_tests();
+    // canvas::color_tests();
+    // canvas::canvas_struct_tests();
+    // canvas::ppm_tests();
+    // canvas::binary_ppm_tests();
+    // canvas::ppm::ppm_reader_tests();
+    // canvas::png::zlib::zlib_tests();
+    // canvas::png::png_tests();
+    // canvas::hdr::hdr_tests();
+    // canvas::tonemap::tonemap_tests();
+    // canvas::colorspace::colorspace_tests();
+    // canvas::compare::compare_tests();
+    // canvas::draw::draw_tests();
+    // canvas::font::font_tests();
+    // canvas::plot::plot_tests();
     // projectile::projectile_plot();
     // matrix::matrix_tests();
+    // matrix::generic_matrix_tests();
     // matrix::transform_tests();
+    // matrix::builder_tests();
+    // matrix::quaternion::quaternion_tests();
+    // matrix::decompose::decompose_tests();
+    // matrix::bounds::bounds_tests();
     // clock::clock_render();
     // spheres::ray_sphere_tests();
     // spheres::ray_sphere_transform_tests();
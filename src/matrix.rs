@@ -1,6 +1,19 @@
 #[path = "tuples.rs"]
 pub mod tuples;
 
+#[path = "quaternion.rs"]
+pub mod quaternion;
+
+#[path = "decompose.rs"]
+pub mod decompose;
+
+#[path = "bounds.rs"]
+pub mod bounds;
+
+use std::fmt;
+use std::ops::Mul;
+use tuples::Float;
+
 /*
 The Ray Tracer Challenge (Jamis Buck)
 -------------------------------------
@@ -8,32 +21,181 @@ The Ray Tracer Challenge (Jamis Buck)
 Chapter 3: Matrices
 */
 
-pub struct Matrix2 (
-    [[f32;2];2]
-);
-
-#[derive(Debug)]
-pub struct Matrix3 (
-    [[f32;3];3]
-);
+/*
+One matrix type for every size
+------------------------------
+Matrix<R, C> is R rows by C columns. Both default to 4, so a plain
+`Matrix` is still the 4x4 transform used everywhere else. Matrix3 (normal
+matrices) and Matrix2 (texture transforms) are just other sizes of the same
+type. Build any size the same way:
+
+    let m: Matrix2 = Matrix([[1.0,2.0],[3.0,4.0]]);
+
+Determinants, minors and cofactors use Laplace expansion over row/column
+bitmasks instead of building submatrices, so nothing allocates. That costs
+O(n!), which is fine for n <= 4 but not for large matrices.
+*/
 
 #[derive(Debug, Copy, Clone, PartialEq)]
-pub struct Matrix (
-    pub [[f32;4];4]
+pub struct Matrix<const R: usize = 4, const C: usize = 4> (
+    pub [[Float;C];R]
 );
 
-pub fn multiply_matrix(a:Matrix, b:Matrix) -> Matrix {
-    let mut result = Matrix([
-        [0.0,0.0,0.0,0.0],
-        [0.0,0.0,0.0,0.0],
-        [0.0,0.0,0.0,0.0],
-        [0.0,0.0,0.0,0.0]
-    ]);
+pub type Matrix2 = Matrix<2, 2>;
+pub type Matrix3 = Matrix<3, 3>;
+pub type Matrix4 = Matrix<4, 4>;
+
+impl<const R: usize, const C: usize> Matrix<R, C> {
+    pub fn zeros() -> Matrix<R, C> {
+        Matrix([[0.0;C];R])
+    }
+
+    pub fn transpose(&self) -> Matrix<C, R> {
+        let mut at = Matrix::<C, R>::zeros();
+        for row in 0..R {
+            for col in 0..C {
+                at.0[col][row] = self.0[row][col];
+            }
+        }
+        at
+    }
+
+    pub fn submatrix<const R2: usize, const C2: usize>(&self, row:usize, col:usize) -> Matrix<R2, C2> {
+        // drops one row and one column; R2/C2 must be R-1/C-1
+        const { assert!(R2 + 1 == R && C2 + 1 == C, "submatrix must be one row and one column smaller") };
+        let mut sub = Matrix::<R2, C2>::zeros();
+        for (i, src_row) in (0..R).filter(|&i| i != row).enumerate() {
+            for (j, src_col) in (0..C).filter(|&j| j != col).enumerate() {
+                sub.0[i][j] = self.0[src_row][src_col];
+            }
+        }
+        sub
+    }
+}
+
+impl<const N: usize> Matrix<N, N> {
+    pub fn identity() -> Matrix<N, N> {
+        let mut m = Matrix::<N, N>::zeros();
+        for i in 0..N {
+            m.0[i][i] = 1.0;
+        }
+        m
+    }
+
+    pub fn determinant(&self) -> Float {
+        laplace(&self.0, all_bits(N), all_bits(N))
+    }
+
+    pub fn minor(&self, row:usize, col:usize) -> Float {
+        // determinant of the submatrix without `row` and `col`
+        assert!(row < N && col < N, "minor({}, {}) of a {}x{} matrix", row, col, N, N);
+        laplace(&self.0, all_bits(N) & !(1 << row), all_bits(N) & !(1 << col))
+    }
+
+    pub fn cofactor(&self, row:usize, col:usize) -> Float {
+        if (row + col).is_multiple_of(2) {
+            self.minor(row, col)
+        } else {
+            -self.minor(row, col)
+        }
+    }
+
+    pub fn inverse(&self) -> Result<Matrix<N, N>, InvertError> {
+        // adjugate / determinant; for 4x4 transforms invert() is much faster
+        let det_a = self.determinant();
+        if det_a == 0.0 || !det_a.is_finite() {
+            return Err(InvertError::Singular);
+        }
+        let mut inv = Matrix::<N, N>::zeros();
+        for row in 0..N {
+            for col in 0..N {
+                inv.0[col][row] = self.cofactor(row, col) / det_a;
+            }
+        }
+        Ok(inv)
+    }
+}
+
+impl<const R: usize, const C: usize> tuples::approx::ApproxEq for Matrix<R, C> {
+    fn mismatches(&self, other:&Matrix<R, C>, tol:tuples::approx::Tolerance) -> Vec<tuples::approx::Mismatch> {
+        let mut mismatches = vec![];
+        for row in 0..R {
+            for col in 0..C {
+                let (left, right) = (self.0[row][col], other.0[row][col]);
+                if !tuples::approx::floats_equal(left, right, tol) {
+                    mismatches.push(tuples::approx::Mismatch {
+                        component: format!("[{}][{}]", row, col),
+                        left,
+                        right
+                    });
+                }
+            }
+        }
+        mismatches
+    }
+}
+
+fn all_bits(n:usize) -> u64 {
+    assert!(n <= 64, "matrices larger than 64x64 are not supported");
+    if n == 64 { u64::MAX } else { (1 << n) - 1 }
+}
+
+fn laplace<const N: usize>(m:&[[Float;N];N], rows:u64, cols:u64) -> Float {
+    // Determinant of the submatrix made of the rows/columns whose bits are
+    // set, expanded along its first row.
+    if rows == 0 {
+        return 1.0;
+    }
+    let row = rows.trailing_zeros() as usize;
+    let rest = rows & !(1 << row);
+
+    let mut det = 0.0;
+    let mut sign = 1.0;
+    for col in (0..N).filter(|&j| cols & (1 << j) != 0) {
+        if m[row][col] != 0.0 {
+            det += sign * m[row][col] * laplace(m, rest, cols & !(1 << col));
+        }
+        sign = -sign;
+    }
+    det
+}
+
+pub fn multiply_matrix<const R: usize, const K: usize, const C: usize>(
+    a:Matrix<R, K>,
+    b:Matrix<K, C>
+) -> Matrix<R, C> {
+    // 4x4 (nearly every call) goes through the SIMD kernel; the check is on
+    // constants, so it compiles away.
+    if R == 4 && K == 4 && C == 4 {
+        let product = tuples::simd::mat_mul(&lanes4x4(&a), &lanes4x4(&b));
+        let mut result = Matrix::<R, C>::zeros();
+        for (out, row) in result.0.iter_mut().zip(product.iter()) {
+            out.copy_from_slice(&row[..C]);
+        }
+        return result;
+    }
+    multiply_matrix_scalar(a, b)
+}
+
+// Copies a matrix that is known to be 4x4 into the SIMD layout.
+fn lanes4x4<const R: usize, const C: usize>(m:&Matrix<R, C>) -> tuples::simd::Lanes4x4 {
+    let mut out = [[0.0; 4]; 4];
+    for (out, row) in out.iter_mut().zip(m.0.iter()) {
+        out.copy_from_slice(row);
+    }
+    out
+}
+
+pub fn multiply_matrix_scalar<const R: usize, const K: usize, const C: usize>(
+    a:Matrix<R, K>,
+    b:Matrix<K, C>
+) -> Matrix<R, C> {
+    let mut result = Matrix::<R, C>::zeros();
 
-    for row in 0..4 {
-        for col in 0..4 {
+    for row in 0..R {
+        for col in 0..C {
             let mut sum = 0.0;
-            for n in 0..4 {
+            for n in 0..K {
                 sum += a.0[row][n] * b.0[n][col];
             }
             result.0[row][col] = sum;
@@ -43,159 +205,279 @@ pub fn multiply_matrix(a:Matrix, b:Matrix) -> Matrix {
     result
 }
 
-pub fn multiply_matrix_tup(a:Matrix, b:tuples::Tuple) -> tuples::Tuple {
-    let mut result = tuples::Tuple{x:0.0,y:0.0,z:0.0,w:0.0};
-    result.x = a.0[0][0] * b.x + a.0[0][1] * b.y + a.0[0][2] * b.z + a.0[0][3] * b.w;
-    result.y = a.0[1][0] * b.x + a.0[1][1] * b.y + a.0[1][2] * b.z + a.0[1][3] * b.w;
-    result.z = a.0[2][0] * b.x + a.0[2][1] * b.y + a.0[2][2] * b.z + a.0[2][3] * b.w;
-    result.w = a.0[3][0] * b.x + a.0[3][1] * b.y + a.0[3][2] * b.z + a.0[3][3] * b.w;
+impl<const R: usize, const K: usize, const C: usize> Mul<Matrix<K, C>> for Matrix<R, K> {
+    type Output = Matrix<R, C>;
+    fn mul(self, b:Matrix<K, C>) -> Matrix<R, C> {
+        multiply_matrix(self, b)
+    }
+}
 
-    result
+impl Mul<tuples::Tuple> for Matrix {
+    type Output = tuples::Tuple;
+    fn mul(self, b:tuples::Tuple) -> tuples::Tuple {
+        tuples::Tuple::from_lanes(tuples::simd::mat_vec(&self.0, b.to_lanes()))
+    }
 }
 
-pub fn transpose(a:Matrix) -> Matrix {
-    let mut at = a.clone();
-    at.0[0][1] = a.0[1][0];
-    at.0[0][2] = a.0[2][0];
-    at.0[0][3] = a.0[3][0];
-    at.0[1][0] = a.0[0][1];
-    at.0[1][2] = a.0[2][1];
-    at.0[1][3] = a.0[3][1];
-    at.0[2][0] = a.0[0][2];
-    at.0[2][1] = a.0[1][2];
-    at.0[2][3] = a.0[3][2];
-    at.0[3][0] = a.0[0][3];
-    at.0[3][1] = a.0[1][3];
-    at.0[3][2] = a.0[2][3];
+// Points pick up the translation column, vectors don't. The bottom row is
+// assumed to be (0, 0, 0, 1), which holds for every transform in this file.
+impl Mul<tuples::Point3> for Matrix {
+    type Output = tuples::Point3;
+    fn mul(self, b:tuples::Point3) -> tuples::Point3 {
+        tuples::Point3::from(self * tuples::Tuple::from(b))
+    }
+}
 
-    at
+impl Mul<tuples::Vector3> for Matrix {
+    type Output = tuples::Vector3;
+    fn mul(self, b:tuples::Vector3) -> tuples::Vector3 {
+        tuples::Vector3::from(self * tuples::Tuple::from(b))
+    }
 }
 
-pub fn determinant_2x2(a:Matrix2) -> f32 {
-    a.0[0][0] * a.0[1][1] - a.0[0][1] * a.0[1][0]
+// Works for Tuple, Point3 and Vector3, and hands back the same kind.
+pub fn multiply_matrix_tup<T>(a:Matrix, b:T) -> T
+where
+    Matrix: Mul<T, Output = T>,
+{
+    a * b
 }
 
-pub fn determinant_3x3(a:Matrix3) -> f32 {
-    let sub1 = determinant_2x2(
-        Matrix2([
-            [a.0[1][1], a.0[1][2]],
-            [a.0[2][1], a.0[2][2]]
-        ])
-    );
+impl Mul<tuples::Vector3> for Matrix3 {
+    type Output = tuples::Vector3;
+    fn mul(self, b:tuples::Vector3) -> tuples::Vector3 {
+        let m = self.0;
+        tuples::vector3(
+            m[0][0] * b.x + m[0][1] * b.y + m[0][2] * b.z,
+            m[1][0] * b.x + m[1][1] * b.y + m[1][2] * b.z,
+            m[2][0] * b.x + m[2][1] * b.y + m[2][2] * b.z)
+    }
+}
 
-    let sub2 = determinant_2x2(
-        Matrix2([
-            [a.0[1][0], a.0[1][2]],
-            [a.0[2][0], a.0[2][2]]
-        ])
-    );
+pub fn transpose<const R: usize, const C: usize>(a:Matrix<R, C>) -> Matrix<C, R> {
+    a.transpose()
+}
 
-    let sub3 = determinant_2x2(
-        Matrix2([
-            [a.0[1][0], a.0[1][1]],
-            [a.0[2][0], a.0[2][1]]
-        ])
-    );
+pub fn determinant<const N: usize>(a:Matrix<N, N>) -> Float {
+    a.determinant()
+}
 
-    a.0[0][0] * sub1
-    - a.0[0][1] * sub2
-    + a.0[0][2] * sub3
+pub fn normal_matrix(a:Matrix) -> Result<Matrix3, InvertError> {
+    // inverse-transpose of the upper-left 3x3 block, for transforming normals
+    let upper: Matrix3 = a.submatrix(3, 3);
+    Ok(transpose(upper.inverse()?))
 }
 
-pub fn determinant_4x4(a:Matrix) -> f32 {
-    let sub1 = determinant_3x3(
-        Matrix3([
-            [a.0[1][1], a.0[1][2], a.0[1][3]],
-            [a.0[2][1], a.0[2][2], a.0[2][3]],
-            [a.0[3][1], a.0[3][2], a.0[3][3]]
-        ])
-    );
+/*
+Fast inversion
+--------------
+invert() is called for every ray, so it avoids the cofactor-by-cofactor
+route (16 cofactors, each a recursive expansion). Matrices whose
+bottom row is (0, 0, 0, 1) are affine, which covers everything that
+translation, scaling, shearing and the rotations produce. For those we
+only invert the 3x3 block:
+
+    | A  t |^-1   | A^-1  -A^-1 t |
+    | 0  1 |    = |  0       1    |
+
+Anything else goes through the closed-form inverse built from the
+twelve 2x2 minors of the top and bottom row pairs.
+*/
 
-    let sub2 = determinant_3x3(
-        Matrix3([
-            [a.0[1][0], a.0[1][2], a.0[1][3]],
-            [a.0[2][0], a.0[2][2], a.0[2][3]],
-            [a.0[3][0], a.0[3][2], a.0[3][3]]
-        ])
-    );
+pub fn invert(a:Matrix) -> Matrix {
+    if is_affine(a) {
+        invert_affine(a)
+    } else {
+        invert_general(a)
+    }
+}
 
-    let sub3 = determinant_3x3(
-        Matrix3([
-            [a.0[1][0], a.0[1][1], a.0[1][3]],
-            [a.0[2][0], a.0[2][1], a.0[2][3]],
-            [a.0[3][0], a.0[3][1], a.0[3][3]]
-        ])
-    );
+pub fn is_affine(a:Matrix) -> bool {
+    a.0[3] == [0.0,0.0,0.0,1.0]
+}
 
-    let sub4 = determinant_3x3(
-        Matrix3([
-            [a.0[1][0], a.0[1][1], a.0[1][2]],
-            [a.0[2][0], a.0[2][1], a.0[2][2]],
-            [a.0[3][0], a.0[3][1], a.0[3][2]]
-        ])
-    );
+pub fn invert_general(a:Matrix) -> Matrix {
+    let m = a.0;
+
+    // 2x2 minors of rows 0-1 (s) and rows 2-3 (c)
+    let s0 = m[0][0] * m[1][1] - m[1][0] * m[0][1];
+    let s1 = m[0][0] * m[1][2] - m[1][0] * m[0][2];
+    let s2 = m[0][0] * m[1][3] - m[1][0] * m[0][3];
+    let s3 = m[0][1] * m[1][2] - m[1][1] * m[0][2];
+    let s4 = m[0][1] * m[1][3] - m[1][1] * m[0][3];
+    let s5 = m[0][2] * m[1][3] - m[1][2] * m[0][3];
+
+    let c5 = m[2][2] * m[3][3] - m[3][2] * m[2][3];
+    let c4 = m[2][1] * m[3][3] - m[3][1] * m[2][3];
+    let c3 = m[2][1] * m[3][2] - m[3][1] * m[2][2];
+    let c2 = m[2][0] * m[3][3] - m[3][0] * m[2][3];
+    let c1 = m[2][0] * m[3][2] - m[3][0] * m[2][2];
+    let c0 = m[2][0] * m[3][1] - m[3][0] * m[2][1];
 
-    a.0[0][0] * sub1
-    - a.0[0][1] * sub2
-    + a.0[0][2] * sub3
-    - a.0[0][3] * sub4
+    let det_a = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+    let inv = 1.0 / det_a;
+
+    Matrix([
+        [
+            ( m[1][1] * c5 - m[1][2] * c4 + m[1][3] * c3) * inv,
+            (-m[0][1] * c5 + m[0][2] * c4 - m[0][3] * c3) * inv,
+            ( m[3][1] * s5 - m[3][2] * s4 + m[3][3] * s3) * inv,
+            (-m[2][1] * s5 + m[2][2] * s4 - m[2][3] * s3) * inv,
+        ],
+        [
+            (-m[1][0] * c5 + m[1][2] * c2 - m[1][3] * c1) * inv,
+            ( m[0][0] * c5 - m[0][2] * c2 + m[0][3] * c1) * inv,
+            (-m[3][0] * s5 + m[3][2] * s2 - m[3][3] * s1) * inv,
+            ( m[2][0] * s5 - m[2][2] * s2 + m[2][3] * s1) * inv,
+        ],
+        [
+            ( m[1][0] * c4 - m[1][1] * c2 + m[1][3] * c0) * inv,
+            (-m[0][0] * c4 + m[0][1] * c2 - m[0][3] * c0) * inv,
+            ( m[3][0] * s4 - m[3][1] * s2 + m[3][3] * s0) * inv,
+            (-m[2][0] * s4 + m[2][1] * s2 - m[2][3] * s0) * inv,
+        ],
+        [
+            (-m[1][0] * c3 + m[1][1] * c1 - m[1][2] * c0) * inv,
+            ( m[0][0] * c3 - m[0][1] * c1 + m[0][2] * c0) * inv,
+            (-m[3][0] * s3 + m[3][1] * s1 - m[3][2] * s0) * inv,
+            ( m[2][0] * s3 - m[2][1] * s1 + m[2][2] * s0) * inv,
+        ],
+    ])
 }
 
-pub fn det_submatrix(a:Matrix, row:usize, col:usize) -> f32 {
-    let mut matrix_vec:Vec<f32> = vec![];
+pub fn invert_affine(a:Matrix) -> Matrix {
+    // assumes is_affine(a)
+    let m = a.0;
+
+    // adjugate of the upper-left 3x3 block
+    let r00 = m[1][1] * m[2][2] - m[1][2] * m[2][1];
+    let r01 = m[0][2] * m[2][1] - m[0][1] * m[2][2];
+    let r02 = m[0][1] * m[1][2] - m[0][2] * m[1][1];
+    let r10 = m[1][2] * m[2][0] - m[1][0] * m[2][2];
+    let r11 = m[0][0] * m[2][2] - m[0][2] * m[2][0];
+    let r12 = m[0][2] * m[1][0] - m[0][0] * m[1][2];
+    let r20 = m[1][0] * m[2][1] - m[1][1] * m[2][0];
+    let r21 = m[0][1] * m[2][0] - m[0][0] * m[2][1];
+    let r22 = m[0][0] * m[1][1] - m[0][1] * m[1][0];
+
+    let det_a = m[0][0] * r00 + m[0][1] * r10 + m[0][2] * r20;
+    let inv = 1.0 / det_a;
+
+    let b = [
+        [r00 * inv, r01 * inv, r02 * inv],
+        [r10 * inv, r11 * inv, r12 * inv],
+        [r20 * inv, r21 * inv, r22 * inv],
+    ];
+    let (tx, ty, tz) = (m[0][3], m[1][3], m[2][3]);
+
+    Matrix([
+        [b[0][0], b[0][1], b[0][2], -(b[0][0] * tx + b[0][1] * ty + b[0][2] * tz)],
+        [b[1][0], b[1][1], b[1][2], -(b[1][0] * tx + b[1][1] * ty + b[1][2] * tz)],
+        [b[2][0], b[2][1], b[2][2], -(b[2][0] * tx + b[2][1] * ty + b[2][2] * tz)],
+        [0.0,0.0,0.0,1.0]
+    ])
+}
+
+// The original cofactor-expansion inverse. Slow; kept as the reference the
+// fast paths are checked and benchmarked against.
+pub fn invert_cofactors(a:Matrix) -> Matrix {
+    let det_a = determinant(a);
+    let mut temp1 = Matrix::zeros();
+
     for i in 0..4 {
-        if i != row {
-            for j in a.0[i] {
-                matrix_vec.push(j);
-            }
+        for j in 0..4 {
+            temp1.0[i][j] = a.cofactor(i, j) / det_a;
         }
     }
 
-    matrix_vec.remove(col);
-    matrix_vec.remove(col + 3);
-    matrix_vec.remove(col + 6);
-
-    let submatrix = Matrix3([
-        [matrix_vec[0],matrix_vec[1],matrix_vec[2]],
-        [matrix_vec[3],matrix_vec[4],matrix_vec[5]],
-        [matrix_vec[6],matrix_vec[7],matrix_vec[8]]
-    ]);
+    transpose(temp1)
+}
 
-    // submatrix
+/*
+Checked inversion
+-----------------
+invert() divides by the determinant no matter what, so a singular matrix
+comes back full of NaN/inf. try_invert() refuses only matrices it can't
+invert: Singular for a zero or non-finite determinant, IllConditioned
+with an infinite condition number when the inverse overflows. Strongly
+non-uniform scales like scaling(1e-3, 1e3, 1.0) are ordinary transforms.
+
+condition_number() is the 1-norm condition ||A|| * ||A^-1||. The relative
+error of the inverse is roughly condition * EPSILON, so callers that need
+an accurate inverse can set a limit with try_invert_with(), which also
+returns IllConditioned, carrying the estimate, for anything above it.
+*/
 
-    determinant_3x3(submatrix)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InvertError {
+    // determinant is zero or not finite
+    Singular,
+    // the 1-norm condition number estimate is too large (infinite when the
+    // inverse overflows)
+    IllConditioned{condition:Float},
 }
 
-// pub fn invert(a:Matrix) -> Result<Matrix, String> {
-//     // Check if invertible
-//     if determinant_4x4(a) != 0.0 {
-//         Ok(a)
-//     } else {
-//         Err(String::from("Matrix not invertible"))
-//     }
-    
-// }
+impl fmt::Display for InvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InvertError::Singular => write!(f, "Matrix not invertible (determinant is 0 or not finite)"),
+            InvertError::IllConditioned{condition} => {
+                write!(f, "Matrix too ill-conditioned to invert (condition number {:e})", condition)
+            }
+        }
+    }
+}
 
-pub fn invert(a:Matrix) -> Matrix {
-    let det_a = determinant_4x4(a);
-    let mut temp1 = Matrix([
-        [0.0,0.0,0.0,0.0],
-        [0.0,0.0,0.0,0.0],
-        [0.0,0.0,0.0,0.0],
-        [0.0,0.0,0.0,0.0]
-    ]);
+impl std::error::Error for InvertError {}
 
-    for i in 0..4 {
-        for j in 0..4 {
-            temp1.0[i][j] = (-1.0_f32).powf((i + j) as f32) * det_submatrix(a,i,j) / det_a;
+pub fn norm_1(a:Matrix) -> Float {
+    // largest absolute column sum
+    let mut norm = 0.0;
+    for col in 0..4 {
+        let sum = a.0.iter().map(|row| row[col].abs()).sum::<Float>();
+        if sum > norm {
+            norm = sum;
         }
     }
+    norm
+}
 
-    transpose(temp1)
+pub fn condition_number(a:Matrix) -> Float {
+    // infinite for singular matrices
+    let det_a = determinant(a);
+    if det_a == 0.0 || !det_a.is_finite() {
+        return Float::INFINITY;
+    }
+    let condition = norm_1(a) * norm_1(invert(a));
+    if condition.is_finite() { condition } else { Float::INFINITY }
+}
+
+pub fn try_invert(a:Matrix) -> Result<Matrix, InvertError> {
+    let det_a = determinant(a);
+    if det_a == 0.0 || !det_a.is_finite() {
+        return Err(InvertError::Singular);
+    }
+
+    let inverse = invert(a);
+    if inverse.0.iter().flatten().any(|v| !v.is_finite()) {
+        return Err(InvertError::IllConditioned{condition: Float::INFINITY});
+    }
+    Ok(inverse)
+}
+
+pub fn try_invert_with(a:Matrix, max_condition:Float) -> Result<Matrix, InvertError> {
+    // try_invert, also refusing a condition number above max_condition
+    let inverse = try_invert(a)?;
+    let condition = norm_1(a) * norm_1(inverse);
+    if condition > max_condition {
+        return Err(InvertError::IllConditioned{condition});
+    }
+    Ok(inverse)
 }
 
 #[allow(dead_code)]
 pub fn matrix_tests() {
+    use tuples::approx::{assert_approx_eq, ApproxEq, Tolerance};
     let matrix1 = Matrix([
         [1.0,1.0,1.0,1.0],
         [1.0,1.0,1.0,1.0],
@@ -224,12 +506,12 @@ pub fn matrix_tests() {
         [0.0,0.0,0.0,1.0]
     ]);
 
-    let a2x2 = Matrix2([
+    let a2x2: Matrix2 = Matrix([
         [1.0,2.0],
         [3.0,4.0]
     ]);
 
-    let b3x3 = Matrix3([
+    let b3x3: Matrix3 = Matrix([
         [1.0,2.0,6.0],
         [-5.0,8.0,-4.0],
         [2.0,6.0,4.0]
@@ -280,14 +562,14 @@ pub fn matrix_tests() {
 
     println!("{:?}", transpose(c));
 
-    println!("{:?}", determinant_2x2(a2x2));
-    println!("{:?}", determinant_3x3(b3x3));
-    println!("{:?}",determinant_4x4(e));
+    println!("{:?}", determinant(a2x2));
+    println!("{:?}", determinant(b3x3));
+    println!("{:?}",determinant(e));
 
     println!("{:?}", invert(e));
     println!("{:?}", invert(det0));
 
-    println!("{:?}", det_submatrix(f1,0,1));
+    println!("{:?}", f1.minor(0,1));
 
     println!("\n{:?}", invert(f1));
     println!("\n{:?}", invert(f2));
@@ -313,6 +595,60 @@ pub fn matrix_tests() {
     println!("\n{:?}", invert(transpose(f1)));
     println!("\n{:?}", transpose(invert(f1)));
     println!("{}", invert(transpose(f1)) == transpose(invert(f1)));
+
+    // Fast inversion tests:- every path must agree with the cofactor version
+    let tol = Tolerance::absolute(1e-4);
+    for m in [a, b, c, e, f1, f2, f3, identity] {
+        if determinant(m) != 0.0 {
+            assert_approx_eq!(invert_general(m), invert_cofactors(m), tol);
+            assert_approx_eq!(invert(m), invert_cofactors(m), tol);
+        }
+    }
+    let affine = multiply_matrix(
+        multiply_matrix(translation(10.0,5.0,7.0), rotation_x(0.7)),
+        multiply_matrix(scaling(2.0,3.0,0.5), shearing(1.0,0.0,0.5,0.0,0.0,1.0))
+    );
+    assert!(is_affine(affine) && !is_affine(f1));
+    assert_approx_eq!(invert_affine(affine), invert_cofactors(affine), tol);
+    assert_approx_eq!(multiply_matrix(affine, invert(affine)), identity, tol);
+
+    // Checked inversion tests:-
+    assert_eq!(try_invert(det0), Err(InvertError::Singular));
+    assert_eq!(try_invert(scaling(0.0,1.0,1.0)), Err(InvertError::Singular));
+    assert_eq!(try_invert(identity), Ok(identity));
+    assert_eq!(try_invert(e), Ok(invert(e)));
+    assert!(try_invert(translation(5.0,-3.0,2.0)).is_ok());
+    assert_eq!(condition_number(scaling(1.0,1.0,1.0)), 1.0);
+    assert_eq!(condition_number(scaling(2.0,0.5,1.0)), 4.0);
+    assert_eq!(condition_number(det0), Float::INFINITY);
+
+    let nearly_singular = Matrix([
+        [1.0,1.0,0.0,0.0],
+        [1.0,1.0 + 4.0 * Float::EPSILON,0.0,0.0],
+        [0.0,0.0,1.0,0.0],
+        [0.0,0.0,0.0,1.0]
+    ]);
+    // badly conditioned but invertible: only the diagnostic notices
+    assert!(try_invert(nearly_singular).is_ok());
+    assert!(condition_number(nearly_singular) > 0.01 / Float::EPSILON);
+    let squashed = scaling(1e-3,1e3,1.0);
+    assert!((condition_number(squashed) / 1e6 - 1.0).abs() < 1e-4);
+    assert_eq!(try_invert(squashed), Ok(invert(squashed)));
+    assert_eq!(try_invert(det0).unwrap_err().to_string(), "Matrix not invertible (determinant is 0 or not finite)");
+    // a nonzero determinant whose inverse overflows
+    let overflowing = scaling(Float::MIN_POSITIVE / 16.0,1.0,1.0);
+    assert_ne!(determinant(overflowing), 0.0);
+    assert_eq!(try_invert(overflowing), Err(InvertError::IllConditioned{condition: Float::INFINITY}));
+    // an explicit limit on the condition number
+    assert_eq!(try_invert_with(squashed, 1e7), Ok(invert(squashed)));
+    match try_invert_with(squashed, 1e5) {
+        Err(InvertError::IllConditioned{condition}) => assert!((condition / 1e6 - 1.0).abs() < 1e-4),
+        other => panic!("{:?}", other),
+    }
+    assert!(try_invert_with(nearly_singular, 1e6).is_err());
+    assert_eq!(try_invert_with(det0, 1e6), Err(InvertError::Singular));
+    assert_eq!(try_invert_with(identity, 1.0), Ok(identity));
+    assert!(try_invert_with(squashed, 1e5).unwrap_err().to_string().starts_with("Matrix too ill-conditioned"));
 }
 
 
@@ -330,7 +666,7 @@ Note to self: Whenever I need tuples and matrices in a file,
 always write matrix::tuples::<method/item/whatever>
 */
 
-pub fn translation(x:f32, y:f32, z:f32) -> Matrix {
+pub fn translation(x:Float, y:Float, z:Float) -> Matrix {
     Matrix([
         [1.0,0.0,0.0,x],
         [0.0,1.0,0.0,y],
@@ -339,7 +675,7 @@ pub fn translation(x:f32, y:f32, z:f32) -> Matrix {
     ])
 }
 
-pub fn scaling(x:f32, y:f32, z:f32) -> Matrix {
+pub fn scaling(x:Float, y:Float, z:Float) -> Matrix {
     Matrix([
         [x,0.0,0.0,0.0],
         [0.0,y,0.0,0.0],
@@ -351,7 +687,7 @@ pub fn scaling(x:f32, y:f32, z:f32) -> Matrix {
     // and by 1 on the two other axes.
 }
 
-pub fn rotation_x(angle:f32) -> Matrix {
+pub fn rotation_x(angle:Float) -> Matrix {
     Matrix([
         [1.0,0.0,0.0,0.0],
         [0.0,angle.cos(), -angle.sin(),0.0],
@@ -360,7 +696,7 @@ pub fn rotation_x(angle:f32) -> Matrix {
     ])
 }
 
-pub fn rotation_y(angle:f32) -> Matrix {
+pub fn rotation_y(angle:Float) -> Matrix {
     Matrix([
         [angle.cos(),0.0,angle.sin(),0.0],
         [0.0,1.0,0.0,0.0],
@@ -369,7 +705,7 @@ pub fn rotation_y(angle:f32) -> Matrix {
     ])
 }
 
-pub fn rotation_z(angle:f32) -> Matrix {
+pub fn rotation_z(angle:Float) -> Matrix {
     Matrix([
         [angle.cos(), -angle.sin(),0.0,0.0],
         [angle.sin(), angle.cos(),0.0,0.0],
@@ -379,12 +715,12 @@ pub fn rotation_z(angle:f32) -> Matrix {
 }
 
 pub fn shearing(
-    xy:f32, 
-    xz:f32, 
-    yx:f32, 
-    yz:f32, 
-    zx:f32, 
-    zy:f32
+    xy:Float, 
+    xz:Float, 
+    yx:Float, 
+    yz:Float, 
+    zx:Float, 
+    zy:Float
 ) -> Matrix {
     Matrix([
         [1.0,xy,xz,0.0],
@@ -394,6 +730,151 @@ pub fn shearing(
     ])
 }
 
+pub fn identity() -> Matrix {
+    Matrix([
+        [1.0,0.0,0.0,0.0],
+        [0.0,1.0,0.0,0.0],
+        [0.0,0.0,1.0,0.0],
+        [0.0,0.0,0.0,1.0]
+    ])
+}
+
+pub fn rotation(axis:tuples::Vector3, angle:Float) -> Matrix {
+    // Rodrigues' formula: rotation by `angle` about `axis`, with the same
+    // handedness as rotation_x/y/z (rotation(x-axis, a) == rotation_x(a)).
+    let k = axis.normalize();
+    let (s, c) = angle.sin_cos();
+    let t = 1.0 - c;
+    Matrix([
+        [c + k.x * k.x * t, k.x * k.y * t - k.z * s, k.x * k.z * t + k.y * s, 0.0],
+        [k.y * k.x * t + k.z * s, c + k.y * k.y * t, k.y * k.z * t - k.x * s, 0.0],
+        [k.z * k.x * t - k.y * s, k.z * k.y * t + k.x * s, c + k.z * k.z * t, 0.0],
+        [0.0,0.0,0.0,1.0]
+    ])
+}
+
+// Camera basis for an eye at `from` looking at `to`: (left, true up, forward).
+// An `up` that is zero or parallel to the view direction says nothing about
+// which way is left, so the world y axis stands in for it (the world z axis
+// when looking mostly up or down).
+fn camera_basis(
+    from:tuples::Point3,
+    to:tuples::Point3,
+    up:tuples::Vector3
+) -> (tuples::Vector3, tuples::Vector3, tuples::Vector3) {
+    // `left` is normalized so the basis stays orthonormal even when `up`
+    // isn't perpendicular to the view direction.
+    let forward = (to - from).normalize();
+    let left = forward.cross(up.normalize());
+    let left = if left.magnitude() > Float::EPSILON {
+        left
+    } else if forward.y.abs() < 0.9 {
+        forward.cross(tuples::vector3(0.0,1.0,0.0))
+    } else {
+        forward.cross(tuples::vector3(0.0,0.0,1.0))
+    }.normalize();
+    let true_up = left.cross(forward);
+    (left, true_up, forward)
+}
+
+pub fn view_transform(from:tuples::Point3, to:tuples::Point3, up:tuples::Vector3) -> Matrix {
+    // World space -> eye space, for an eye at `from` looking toward `to`.
+    // The eye looks down its own -z axis.
+    let (left, true_up, forward) = camera_basis(from, to, up);
+    let orientation = Matrix([
+        [left.x,left.y,left.z,0.0],
+        [true_up.x,true_up.y,true_up.z,0.0],
+        [-forward.x,-forward.y,-forward.z,0.0],
+        [0.0,0.0,0.0,1.0]
+    ]);
+    multiply_matrix(orientation, translation(-from.x, -from.y, -from.z))
+}
+
+pub fn look_at(from:tuples::Point3, to:tuples::Point3, up:tuples::Vector3) -> Matrix {
+    // Object space -> world space: puts an object at `from` with its -z axis
+    // pointing at `to`. This is the inverse of view_transform, built directly.
+    let (left, true_up, forward) = camera_basis(from, to, up);
+    Matrix([
+        [left.x,true_up.x,-forward.x,from.x],
+        [left.y,true_up.y,-forward.y,from.y],
+        [left.z,true_up.z,-forward.z,from.z],
+        [0.0,0.0,0.0,1.0]
+    ])
+}
+
+/*
+Fluent transforms
+-----------------
+Chaining by hand has to be written inside-out,
+multiply_matrix(multiply_matrix(c, b), a). Transform applies its steps in
+reading order instead:
+
+    Transform::identity().rotate_x(pi/2.0).scale(5.0,5.0,5.0).translate(10.0,5.0,7.0)
+
+rotates first, then scales, then translates. Each step multiplies on the left.
+*/
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform (
+    pub Matrix
+);
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform(identity())
+    }
+
+    pub fn then(self, m:Matrix) -> Transform {
+        Transform(multiply_matrix(m, self.0))
+    }
+
+    pub fn translate(self, x:Float, y:Float, z:Float) -> Transform {
+        self.then(translation(x, y, z))
+    }
+
+    pub fn scale(self, x:Float, y:Float, z:Float) -> Transform {
+        self.then(scaling(x, y, z))
+    }
+
+    pub fn rotate_x(self, angle:Float) -> Transform {
+        self.then(rotation_x(angle))
+    }
+
+    pub fn rotate_y(self, angle:Float) -> Transform {
+        self.then(rotation_y(angle))
+    }
+
+    pub fn rotate_z(self, angle:Float) -> Transform {
+        self.then(rotation_z(angle))
+    }
+
+    pub fn rotate(self, axis:tuples::Vector3, angle:Float) -> Transform {
+        self.then(rotation(axis, angle))
+    }
+
+    pub fn shear(self, xy:Float, xz:Float, yx:Float, yz:Float, zx:Float, zy:Float) -> Transform {
+        self.then(shearing(xy, xz, yx, yz, zx, zy))
+    }
+
+    pub fn rotate_by(self, q:quaternion::Quaternion) -> Transform {
+        self.then(q.to_matrix())
+    }
+
+    pub fn look_at(self, from:tuples::Point3, to:tuples::Point3, up:tuples::Vector3) -> Transform {
+        self.then(look_at(from, to, up))
+    }
+
+    pub fn build(self) -> Matrix {
+        self.0
+    }
+}
+
+impl From<Transform> for Matrix {
+    fn from(t:Transform) -> Matrix {
+        t.0
+    }
+}
+
 #[allow(dead_code)]
 pub fn transform_tests() {
     let p1 = tuples::point(-3.0,4.0,5.0);
@@ -431,7 +912,7 @@ pub fn transform_tests() {
     println!("{:?}", tuples::normalize(multiply_matrix_tup(trans2, v2)));
 
     // Rotation tests:-
-    let pi:f32 = 3.14159265;
+    let pi = tuples::consts::PI;
     let p3 = tuples::point(0.0,1.0,0.0);
     let eighth_x = rotation_x(pi/4.0);
     let quarter_x = rotation_x(pi/2.0);
@@ -486,4 +967,225 @@ pub fn transform_tests() {
     let e = multiply_matrix(d, a);
     assert!(tuples::equal_tuples(p7_3,multiply_matrix_tup(e,p7_0)));
 
+    // Typed point/vector tests:- translation moves points but not vectors
+    let p8 = tuples::point3(-3.0,4.0,5.0);
+    let v8 = tuples::vector3(-3.0,4.0,5.0);
+    assert_eq!(multiply_matrix_tup(trans1, p8), tuples::point3(2.0,1.0,7.0));
+    assert_eq!(multiply_matrix_tup(trans1, v8), v8);
+    assert_eq!(trans3 * v8, tuples::vector3(-6.0,12.0,20.0));
+    assert!(tuples::equal_tuples((e * tuples::point3(1.0,0.0,1.0)).into(), p7_3));
+
+    // Approximate equality:- rotations are off by a few ULPs, not by EPSILON
+    use tuples::approx::{assert_approx_eq, ApproxEq, Tolerance};
+    let half = tuples::consts::FRAC_1_SQRT_2;
+    assert_approx_eq!(multiply_matrix_tup(eighth_x, p3), tuples::point(0.0,half,half));
+    assert_approx_eq!(multiply_matrix_tup(quarter_z, p5), tuples::point(-1.0,0.0,0.0));
+    assert_approx_eq!(multiply_matrix(rotation_x(pi/4.0), rotation_x(pi/4.0)), rotation_x(pi/2.0));
+    assert_approx_eq!(invert(eighth_y), transpose(eighth_y), Tolerance::absolute(1e-6));
+    assert!(!trans1.approx_eq(&trans2));
+    let wrong = trans1.mismatches(&translation(5.0,-3.0,2.5), Tolerance::default());
+    assert_eq!(wrong.len(), 1);
+    assert_eq!(wrong[0].component, "[2][3]");
+
+}
+
+#[allow(dead_code)]
+pub fn generic_matrix_tests() {
+    use tuples::approx::{assert_approx_eq, ApproxEq, Tolerance};
+    // Submatrices shrink by one row and column
+    let a3: Matrix3 = Matrix([
+        [1.0,5.0,0.0],
+        [-3.0,2.0,7.0],
+        [0.0,6.0,-3.0]
+    ]);
+    let s2: Matrix2 = a3.submatrix(0, 2);
+    assert_eq!(s2, Matrix([[-3.0,2.0],[0.0,6.0]]));
+
+    let a4 = Matrix([
+        [-6.0,1.0,1.0,6.0],
+        [-8.0,5.0,8.0,6.0],
+        [-1.0,0.0,8.0,2.0],
+        [-7.0,1.0,-1.0,1.0]
+    ]);
+    let s3: Matrix3 = a4.submatrix(2, 1);
+    assert_eq!(s3, Matrix([[-6.0,1.0,6.0],[-8.0,8.0,6.0],[-7.0,-1.0,1.0]]));
+
+    // Minors, cofactors and determinants at every size
+    let b3: Matrix3 = Matrix([
+        [3.0,5.0,0.0],
+        [2.0,-1.0,-7.0],
+        [6.0,-1.0,5.0]
+    ]);
+    let s: Matrix2 = b3.submatrix(1, 0);
+    assert_eq!(determinant(s), 25.0);
+    assert_eq!(b3.minor(1, 0), 25.0);
+    assert_eq!(b3.minor(0, 0), -12.0);
+    assert_eq!(b3.cofactor(0, 0), -12.0);
+    assert_eq!(b3.cofactor(1, 0), -25.0);
+    // out-of-range rows and columns panic rather than removing nothing
+    assert!(std::panic::catch_unwind(|| b3.minor(3, 0)).is_err());
+    assert!(std::panic::catch_unwind(|| b3.cofactor(0, 3)).is_err());
+
+    let c3: Matrix3 = Matrix([
+        [1.0,2.0,6.0],
+        [-5.0,8.0,-4.0],
+        [2.0,6.0,4.0]
+    ]);
+    assert_eq!([c3.cofactor(0, 0), c3.cofactor(0, 1), c3.cofactor(0, 2)], [56.0,12.0,-46.0]);
+    assert_eq!(c3.determinant(), -196.0);
+
+    let d4 = Matrix([
+        [-2.0,-8.0,3.0,5.0],
+        [-3.0,1.0,7.0,3.0],
+        [1.0,2.0,-9.0,6.0],
+        [-6.0,7.0,7.0,-9.0]
+    ]);
+    assert_eq!([d4.cofactor(0, 0), d4.cofactor(0, 1), d4.cofactor(0, 2), d4.cofactor(0, 3)], [690.0,447.0,210.0,51.0]);
+    assert_eq!(determinant(d4), -4071.0);
+    assert_eq!(determinant(Matrix([[1.0,2.0],[3.0,4.0]])), -2.0);
+    assert_eq!(determinant(Matrix([[7.0]])), 7.0);
+
+    // Identity, transpose and products of any shape
+    let i4: Matrix4 = Matrix::identity();
+    assert_eq!(i4, identity());
+    assert_eq!(Matrix3::identity().determinant(), 1.0);
+    let wide = Matrix([[1.0,2.0,3.0],[4.0,5.0,6.0]]);
+    let tall = transpose(wide);
+    assert_eq!(tall, Matrix([[1.0,4.0],[2.0,5.0],[3.0,6.0]]));
+    assert_eq!(wide * tall, Matrix([[14.0,32.0],[32.0,77.0]]));
+    assert_eq!(multiply_matrix(tall, wide).0[2], [27.0,36.0,45.0]);
+    assert_eq!(multiply_matrix(d4, i4), d4);
+
+    // Inverse at any size agrees with the 4x4 fast path
+    let tol = Tolerance::absolute(1e-5);
+    assert_approx_eq!(d4.inverse().unwrap(), invert(d4), tol);
+    let t2: Matrix2 = Matrix([[2.0,0.0],[1.0,0.5]]);
+    assert_eq!(t2.inverse(), Ok(Matrix([[0.5,0.0],[-1.0,2.0]])));
+    assert_eq!(Matrix([[1.0,2.0],[2.0,4.0]]).inverse(), Err(InvertError::Singular));
+    let round_trip = c3 * c3.inverse().unwrap() * tuples::vector3(1.0,2.0,3.0);
+    assert!((round_trip - tuples::vector3(1.0,2.0,3.0)).magnitude() < 1e-5);
+
+    // Normal matrix of a non-uniform scale
+    let n = normal_matrix(multiply_matrix(translation(5.0,0.0,0.0), scaling(2.0,4.0,1.0))).unwrap();
+    assert_eq!(n, Matrix([[0.5,0.0,0.0],[0.0,0.25,0.0],[0.0,0.0,1.0]]));
+    assert_eq!(normal_matrix(scaling(0.0,1.0,1.0)), Err(InvertError::Singular));
+
+    // The SIMD 4x4 product and matrix * tuple match the scalar loops exactly
+    let view = view_transform(tuples::point3(1.0,3.0,2.0), tuples::point3(4.0,-2.0,8.0), tuples::vector3(1.0,1.0,0.0));
+    for (x, y) in [(a4, d4), (view, rotation(tuples::vector3(1.0,2.0,-1.0), 0.8)), (invert(view), view)] {
+        assert_eq!(multiply_matrix(x, y), multiply_matrix_scalar(x, y));
+        let t = tuples::point(0.3,-1.7,2.9);
+        let expected = tuples::Tuple::from_lanes(tuples::simd::scalar::mat_vec(&x.0, t.to_lanes()));
+        assert!(tuples::equal_tuples(x * t, expected) && (x * t).x.to_bits() == expected.x.to_bits());
+    }
+}
+
+#[allow(dead_code)]
+pub fn builder_tests() {
+    use tuples::approx::{assert_approx_eq, ApproxEq, Tolerance};
+    let pi = tuples::consts::PI;
+    let tol = Tolerance::absolute(1e-5);
+
+    // Builder applies in reading order
+    let a = rotation_x(pi/2.0);
+    let b = scaling(5.0,5.0,5.0);
+    let c = translation(10.0,5.0,7.0);
+    let chained = Transform::identity()
+        .rotate_x(pi/2.0)
+        .scale(5.0,5.0,5.0)
+        .translate(10.0,5.0,7.0)
+        .build();
+    assert_approx_eq!(chained, multiply_matrix(multiply_matrix(c, b), a), tol);
+    assert_eq!(
+        chained * tuples::point3(1.0,0.0,1.0),
+        tuples::point3(15.0,0.0,7.0)
+    );
+    assert_eq!(Matrix::from(Transform::identity()), identity());
+    assert_approx_eq!(
+        Transform::identity().shear(1.0,0.0,0.0,0.0,0.0,0.0).rotate_y(0.3).build(),
+        multiply_matrix(rotation_y(0.3), shearing(1.0,0.0,0.0,0.0,0.0,0.0)),
+        tol
+    );
+
+    // Arbitrary-axis rotation matches the axis-aligned ones
+    let x_axis = tuples::vector3(1.0,0.0,0.0);
+    let y_axis = tuples::vector3(0.0,1.0,0.0);
+    let z_axis = tuples::vector3(0.0,0.0,1.0);
+    assert_approx_eq!(rotation(x_axis, 0.7), rotation_x(0.7), tol);
+    assert_approx_eq!(rotation(y_axis, -1.3), rotation_y(-1.3), tol);
+    assert_approx_eq!(rotation(z_axis * 3.0, 2.1), rotation_z(2.1), tol);
+    let diagonal = rotation(tuples::vector3(1.0,1.0,1.0), 2.0 * pi / 3.0);
+    assert_approx_eq!(
+        diagonal,
+        Matrix([
+            [0.0,0.0,1.0,0.0],
+            [1.0,0.0,0.0,0.0],
+            [0.0,1.0,0.0,0.0],
+            [0.0,0.0,0.0,1.0]
+        ]),
+        tol
+    );
+
+    // View transform
+    let origin = tuples::Point3::origin();
+    assert_approx_eq!(
+        view_transform(origin, tuples::point3(0.0,0.0,-1.0), y_axis),
+        identity(),
+        tol
+    );
+    assert_approx_eq!(
+        view_transform(origin, tuples::point3(0.0,0.0,1.0), y_axis),
+        scaling(-1.0,1.0,-1.0),
+        tol
+    );
+    assert_approx_eq!(
+        view_transform(tuples::point3(0.0,0.0,8.0), origin, y_axis),
+        translation(0.0,0.0,-8.0),
+        tol
+    );
+    let from = tuples::point3(1.0,3.0,2.0);
+    let to = tuples::point3(4.0,-2.0,8.0);
+    let up = tuples::vector3(1.0,1.0,0.0);
+    let view = view_transform(from, to, up);
+    let eye = view * from;
+    let target = view * to;
+    assert_approx_eq!(eye, origin, tol);
+    assert_approx_eq!(target, tuples::point3(0.0,0.0,-(to - from).magnitude()), Tolerance::absolute(1e-4));
+    // the rotation part is orthonormal
+    let rot = Matrix([
+        [view.0[0][0],view.0[0][1],view.0[0][2],0.0],
+        [view.0[1][0],view.0[1][1],view.0[1][2],0.0],
+        [view.0[2][0],view.0[2][1],view.0[2][2],0.0],
+        [0.0,0.0,0.0,1.0]
+    ]);
+    assert_approx_eq!(multiply_matrix(rot, transpose(rot)), identity(), tol);
+
+    // look_at undoes view_transform and points -z at the target
+    assert_approx_eq!(multiply_matrix(look_at(from, to, up), view), identity(), tol);
+    let facing = look_at(from, to, up) * tuples::vector3(0.0,0.0,-1.0);
+    assert_approx_eq!(facing, (to - from).normalize(), tol);
+    assert_eq!(
+        Transform::identity().look_at(from, to, up).build(),
+        look_at(from, to, up)
+    );
+    // an up vector along the view direction, or none at all, still gives
+    // an orthonormal view looking at the target
+    let ahead = tuples::point3(3.0,-1.0,2.0);
+    for (to, up) in [
+        (ahead, ahead - from),
+        (ahead, from - ahead),
+        (ahead, tuples::vector3(0.0,0.0,0.0)),
+        (from + y_axis * 5.0, y_axis),
+        (from - y_axis * 5.0, y_axis),
+    ] {
+        let view = view_transform(from, to, up);
+        assert!(view.0.iter().flatten().all(|v| v.is_finite()), "{:?}", view);
+        assert_approx_eq!(multiply_matrix(look_at(from, to, up), view), identity(), tol);
+        assert_approx_eq!(view * to, tuples::point3(0.0,0.0,-(to - from).magnitude()), Tolerance::absolute(1e-4));
+    }
+    assert_approx_eq!(
+        Transform::identity().rotate(z_axis, 0.4).rotate_z(-0.4).build(),
+        identity(),
+        tol
+    );
 }
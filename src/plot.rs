@@ -0,0 +1,564 @@
+/*
+Plots
+-----
+Line and scatter plots of data series, so a simulation can be looked at
+without mapping world coordinates to pixels by hand:
+
+    let plot = Plot::new(320, 200)
+        .title("Projectile")
+        .x_label("x (m)")
+        .y_label("height (m)")
+        .series(Series::new("light wind", &calm))
+        .series(Series::new("strong wind", &windy).style(Style::Points));
+    plot.render().save_png("renders/trajectory.png", PngFormat::Rgb8)
+
+Series take anything that converts Into (x, y) pairs, and skip points
+that aren't finite. Every copy of tuples.rs converts its Tuples (owned or
+borrowed) to their x and y, so a demo with its own tuples.rs passes them
+straight in. Each axis spans the data unless given a range, widened to
+the nearest "nice" tick (a 1, 2 or 5 times a power of ten, about five
+ticks per axis) where that doesn't overflow. A range can be given either
+way round; one with no width is padded by half its value each side, as
+a single data value is. Series without a colour take the next one from
+PALETTE.
+
+Labels use the bitmap font in font.rs, so they're upper case.
+*/
+
+use super::draw::clip_segment;
+use super::font::{text_width, GLYPH_HEIGHT, LINE_HEIGHT};
+use super::tuples::Float;
+use super::{canvas, color, to_f64, Canvas, Color};
+
+const PALETTE:[(Float, Float, Float); 6] = [
+    (1.0, 0.8, 0.1),
+    (0.2, 0.7, 1.0),
+    (1.0, 0.3, 0.7),
+    (0.4, 0.9, 0.3),
+    (1.0, 0.5, 0.1),
+    (0.7, 0.5, 1.0),
+];
+const PADDING:isize = 4;
+const TICK:isize = 3;
+const MARKER_RADIUS:usize = 2;
+// tick labels never show more decimals than this; past it they'd be all
+// zeros anyway
+const MAX_DECIMALS:usize = 16;
+
+pub fn palette(i:usize) -> Color {
+    let (r, g, b) = PALETTE[i % PALETTE.len()];
+    color(r, g, b)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Style {
+    Line,
+    Points,
+    LinePoints,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub label:String,
+    pub points:Vec<(Float, Float)>,
+    // None picks from the palette
+    pub colour:Option<Color>,
+    pub style:Style,
+}
+
+impl Series {
+    pub fn new<P: Into<(Float, Float)>>(label:&str, points:impl IntoIterator<Item = P>) -> Series {
+        Series {
+            label: label.to_string(),
+            points: points.into_iter().map(Into::into).collect(),
+            colour: None,
+            style: Style::Line,
+        }
+    }
+
+    pub fn colour(self, colour:Color) -> Series {
+        Series{colour: Some(colour), ..self}
+    }
+
+    pub fn style(self, style:Style) -> Series {
+        Series{style, ..self}
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Axis {
+    pub min:f64,
+    pub max:f64,
+    // distance between ticks
+    pub step:f64,
+}
+
+fn half_span(min:f64, max:f64) -> f64 {
+    // halved before subtracting, so even -MAX..MAX is finite
+    max / 2.0 - min / 2.0
+}
+
+fn around(value:f64) -> (f64, f64) {
+    // a span for a single value: half of it either side, or 1 either side
+    // of 0 and of values too small to pad that way
+    let pad = value.abs() / 2.0;
+    let (min, max) = ((value - pad).max(-f64::MAX), (value + pad).min(f64::MAX));
+    if half_span(min, max) > 0.0 { (min, max) } else { (value - 1.0, value + 1.0) }
+}
+
+fn nice_step(min:f64, max:f64) -> f64 {
+    // never 0, however close min and max are
+    let raw = (half_span(min, max) / 2.5).max(f64::MIN_POSITIVE);
+    let magnitude = 10f64.powf(raw.log10().floor());
+    let norm = raw / magnitude;
+    let nice = if norm < 1.5 { 1.0 } else if norm < 3.0 { 2.0 } else if norm < 7.0 { 5.0 } else { 10.0 };
+    nice * magnitude
+}
+
+impl Axis {
+    pub fn fit(values:impl IntoIterator<Item = f64>) -> Axis {
+        // the data's extent widened to whole ticks; 0..1 with no data
+        let (mut min, mut max) = values.into_iter()
+            .filter(|v| v.is_finite())
+            .fold((f64::INFINITY, -f64::INFINITY), |(lo, hi), v| (lo.min(v), hi.max(v)));
+        if min > max {
+            (min, max) = (0.0, 1.0);
+        }
+        if half_span(min, max) <= 0.0 {
+            (min, max) = around(min);
+        }
+        let step = nice_step(min, max);
+        let (lo, hi) = ((min / step).floor() * step, (max / step).ceil() * step);
+        // no room to widen next to MAX: keep the data's extent
+        if lo.is_finite() && hi.is_finite() {
+            Axis{min: lo, max: hi, step}
+        } else {
+            Axis{min, max, step}
+        }
+    }
+
+    pub fn range(min:f64, max:f64) -> Axis {
+        // exactly min..max (either way round), ticks wherever they fall
+        // inside; a range that isn't finite is fitted to whatever ends of
+        // it are, and one with no width is padded like a single value
+        if !(min.is_finite() && max.is_finite()) {
+            return Axis::fit([min, max]);
+        }
+        let (min, max) = if min <= max { (min, max) } else { (max, min) };
+        let (min, max) = if half_span(min, max) > 0.0 { (min, max) } else { around(min / 2.0 + max / 2.0) };
+        Axis{min, max, step: nice_step(min, max)}
+    }
+
+    pub fn ticks(&self) -> Vec<f64> {
+        // a little slack in tick units, which can't overflow next to MAX
+        let first = (self.min / self.step - 1e-9).ceil() as i64;
+        let last = (self.max / self.step + 1e-9).floor() as i64;
+        (first..=last).map(|k| k as f64 * self.step).collect()
+    }
+
+    pub fn label(&self, value:f64) -> String {
+        let decimals = ((-self.step.log10().floor()).max(0.0) as usize).min(MAX_DECIMALS);
+        let text = format!("{:.*}", decimals, value);
+        // no "-0"
+        if text.trim_start_matches('-').chars().all(|c| c == '0' || c == '.') {
+            text.trim_start_matches('-').to_string()
+        } else {
+            text
+        }
+    }
+
+    fn fraction(&self, value:f64) -> f64 {
+        (value / 2.0 - self.min / 2.0) / half_span(self.min, self.max)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plot {
+    pub width:usize,
+    pub height:usize,
+    pub title:String,
+    pub x_label:String,
+    pub y_label:String,
+    pub x_range:Option<(Float, Float)>,
+    pub y_range:Option<(Float, Float)>,
+    pub grid:bool,
+    pub legend:bool,
+    pub background:Color,
+    pub foreground:Color,
+    pub grid_colour:Color,
+    pub series:Vec<Series>,
+}
+
+// The plotting area in pixels, inclusive, and the axes it shows
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Frame {
+    pub left:isize,
+    pub top:isize,
+    pub right:isize,
+    pub bottom:isize,
+    pub x:Axis,
+    pub y:Axis,
+}
+
+impl Frame {
+    pub fn pixel(&self, x:Float, y:Float) -> (f64, f64) {
+        (
+            self.left as f64 + self.x.fraction(to_f64(x)) * (self.right - self.left) as f64,
+            self.bottom as f64 - self.y.fraction(to_f64(y)) * (self.bottom - self.top) as f64,
+        )
+    }
+
+    fn contains(&self, (x, y):(f64, f64)) -> bool {
+        x >= self.left as f64 && x <= self.right as f64 && y >= self.top as f64 && y <= self.bottom as f64
+    }
+}
+
+impl Plot {
+    pub fn new(width:usize, height:usize) -> Plot {
+        Plot {
+            width,
+            height,
+            title: String::new(),
+            x_label: String::new(),
+            y_label: String::new(),
+            x_range: None,
+            y_range: None,
+            grid: true,
+            legend: true,
+            background: color(0.0, 0.0, 0.0),
+            foreground: color(0.85, 0.85, 0.85),
+            grid_colour: color(0.2, 0.2, 0.2),
+            series: Vec::new(),
+        }
+    }
+
+    pub fn title(self, title:&str) -> Plot {
+        Plot{title: title.to_string(), ..self}
+    }
+
+    pub fn x_label(self, label:&str) -> Plot {
+        Plot{x_label: label.to_string(), ..self}
+    }
+
+    pub fn y_label(self, label:&str) -> Plot {
+        Plot{y_label: label.to_string(), ..self}
+    }
+
+    pub fn x_range(self, min:Float, max:Float) -> Plot {
+        Plot{x_range: Some((min, max)), ..self}
+    }
+
+    pub fn y_range(self, min:Float, max:Float) -> Plot {
+        Plot{y_range: Some((min, max)), ..self}
+    }
+
+    pub fn grid(self, grid:bool) -> Plot {
+        Plot{grid, ..self}
+    }
+
+    pub fn legend(self, legend:bool) -> Plot {
+        Plot{legend, ..self}
+    }
+
+    pub fn colours(self, background:Color, foreground:Color, grid:Color) -> Plot {
+        Plot{background, foreground, grid_colour: grid, ..self}
+    }
+
+    pub fn series(mut self, series:Series) -> Plot {
+        self.series.push(series);
+        self
+    }
+
+    fn series_colour(&self, i:usize) -> Color {
+        self.series[i].colour.unwrap_or_else(|| palette(i))
+    }
+
+    pub fn axes(&self) -> (Axis, Axis) {
+        let points = || self.series.iter().flat_map(|s| s.points.iter());
+        let axis = |range:Option<(Float, Float)>, values:Vec<f64>| match range {
+            Some((min, max)) => Axis::range(to_f64(min), to_f64(max)),
+            None => Axis::fit(values),
+        };
+        (
+            axis(self.x_range, points().map(|p| to_f64(p.0)).collect()),
+            axis(self.y_range, points().map(|p| to_f64(p.1)).collect()),
+        )
+    }
+
+    pub fn frame(&self) -> Frame {
+        let (x, y) = self.axes();
+        let glyph = GLYPH_HEIGHT as isize;
+        let label_width = |axis:&Axis| axis.ticks().iter().map(|&t| text_width(&axis.label(t), 1)).max().unwrap_or(0) as isize;
+
+        // title and y label share the line above the plot
+        let above = if self.title.is_empty() && self.y_label.is_empty() { 0 } else { LINE_HEIGHT as isize + 2 };
+        let below = TICK + 2 + glyph + if self.x_label.is_empty() { 0 } else { LINE_HEIGHT as isize + 2 };
+        // room for half the last x tick label to hang past the frame
+        let hang = x.ticks().last().map_or(0, |&t| text_width(&x.label(t), 1) as isize / 2);
+        Frame {
+            left: PADDING + label_width(&y) + 2 + TICK,
+            top: PADDING + above,
+            right: self.width as isize - 1 - PADDING.max(hang + 1),
+            bottom: self.height as isize - 1 - PADDING - below,
+            x,
+            y,
+        }
+    }
+
+    pub fn render(&self) -> Canvas {
+        let mut image = canvas(self.width, self.height);
+        image.fill(self.background);
+        let frame = self.frame();
+        if frame.right - frame.left < 2 || frame.bottom - frame.top < 2 {
+            // too small for anything but the background
+            return image;
+        }
+        let fg = self.foreground;
+        let glyph = GLYPH_HEIGHT as isize;
+
+        // grid, ticks and tick labels
+        for t in frame.x.ticks() {
+            let px = frame.pixel(t as Float, 0.0).0.round() as isize;
+            if self.grid {
+                image.draw_line(px, frame.top, px, frame.bottom, self.grid_colour);
+            }
+            image.draw_line(px, frame.bottom + 1, px, frame.bottom + TICK, fg);
+            let label = frame.x.label(t);
+            image.draw_text(px - text_width(&label, 1) as isize / 2, frame.bottom + TICK + 2, &label, fg, 1);
+        }
+        for t in frame.y.ticks() {
+            let py = frame.pixel(0.0, t as Float).1.round() as isize;
+            if self.grid {
+                image.draw_line(frame.left, py, frame.right, py, self.grid_colour);
+            }
+            image.draw_line(frame.left - TICK, py, frame.left - 1, py, fg);
+            let label = frame.y.label(t);
+            image.draw_text(frame.left - TICK - 2 - text_width(&label, 1) as isize, py - glyph / 2, &label, fg, 1);
+        }
+
+        for i in 0..self.series.len() {
+            self.draw_series(&mut image, &frame, i);
+        }
+        let (w, h) = ((frame.right - frame.left + 1) as usize, (frame.bottom - frame.top + 1) as usize);
+        image.draw_rect(frame.left, frame.top, w, h, fg);
+
+        // labels
+        if !self.y_label.is_empty() {
+            image.draw_text(PADDING, PADDING, &self.y_label, fg, 1);
+        }
+        if !self.title.is_empty() {
+            let centre = (frame.left + frame.right) / 2;
+            image.draw_text(centre - text_width(&self.title, 1) as isize / 2, PADDING, &self.title, fg, 1);
+        }
+        if !self.x_label.is_empty() {
+            let centre = (frame.left + frame.right) / 2;
+            let y = frame.bottom + TICK + 2 + LINE_HEIGHT as isize + 2;
+            image.draw_text(centre - text_width(&self.x_label, 1) as isize / 2, y, &self.x_label, fg, 1);
+        }
+        if self.legend {
+            self.draw_legend(&mut image, &frame);
+        }
+        image
+    }
+
+    fn draw_series(&self, image:&mut Canvas, frame:&Frame, i:usize) {
+        let series = &self.series[i];
+        let colour = self.series_colour(i);
+        let finite = |p:&&(Float, Float)| p.0.is_finite() && p.1.is_finite();
+        let min = (frame.left as f64, frame.top as f64);
+        let max = (frame.right as f64, frame.bottom as f64);
+
+        if series.style != Style::Points {
+            // a non-finite point breaks the line
+            for pair in series.points.windows(2) {
+                if !pair.iter().all(|p| finite(&p)) {
+                    continue;
+                }
+                let (a, b) = (frame.pixel(pair[0].0, pair[0].1), frame.pixel(pair[1].0, pair[1].1));
+                if let Some((a, b)) = clip_segment(a, b, min, max) {
+                    image.draw_line_aa(a.0 as Float, a.1 as Float, b.0 as Float, b.1 as Float, colour);
+                }
+            }
+        }
+        if series.style != Style::Line {
+            for p in series.points.iter().filter(finite) {
+                let px = frame.pixel(p.0, p.1);
+                if frame.contains(px) {
+                    image.fill_circle(px.0.round() as isize, px.1.round() as isize, MARKER_RADIUS, colour);
+                }
+            }
+        }
+    }
+
+    fn draw_legend(&self, image:&mut Canvas, frame:&Frame) {
+        let entries:Vec<usize> = (0..self.series.len()).filter(|&i| !self.series[i].label.is_empty()).collect();
+        if entries.is_empty() {
+            return;
+        }
+        const SAMPLE:isize = 9;
+        let row = LINE_HEIGHT as isize + 2;
+        let widest = entries.iter().map(|&i| text_width(&self.series[i].label, 1)).max().unwrap_or(0) as isize;
+        let (w, h) = (3 + SAMPLE + 3 + widest + 3, 3 + row * entries.len() as isize - 2 + 3);
+        // top right, just inside the frame
+        let (left, top) = (frame.right - 3 - w + 1, frame.top + 3);
+        image.fill_rect(left, top, w as usize, h as usize, self.background);
+        image.draw_rect(left, top, w as usize, h as usize, self.foreground);
+
+        for (n, &i) in entries.iter().enumerate() {
+            let colour = self.series_colour(i);
+            let y = top + 3 + row * n as isize;
+            let middle = y + GLYPH_HEIGHT as isize / 2;
+            if self.series[i].style != Style::Points {
+                image.draw_line(left + 3, middle, left + 2 + SAMPLE, middle, colour);
+            }
+            if self.series[i].style != Style::Line {
+                image.fill_circle(left + 3 + SAMPLE / 2, middle, MARKER_RADIUS, colour);
+            }
+            image.draw_text(left + 3 + SAMPLE + 3, y, &self.series[i].label, self.foreground, 1);
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn plot_tests() {
+    use super::tuples::point;
+    let black = color(0.0, 0.0, 0.0);
+
+    // Nice axes
+    let axis = Axis::fit([0.3, 9.2]);
+    assert_eq!((axis.min, axis.max, axis.step), (0.0, 10.0, 2.0));
+    assert_eq!(axis.ticks(), vec![0.0, 2.0, 4.0, 6.0, 8.0, 10.0]);
+    let axis = Axis::fit([-0.013, 0.021]);
+    assert_eq!(axis.step, 0.005);
+    assert!(axis.min <= -0.013 && axis.max >= 0.021 && axis.ticks().len() >= 5);
+    assert_eq!(axis.label(0.015), "0.015");
+    assert_eq!(axis.label(-1e-12), "0.000");
+    let axis = Axis::fit([120.0, 4480.0]);
+    assert_eq!((axis.min, axis.max, axis.step), (0.0, 5000.0, 1000.0));
+    assert_eq!(axis.label(3000.0), "3000");
+    // a single value, no data, and nonsense
+    assert_eq!(Axis::fit([5.0, 5.0]).ticks().first(), Some(&2.0));
+    assert_eq!(Axis::fit([0.0]), Axis{min: -1.0, max: 1.0, step: 0.5});
+    assert_eq!(Axis::fit([]), Axis{min: 0.0, max: 1.0, step: 0.2});
+    assert_eq!(Axis::fit([f64::NAN, f64::INFINITY, 2.0, 4.0]).ticks(), vec![2.0, 2.5, 3.0, 3.5, 4.0]);
+    // fixed ranges are kept as given
+    let axis = Axis::range(-3.0, 7.5);
+    assert_eq!((axis.min, axis.max), (-3.0, 7.5));
+    assert_eq!(axis.ticks(), vec![-2.0, 0.0, 2.0, 4.0, 6.0]);
+    // reversed ranges are swapped, empty ones padded like a single value
+    assert_eq!(Axis::range(5.0, 2.0), Axis::range(2.0, 5.0));
+    assert_eq!(Axis::range(2.0, 2.0), Axis::range(1.0, 3.0));
+    assert_eq!((Axis::range(1e20, 1e20).min, Axis::range(1e20, 1e20).max), (5e19, 1.5e20));
+    assert_eq!((Axis::range(0.0, 0.0).min, Axis::range(0.0, 0.0).max), (-1.0, 1.0));
+    // spans too small to halve still get a positive step and short labels
+    for axis in [Axis::fit([5e-324]), Axis::fit([0.0, 5e-324]), Axis::range(0.0, 5e-324), Axis::range(1e-310, 2e-310)] {
+        assert!(axis.step > 0.0 && axis.min < axis.max && axis.ticks().len() <= 6, "{:?}", axis);
+        assert!(axis.label(axis.min).len() <= MAX_DECIMALS + 3, "{:?}", axis);
+    }
+    // spans too wide to subtract still give finite axes
+    for axis in [
+        Axis::fit([-1e308, 1e308]),
+        Axis::fit([-f64::MAX, f64::MAX]),
+        Axis::fit([0.0, 1.7e308]),
+        Axis::fit([f64::MAX]),
+        Axis::range(-f64::MAX, f64::MAX),
+        Axis::range(f64::NEG_INFINITY, 3.0),
+    ] {
+        assert!(axis.min.is_finite() && axis.max.is_finite() && axis.min < axis.max, "{:?}", axis);
+        assert!(axis.step.is_finite() && axis.step > 0.0 && !axis.ticks().is_empty(), "{:?}", axis);
+        assert_eq!((axis.fraction(axis.min), axis.fraction(axis.max)), (0.0, 1.0));
+    }
+    assert_eq!(Axis::fit([-1e308, 1e308]), Axis{min: -1e308, max: 1e308, step: 5e307});
+    assert_eq!(Axis::range(f64::NEG_INFINITY, 3.0), Axis::fit([3.0]));
+
+    // Series from pairs and tuples
+    let pairs = Series::new("pairs", [(0.0, 1.0), (2.0, 3.0)]);
+    let tuples = vec![point(0.0, 1.0, 9.0), point(2.0, 3.0, 9.0)];
+    assert_eq!(pairs.points, Series::new("borrowed", &tuples).points);
+    assert_eq!(pairs.points, Series::new("owned", tuples).points);
+    assert_eq!(pairs.style, Style::Line);
+    let styled = pairs.clone().colour(color(1.0, 0.0, 0.0)).style(Style::Points);
+    assert_eq!((styled.colour, styled.style), (Some(color(1.0, 0.0, 0.0)), Style::Points));
+
+    // Layout: ranges come from every series
+    let plot = Plot::new(200, 120)
+        .series(Series::new("a", [(0.0, 0.0), (10.0, 50.0)]))
+        .series(Series::new("b", [(-5.0, 20.0), (3.0, -40.0)]));
+    let (x, y) = plot.axes();
+    assert!(x.min <= -5.0 && x.max >= 10.0 && y.min <= -40.0 && y.max >= 50.0);
+    let frame = plot.frame();
+    assert!(frame.left > 0 && frame.top > 0 && frame.right < 199 && frame.bottom < 119);
+    assert_eq!(frame.pixel(x.min as Float, y.min as Float), (frame.left as f64, frame.bottom as f64));
+    assert_eq!(frame.pixel(x.max as Float, y.max as Float), (frame.right as f64, frame.top as f64));
+    let fixed = plot.clone().x_range(0.0, 1.0).y_range(0.0, 2.0);
+    assert_eq!(fixed.axes().0, Axis::range(0.0, 1.0));
+    // labels take room
+    let labelled = plot.clone().title("T").x_label("X");
+    assert!(labelled.frame().top > frame.top && labelled.frame().bottom < frame.bottom);
+
+    // Rendering: a horizontal line at y = 1 lands on its row, in its colour
+    let red = color(1.0, 0.0, 0.0);
+    let plot = Plot::new(160, 100)
+        .grid(false)
+        .legend(false)
+        .y_range(0.0, 2.0)
+        .series(Series::new("", [(0.0, 1.0), (4.0, 1.0)]).colour(red));
+    let image = plot.render();
+    let frame = plot.frame();
+    let row = frame.pixel(0.0, 1.0).1;
+    let middle = ((frame.left + frame.right) / 2) as usize;
+    // anti-aliased over the one or two rows nearest it
+    let (above, below) = (image.pixel_at(middle, row.floor() as usize), image.pixel_at(middle, row.ceil() as usize));
+    let ink = if row.fract() == 0.0 { above.red } else { above.red + below.red };
+    assert!((ink - 1.0).abs() < 1e-3 && above.green == 0.0, "{:?} {:?}", above, below);
+    assert_eq!(image.pixel_at(middle, row as usize - 3), black);
+    // the frame and tick marks are drawn, and tick labels sit under the axis
+    assert_eq!(image.pixel_at(middle, frame.top as usize), plot.foreground);
+    assert_eq!(image.pixel_at(frame.left as usize - 1, frame.bottom as usize), plot.foreground);
+    let under = (frame.bottom as usize + 5..frame.bottom as usize + 11).any(|y| image.pixel_at(frame.left as usize, y) == plot.foreground);
+    assert!(under);
+
+    // Grid lines, palette colours and a legend
+    let plot = Plot::new(240, 160)
+        .title("Two series")
+        .x_label("x")
+        .y_label("y")
+        .series(Series::new("line", (0..50).map(|i| (i as Float, (i as Float / 8.0).sin()))))
+        .series(Series::new("dots", (0..50).map(|i| (i as Float, (i as Float / 8.0).cos()))).style(Style::Points));
+    let image = plot.render();
+    let frame = plot.frame();
+    let colours:Vec<Color> = image.pixels().map(|(_, _, p)| p).collect();
+    assert!(colours.contains(&palette(0)) && colours.contains(&palette(1)));
+    assert!(colours.contains(&plot.grid_colour));
+    // the legend box is in the top right corner of the frame
+    assert_eq!(image.pixel_at(frame.right as usize - 3, frame.top as usize + 3), plot.foreground);
+    assert_ne!(palette(0), palette(1));
+    let light = plot.clone().colours(color(1.0, 1.0, 1.0), black, color(0.9, 0.9, 0.9)).render();
+    assert_eq!(light.pixel_at(1, 1), color(1.0, 1.0, 1.0));
+    assert_eq!(light.pixel_at(frame.left as usize, frame.top as usize), black);
+    assert_eq!(palette(6), palette(0));
+
+    // Points outside fixed ranges are clipped to the frame, NaNs break lines
+    let plot = Plot::new(120, 80)
+        .grid(false)
+        .legend(false)
+        .x_range(0.0, 1.0)
+        .y_range(0.0, 1.0)
+        .series(Series::new("", [(-10.0, 0.5), (10.0, 0.5), (Float::NAN, 0.0), (0.5, 100.0), (0.5, -100.0)]).style(Style::LinePoints));
+    let image = plot.render();
+    let frame = plot.frame();
+    let outside = image.pixels().filter(|&(x, y, p)| {
+        let (x, y) = (x as isize, y as isize);
+        let inside = x >= frame.left && x <= frame.right && y >= frame.top && y <= frame.bottom;
+        !inside && p != black && p != plot.foreground
+    }).count();
+    assert_eq!(outside, 0);
+
+    // Degenerate plots don't panic
+    Plot::new(0, 0).render();
+    Plot::new(10, 10).series(Series::new("x", [(1.0, 1.0)])).render();
+    Plot::new(200, 100).series(Series::new("empty", Vec::<(Float, Float)>::new())).render();
+    Plot::new(200, 100).series(Series::new("huge", [(-Float::MAX, 0.0), (Float::MAX, Float::MAX)])).render();
+    Plot::new(200, 100).y_range(1e20, 1e20).series(Series::new("flat", [(0.0, 1e20), (1.0, 1e20)])).render();
+    Plot::new(200, 100).x_range(5.0, 2.0).y_range(0.0, Float::MIN_POSITIVE).render();
+}
@@ -0,0 +1,538 @@
+/*
+PNG
+---
+Canvases to and from PNG without any crates, so renders open in a browser
+or a code review tool. Compression is zlib.rs; the CRC-32 over each chunk
+is here.
+
+Writing: 8 or 16 bits per sample, RGB or RGBA, non-interlaced. Channels
+are clamped and rounded exactly like the PPM writer. A Canvas has no
+alpha, so RGBA output is opaque unless encode_png is given an alpha
+channel. Each row gets whichever of the five PNG filters gives the
+smallest sum of absolute differences (the usual libpng heuristic). PNG
+has no empty images: encode_png panics on a canvas with no pixels, and
+the Canvas methods return an InvalidInput error instead.
+
+Reading: greyscale, grey + alpha, RGB and RGBA at 8 or 16 bits, all five
+filters, IDAT split over any number of chunks. Palette images, bit depths
+below 8 and interlacing are reported as PngError::Unsupported. Every
+chunk's CRC is checked; ancillary chunks (gAMA, tEXt, ...) are skipped.
+*/
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[path = "zlib.rs"]
+pub mod zlib;
+
+use super::tuples::Float;
+use super::tonemap::DisplayTransform;
+// quantize: same clamping and rounding as the PPM writer
+use super::{canvas, color, quantize, Canvas};
+
+const SIGNATURE:[u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+// IDAT data is split into chunks of at most this many bytes
+const IDAT_CHUNK:usize = 1 << 20;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PngFormat {
+    Rgb8,
+    Rgb16,
+    Rgba8,
+    Rgba16,
+}
+
+impl PngFormat {
+    fn color_type(self) -> u8 {
+        match self {
+            PngFormat::Rgb8 | PngFormat::Rgb16 => 2,
+            PngFormat::Rgba8 | PngFormat::Rgba16 => 6,
+        }
+    }
+
+    fn bit_depth(self) -> u8 {
+        match self {
+            PngFormat::Rgb8 | PngFormat::Rgba8 => 8,
+            PngFormat::Rgb16 | PngFormat::Rgba16 => 16,
+        }
+    }
+}
+
+// A decoded PNG: the colour in a Canvas, plus the alpha channel (row
+// major, 0..1) when the file had one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PngImage {
+    pub canvas:Canvas,
+    pub alpha:Option<Vec<Float>>,
+    pub bit_depth:u8,
+}
+
+#[derive(Debug)]
+pub enum PngError {
+    Io(io::Error),
+    BadSignature,
+    // chunk data that runs past the end of the file
+    Truncated,
+    BadCrc{chunk:String},
+    // IHDR missing, misplaced or with invalid values
+    BadHeader(String),
+    Unsupported(String),
+    MissingImageData,
+    Inflate(zlib::InflateError),
+    BadFilter(u8),
+    // decompressed data isn't the size the header implies
+    BadDataLength{expected:usize, found:usize},
+}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PngError::Io(e) => write!(f, "Can't read PNG: {}", e),
+            PngError::BadSignature => write!(f, "Not a PNG file (bad signature)"),
+            PngError::Truncated => write!(f, "PNG file ends in the middle of a chunk"),
+            PngError::BadCrc{chunk} => write!(f, "CRC mismatch in PNG chunk {}", chunk),
+            PngError::BadHeader(why) => write!(f, "Bad PNG header: {}", why),
+            PngError::Unsupported(what) => write!(f, "Unsupported PNG: {}", what),
+            PngError::MissingImageData => write!(f, "PNG has no IDAT chunks"),
+            PngError::Inflate(e) => write!(f, "Bad PNG image data: {}", e),
+            PngError::BadFilter(filter) => write!(f, "Unknown PNG row filter {}", filter),
+            PngError::BadDataLength{expected, found} => {
+                write!(f, "PNG image data is {} bytes, expected {}", found, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PngError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PngError::Io(e) => Some(e),
+            PngError::Inflate(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PngError {
+    fn from(e:io::Error) -> PngError {
+        PngError::Io(e)
+    }
+}
+
+impl From<zlib::InflateError> for PngError {
+    fn from(e:zlib::InflateError) -> PngError {
+        PngError::Inflate(e)
+    }
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+const CRC_TABLE:[u32; 256] = crc_table();
+
+pub fn crc32(data:&[u8]) -> u32 {
+    !data.iter().fold(0xffff_ffffu32, |c, &b| CRC_TABLE[((c ^ b as u32) & 0xff) as usize] ^ (c >> 8))
+}
+
+fn write_chunk<W: io::Write>(w:&mut W, kind:&[u8; 4], data:&[u8]) -> io::Result<()> {
+    w.write_all(&(data.len() as u32).to_be_bytes())?;
+    w.write_all(kind)?;
+    w.write_all(data)?;
+    // the CRC covers the type and the data, not the length
+    let mut crc_input = Vec::with_capacity(4 + data.len());
+    crc_input.extend_from_slice(kind);
+    crc_input.extend_from_slice(data);
+    w.write_all(&crc32(&crc_input).to_be_bytes())
+}
+
+fn paeth(a:u8, b:u8, c:u8) -> u8 {
+    // left, up, upper left: whichever is closest to a + b - c
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+fn filter_row(filter:u8, row:&[u8], prior:&[u8], bpp:usize, out:&mut Vec<u8>) {
+    out.clear();
+    out.push(filter);
+    for i in 0..row.len() {
+        let a = if i >= bpp { row[i - bpp] } else { 0 };
+        let b = prior[i];
+        let c = if i >= bpp { prior[i - bpp] } else { 0 };
+        let predicted = match filter {
+            0 => 0,
+            1 => a,
+            2 => b,
+            3 => ((a as u16 + b as u16) / 2) as u8,
+            _ => paeth(a, b, c),
+        };
+        out.push(row[i].wrapping_sub(predicted));
+    }
+}
+
+pub fn encode_png(image:&Canvas, alpha:Option<&[Float]>, format:PngFormat) -> Vec<u8> {
+    encode_png_with(image, alpha, format, &DisplayTransform::identity())
+}
+
+pub fn encode_png_with(image:&Canvas, alpha:Option<&[Float]>, format:PngFormat, display:&DisplayTransform) -> Vec<u8> {
+    // alpha, if given, has one value per pixel in row order; display
+    // applies to the colour channels only
+    assert!(!is_empty(image), "PNG can't store a {}x{} image", image.width(), image.height());
+    if let Some(alpha) = alpha {
+        assert_eq!(alpha.len(), image.width() * image.height(), "one alpha value per pixel");
+    }
+    let channels = if format.color_type() == 6 { 4 } else { 3 };
+    let sample_bytes = format.bit_depth() as usize / 8;
+    let bpp = channels * sample_bytes;
+    let stride = image.width() * bpp;
+
+    // filtered scanlines, each prefixed with its filter type
+    let mut raw = Vec::with_capacity((stride + 1) * image.height());
+    let mut prior = vec![0u8; stride];
+    let mut row_bytes = Vec::with_capacity(stride);
+    let (mut best, mut candidate) = (Vec::with_capacity(stride + 1), Vec::with_capacity(stride + 1));
+    for (y, row) in image.rows().enumerate().take(image.height()) {
+        row_bytes.clear();
+        for (x, pixel) in row.iter().enumerate() {
+            let pixel = display.apply(*pixel);
+            let samples = [pixel.red, pixel.green, pixel.blue, alpha.map_or(1.0, |a| a[y * image.width() + x])];
+            for &s in &samples[..channels] {
+                if sample_bytes == 1 {
+                    row_bytes.push(quantize(s, 255.0) as u8);
+                } else {
+                    row_bytes.extend(quantize(s, 65535.0).to_be_bytes());
+                }
+            }
+        }
+
+        let mut best_score = u64::MAX;
+        for filter in 0..5 {
+            filter_row(filter, &row_bytes, &prior, bpp, &mut candidate);
+            let score = candidate[1..].iter().map(|&v| (v as i8).unsigned_abs() as u64).sum();
+            if score < best_score {
+                best_score = score;
+                std::mem::swap(&mut best, &mut candidate);
+            }
+        }
+        raw.extend_from_slice(&best);
+        prior.clone_from(&row_bytes);
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend((image.width() as u32).to_be_bytes());
+    ihdr.extend((image.height() as u32).to_be_bytes());
+    // bit depth, colour type, compression, filter method, no interlace
+    ihdr.extend([format.bit_depth(), format.color_type(), 0, 0, 0]);
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &ihdr).expect("writing to a Vec can't fail");
+    let compressed = zlib::compress(&raw);
+    for part in compressed.chunks(IDAT_CHUNK) {
+        write_chunk(&mut out, b"IDAT", part).expect("writing to a Vec can't fail");
+    }
+    write_chunk(&mut out, b"IEND", &[]).expect("writing to a Vec can't fail");
+    out
+}
+
+fn is_empty(image:&Canvas) -> bool {
+    image.width() == 0 || image.height() == 0
+}
+
+fn check_not_empty(image:&Canvas) -> io::Result<()> {
+    if is_empty(image) {
+        let message = format!("PNG can't store a {}x{} image", image.width(), image.height());
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    }
+    Ok(())
+}
+
+pub fn decode_png(data:&[u8]) -> Result<PngImage, PngError> {
+    if data.len() < 8 || data[..8] != SIGNATURE {
+        return Err(PngError::BadSignature);
+    }
+
+    let mut pos = 8;
+    let mut header:Option<(usize, usize, u8, u8)> = None;
+    let mut idat = Vec::new();
+    loop {
+        let length_bytes = data.get(pos..pos + 4).ok_or(PngError::Truncated)?;
+        let length = u32::from_be_bytes([length_bytes[0], length_bytes[1], length_bytes[2], length_bytes[3]]) as usize;
+        let body = data.get(pos + 4..pos + 8 + length).ok_or(PngError::Truncated)?;
+        let crc_bytes = data.get(pos + 8 + length..pos + 12 + length).ok_or(PngError::Truncated)?;
+        let (kind, chunk) = (&body[..4], &body[4..]);
+        let name = String::from_utf8_lossy(kind).into_owned();
+        if crc32(body) != u32::from_be_bytes([crc_bytes[0], crc_bytes[1], crc_bytes[2], crc_bytes[3]]) {
+            return Err(PngError::BadCrc{chunk: name});
+        }
+        pos += 12 + length;
+
+        match kind {
+            b"IHDR" => {
+                if header.is_some() || chunk.len() != 13 {
+                    return Err(PngError::BadHeader(String::from("IHDR must come first, once, with 13 bytes")));
+                }
+                let width = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as usize;
+                let height = u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as usize;
+                let (depth, color_type) = (chunk[8], chunk[9]);
+                if width == 0 || height == 0 {
+                    return Err(PngError::BadHeader(format!("{}x{} image", width, height)));
+                }
+                if chunk[10] != 0 || chunk[11] != 0 {
+                    return Err(PngError::BadHeader(String::from("unknown compression or filter method")));
+                }
+                if chunk[12] != 0 {
+                    return Err(PngError::Unsupported(String::from("interlaced images")));
+                }
+                match (color_type, depth) {
+                    (0 | 2 | 4 | 6, 8 | 16) => {}
+                    (3, _) => return Err(PngError::Unsupported(String::from("palette images"))),
+                    (0 | 2 | 4 | 6, 1 | 2 | 4) => {
+                        return Err(PngError::Unsupported(format!("{}-bit samples", depth)))
+                    }
+                    _ => return Err(PngError::BadHeader(format!("colour type {} at {} bits", color_type, depth))),
+                }
+                header = Some((width, height, depth, color_type));
+            }
+            _ if header.is_none() => {
+                return Err(PngError::BadHeader(format!("{} chunk before IHDR", name)));
+            }
+            b"IDAT" => idat.extend_from_slice(chunk),
+            b"IEND" => break,
+            // lowercase first letter: ancillary, safe to skip
+            _ if kind[0].is_ascii_lowercase() => {}
+            _ => return Err(PngError::Unsupported(format!("critical chunk {}", name))),
+        }
+    }
+
+    let (width, height, depth, color_type) = header.ok_or(PngError::MissingImageData)?;
+    if idat.is_empty() {
+        return Err(PngError::MissingImageData);
+    }
+    let channels = match color_type { 0 => 1, 2 => 3, 4 => 2, _ => 4 };
+    let sample_bytes = depth as usize / 8;
+    let bpp = channels * sample_bytes;
+    let stride = width.checked_mul(bpp).ok_or(PngError::BadHeader(String::from("image too large")))?;
+    let expected = stride.checked_add(1).and_then(|s| s.checked_mul(height))
+        .ok_or(PngError::BadHeader(String::from("image too large")))?;
+
+    // never inflate more than the header allows for
+    let raw = zlib::decompress_limited(&idat, expected)?;
+    if raw.len() != expected {
+        return Err(PngError::BadDataLength{expected, found: raw.len()});
+    }
+
+    // undo the filters in place, row by row
+    let mut pixels = vec![0u8; stride * height];
+    for y in 0..height {
+        let filter = raw[y * (stride + 1)];
+        let line = &raw[y * (stride + 1) + 1..(y + 1) * (stride + 1)];
+        let (done, rest) = pixels.split_at_mut(y * stride);
+        let prior = if y > 0 { &done[(y - 1) * stride..] } else { &[][..] };
+        let row = &mut rest[..stride];
+        for i in 0..stride {
+            let a = if i >= bpp { row[i - bpp] } else { 0 };
+            let b = if y > 0 { prior[i] } else { 0 };
+            let c = if i >= bpp && y > 0 { prior[i - bpp] } else { 0 };
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(PngError::BadFilter(filter)),
+            };
+            row[i] = line[i].wrapping_add(predicted);
+        }
+    }
+
+    let maxval = if depth == 8 { 255.0 } else { 65535.0 };
+    let sample = |bytes:&[u8], i:usize| -> Float {
+        let v = if sample_bytes == 1 { bytes[i] as u16 } else { u16::from_be_bytes([bytes[2 * i], bytes[2 * i + 1]]) };
+        v as Float / maxval
+    };
+    let mut image = canvas(width, height);
+    let mut alpha = if channels % 2 == 0 { Some(Vec::with_capacity(width * height)) } else { None };
+    for (pixel, bytes) in image.pixels_mut().zip(pixels.chunks_exact(bpp)) {
+        *pixel = if channels <= 2 {
+            let grey = sample(bytes, 0);
+            color(grey, grey, grey)
+        } else {
+            color(sample(bytes, 0), sample(bytes, 1), sample(bytes, 2))
+        };
+        if let Some(alpha) = alpha.as_mut() {
+            alpha.push(sample(bytes, channels - 1));
+        }
+    }
+    Ok(PngImage{canvas: image, alpha, bit_depth: depth})
+}
+
+pub fn load_png<P: AsRef<Path>>(path:P) -> Result<PngImage, PngError> {
+    decode_png(&fs::read(path)?)
+}
+
+impl Canvas {
+    pub fn write_png<W: io::Write>(&self, w:&mut W, format:PngFormat) -> io::Result<()> {
+        check_not_empty(self)?;
+        w.write_all(&encode_png(self, None, format))
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path:P, format:PngFormat) -> io::Result<()> {
+        check_not_empty(self)?;
+        fs::write(path, encode_png(self, None, format))
+    }
+
+    pub fn write_png_with<W: io::Write>(&self, w:&mut W, format:PngFormat, display:&DisplayTransform) -> io::Result<()> {
+        check_not_empty(self)?;
+        w.write_all(&encode_png_with(self, None, format, display))
+    }
+
+    pub fn save_png_with<P: AsRef<Path>>(&self, path:P, format:PngFormat, display:&DisplayTransform) -> io::Result<()> {
+        check_not_empty(self)?;
+        fs::write(path, encode_png_with(self, None, format, display))
+    }
+}
+
+#[allow(dead_code)]
+pub fn png_tests() {
+    use super::tuples::approx::{ApproxEq, Tolerance};
+
+    // CRC-32 check value, and the IEND chunk every PNG ends with
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    assert_eq!(crc32(b"IEND"), 0xae42_6082);
+
+    // A test image with gradients, flat areas and out-of-range values
+    let mut image = canvas(37, 23);
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let (u, v) = (x as Float / 36.0, y as Float / 22.0);
+            let c = if x < 10 { color(0.25, 0.5, 0.75) } else { color(u, v, (u * v * 7.0).sin()) };
+            image.set_pixel(x, y, c);
+        }
+    }
+    image.set_pixel(0, 0, color(2.0, -1.0, Float::NAN));
+    let clamped = |c:super::Color| color(
+        if c.red.is_nan() { 0.0 } else { c.red.clamp(0.0, 1.0) },
+        if c.green.is_nan() { 0.0 } else { c.green.clamp(0.0, 1.0) },
+        if c.blue.is_nan() { 0.0 } else { c.blue.clamp(0.0, 1.0) },
+    );
+    let alpha:Vec<Float> = (0..37 * 23).map(|i| (i % 11) as Float / 10.0).collect();
+
+    // Round trip every format, with and without an alpha channel
+    for format in [PngFormat::Rgb8, PngFormat::Rgb16, PngFormat::Rgba8, PngFormat::Rgba16] {
+        let step = if format.bit_depth() == 8 { 1.0 / 255.0 } else { 1.0 / 65535.0 };
+        let tolerance = Tolerance::absolute(step / 2.0 + 1e-6);
+        for with_alpha in [false, true] {
+            let bytes = encode_png(&image, if with_alpha { Some(&alpha) } else { None }, format);
+            assert_eq!(bytes[..8], SIGNATURE);
+            let back = decode_png(&bytes).expect("own output decodes");
+            assert_eq!(back.bit_depth, format.bit_depth());
+            assert_eq!((back.canvas.width(), back.canvas.height()), (37, 23));
+            for ((_, _, a), (_, _, b)) in image.pixels().zip(back.canvas.pixels()) {
+                assert!(clamped(a).approx_eq_with(&b, tolerance));
+            }
+            match (format, back.alpha) {
+                (PngFormat::Rgb8 | PngFormat::Rgb16, None) => {}
+                (PngFormat::Rgba8 | PngFormat::Rgba16, Some(a)) => {
+                    let expected:Vec<Float> = if with_alpha { alpha.clone() } else { vec![1.0; 37 * 23] };
+                    assert!(a.iter().zip(&expected).all(|(p, q)| (p - q).abs() <= step / 2.0 + 1e-6));
+                }
+                (f, a) => panic!("{:?} decoded with alpha {:?}", f, a.is_some()),
+            }
+        }
+    }
+
+    // 8-bit values come back exactly
+    let mut exact = canvas(3, 1);
+    exact.set_pixel(0, 0, color(1.0, 0.0, 51.0 / 255.0));
+    exact.set_pixel(2, 0, color(0.2, 0.4, 0.6));
+    let back = decode_png(&encode_png(&exact, None, PngFormat::Rgb8)).unwrap();
+    assert_eq!(back.canvas.pixel_at(0, 0), color(1.0, 0.0, 0.2));
+
+    // Files, via the Canvas methods
+    let path = std::env::temp_dir().join("hyperion_png_test.png");
+    image.save_png(&path, PngFormat::Rgb16).expect("temp dir is writable");
+    let loaded = load_png(&path).expect("saved PNG loads");
+    let _ = fs::remove_file(&path);
+    let mut written = Vec::new();
+    image.write_png(&mut written, PngFormat::Rgb16).expect("writing to a Vec can't fail");
+    assert_eq!(loaded, decode_png(&written).unwrap());
+
+    // Empty canvases have no PNG form: an error, and no file
+    let empty_path = std::env::temp_dir().join("hyperion_png_empty.png");
+    let _ = fs::remove_file(&empty_path);
+    for empty in [canvas(0, 4), canvas(4, 0)] {
+        let err = empty.write_png(&mut Vec::new(), PngFormat::Rgb8).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("can't store a"));
+        assert!(empty.save_png(&empty_path, PngFormat::Rgb8).is_err() && !empty_path.exists());
+    }
+
+    // A greyscale + alpha PNG made by another encoder (Python's zlib +
+    // hand-built chunks): 2x2, filters None and Sub
+    let grey = [
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+        0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x08, 0x04, 0x00, 0x00, 0x00, 0xd8, 0xbf, 0xc5,
+        0xaf, 0x00, 0x00, 0x00, 0x12, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0xf8, 0xff, 0xdf, 0xd8,
+        0x98, 0x91, 0x81, 0x21, 0xed, 0x3f, 0x00, 0x15, 0x60, 0x03, 0xcb, 0xc1, 0xb9, 0xb8, 0x93, 0x00,
+        0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+    let img = decode_png(&grey).expect("valid grey + alpha PNG");
+    assert_eq!(img.canvas.pixel_at(0, 0), color(1.0, 1.0, 1.0));
+    assert_eq!(img.canvas.pixel_at(1, 0), color(0.2, 0.2, 0.2));
+    assert_eq!(img.canvas.pixel_at(1, 1), color(0.4, 0.4, 0.4));
+    assert_eq!(img.alpha, Some(vec![1.0, 0.2, 0.0, 1.0]));
+
+    // Errors
+    let good = encode_png(&exact, None, PngFormat::Rgb8);
+    let err = |data:&[u8]| decode_png(data).expect_err("should be rejected");
+    assert!(matches!(err(b"GIF89a"), PngError::BadSignature));
+    assert!(matches!(err(&good[..good.len() - 6]), PngError::Truncated));
+    let mut corrupt = good.clone();
+    corrupt[20] ^= 0xff;
+    assert!(matches!(err(&corrupt), PngError::BadCrc{chunk} if chunk == "IHDR"));
+    let mut palette = Vec::from(&SIGNATURE[..]);
+    write_chunk(&mut palette, b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 3, 0, 0, 0]).unwrap();
+    assert!(matches!(err(&palette), PngError::Unsupported(_)));
+    let mut no_data = Vec::from(&SIGNATURE[..]);
+    write_chunk(&mut no_data, b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]).unwrap();
+    write_chunk(&mut no_data, b"tEXt", b"Comment\0skipped").unwrap();
+    write_chunk(&mut no_data, b"IEND", &[]).unwrap();
+    assert!(matches!(err(&no_data), PngError::MissingImageData));
+    let mut bad_filter = Vec::from(&SIGNATURE[..]);
+    write_chunk(&mut bad_filter, b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]).unwrap();
+    write_chunk(&mut bad_filter, b"IDAT", &zlib::compress(&[9, 1, 2, 3])).unwrap();
+    write_chunk(&mut bad_filter, b"IEND", &[]).unwrap();
+    assert!(matches!(err(&bad_filter), PngError::BadFilter(9)));
+    let mut short = Vec::from(&SIGNATURE[..]);
+    write_chunk(&mut short, b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]).unwrap();
+    write_chunk(&mut short, b"IDAT", &zlib::compress(&[0, 1, 2])).unwrap();
+    write_chunk(&mut short, b"IEND", &[]).unwrap();
+    assert!(matches!(err(&short), PngError::BadDataLength{expected: 4, found: 3}));
+    let mut bomb = Vec::from(&SIGNATURE[..]);
+    write_chunk(&mut bomb, b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]).unwrap();
+    write_chunk(&mut bomb, b"IDAT", &zlib::compress(&vec![0; 1 << 20])).unwrap();
+    write_chunk(&mut bomb, b"IEND", &[]).unwrap();
+    assert!(matches!(err(&bomb), PngError::Inflate(zlib::InflateError::TooLong{limit: 4})));
+    assert_eq!(err(&palette).to_string(), "Unsupported PNG: palette images");
+}
@@ -0,0 +1,249 @@
+/*
+PPM reader
+----------
+Loads plain (P3) and binary (P6) PPM files back into a Canvas, e.g. the
+files in renders/ for use as textures or regression references.
+
+Handles everything the netpbm spec allows in the header: '#' comments
+running to the end of the line, any amount of whitespace, and maxval
+anywhere from 1 to 65535. Binary samples are 1 byte each when maxval < 256
+and 2 bytes (big-endian) otherwise. Samples are scaled to 0..1 by maxval.
+Anything after the last sample is ignored.
+*/
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::tuples::Float;
+use super::{canvas, color, Canvas};
+
+#[derive(Debug)]
+pub enum PpmError {
+    Io(io::Error),
+    // first two bytes weren't P3 or P6
+    BadMagic(String),
+    // width, height or maxval missing or not a number
+    BadHeader{field:&'static str, found:String},
+    BadMaxval(u64),
+    TooLarge{width:usize, height:usize},
+    // a P3 sample that isn't a number, or any sample above maxval
+    BadSample{index:usize, found:String},
+    Truncated{expected:usize, found:usize},
+}
+
+impl fmt::Display for PpmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PpmError::Io(e) => write!(f, "Can't read PPM: {}", e),
+            PpmError::BadMagic(magic) => write!(f, "Not a P3 or P6 PPM file (starts with {:?})", magic),
+            PpmError::BadHeader{field, found} => write!(f, "Bad PPM header: expected {}, found {:?}", field, found),
+            PpmError::BadMaxval(maxval) => write!(f, "PPM maxval {} is outside 1..=65535", maxval),
+            PpmError::TooLarge{width, height} => write!(f, "PPM image {}x{} is too large", width, height),
+            PpmError::BadSample{index, found} => write!(f, "Bad PPM sample #{}: {:?}", index, found),
+            PpmError::Truncated{expected, found} => {
+                write!(f, "PPM data ends early: expected {} samples, found {}", expected, found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PpmError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PpmError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PpmError {
+    fn from(e:io::Error) -> PpmError {
+        PpmError::Io(e)
+    }
+}
+
+// Whitespace/comment-aware reader over the raw bytes.
+struct Tokens<'a> {
+    data:&'a [u8],
+    pos:usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn skip_blank(&mut self) {
+        while self.pos < self.data.len() {
+            match self.data[self.pos] {
+                b'#' => {
+                    while self.pos < self.data.len() && self.data[self.pos] != b'\n' && self.data[self.pos] != b'\r' {
+                        self.pos += 1;
+                    }
+                }
+                b if b.is_ascii_whitespace() => self.pos += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        self.skip_blank();
+        let start = self.pos;
+        while self.pos < self.data.len() && !self.data[self.pos].is_ascii_whitespace() && self.data[self.pos] != b'#' {
+            self.pos += 1;
+        }
+        if self.pos > start { Some(&self.data[start..self.pos]) } else { None }
+    }
+
+    fn number(&mut self, field:&'static str) -> Result<u64, PpmError> {
+        let token = self.next().ok_or(PpmError::BadHeader{field, found: String::from("end of file")})?;
+        parse_number(token).ok_or_else(|| PpmError::BadHeader{field, found: lossy(token)})
+    }
+}
+
+fn parse_number(token:&[u8]) -> Option<u64> {
+    // digits only: no signs, no decimals
+    if token.is_empty() || !token.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(token).ok()?.parse().ok()
+}
+
+fn lossy(token:&[u8]) -> String {
+    String::from_utf8_lossy(token).into_owned()
+}
+
+pub fn parse_ppm(data:&[u8]) -> Result<Canvas, PpmError> {
+    let magic = &data[..data.len().min(2)];
+    let binary = match magic {
+        b"P3" => false,
+        b"P6" => true,
+        _ => return Err(PpmError::BadMagic(lossy(magic))),
+    };
+    let mut tokens = Tokens{data, pos: 2};
+
+    // the magic number must be followed by whitespace or a comment
+    if data.len() > 2 && !data[2].is_ascii_whitespace() && data[2] != b'#' {
+        return Err(PpmError::BadMagic(lossy(&data[..data.len().min(3)])));
+    }
+
+    let width = tokens.number("width")?;
+    let height = tokens.number("height")?;
+    let maxval = tokens.number("maxval")?;
+    if maxval == 0 || maxval > 65535 {
+        return Err(PpmError::BadMaxval(maxval));
+    }
+    let too_large = PpmError::TooLarge{width: width as usize, height: height as usize};
+    let samples = usize::try_from(width).ok()
+        .zip(usize::try_from(height).ok())
+        .and_then(|(w, h)| w.checked_mul(h)?.checked_mul(3))
+        .ok_or(too_large)?;
+    let (width, height) = (width as usize, height as usize);
+    let scale = 1.0 / maxval as Float;
+
+    let mut values:Vec<Float> = Vec::with_capacity(samples.min(data.len()));
+    if binary {
+        // exactly one whitespace byte separates maxval from the raster
+        let start = tokens.pos + 1;
+        let bytes_per_sample = if maxval < 256 { 1 } else { 2 };
+        let raster = data.get(start..).unwrap_or(&[]);
+        let available = raster.len() / bytes_per_sample;
+        if available < samples {
+            return Err(PpmError::Truncated{expected: samples, found: available});
+        }
+        for (index, chunk) in raster.chunks_exact(bytes_per_sample).take(samples).enumerate() {
+            let v = if bytes_per_sample == 1 { chunk[0] as u64 } else { u16::from_be_bytes([chunk[0], chunk[1]]) as u64 };
+            if v > maxval {
+                return Err(PpmError::BadSample{index, found: v.to_string()});
+            }
+            values.push(v as Float * scale);
+        }
+    } else {
+        for index in 0..samples {
+            let token = tokens.next().ok_or(PpmError::Truncated{expected: samples, found: index})?;
+            let v = parse_number(token).filter(|v| *v <= maxval)
+                .ok_or_else(|| PpmError::BadSample{index, found: lossy(token)})?;
+            values.push(v as Float * scale);
+        }
+    }
+
+    let mut image = canvas(width, height);
+    for (pixel, rgb) in image.pixels_mut().zip(values.chunks_exact(3)) {
+        *pixel = color(rgb[0], rgb[1], rgb[2]);
+    }
+    Ok(image)
+}
+
+pub fn read_ppm<R: io::Read>(r:&mut R) -> Result<Canvas, PpmError> {
+    let mut data = Vec::new();
+    r.read_to_end(&mut data)?;
+    parse_ppm(&data)
+}
+
+pub fn load_ppm<P: AsRef<Path>>(path:P) -> Result<Canvas, PpmError> {
+    parse_ppm(&fs::read(path)?)
+}
+
+#[allow(dead_code)]
+pub fn ppm_reader_tests() {
+    use super::tuples::approx::{assert_approx_eq, ApproxEq};
+
+    // Round trip through the writer
+    let mut original = canvas(4, 3);
+    original.set_pixel(0, 0, color(1.0, 0.0, 0.0));
+    original.set_pixel(3, 2, color(0.2, 0.4, 0.6));
+    original.set_pixel(1, 1, color(1.5, -1.0, 0.5));
+    let back = parse_ppm(original.to_ppm().as_bytes()).expect("writer output parses");
+    assert_eq!((back.width(), back.height()), (4, 3));
+    assert_eq!(back.pixel_at(0, 0), color(1.0, 0.0, 0.0));
+    assert_approx_eq!(back.pixel_at(3, 2), color(0.2, 0.4, 0.6));
+    // clamped on the way out, and 0.5 rounds to 128
+    assert_approx_eq!(back.pixel_at(1, 1), color(1.0, 0.0, 128.0 / 255.0));
+
+    // Comments, odd whitespace and a small maxval
+    let p3 = b"P3 # made by hand\n# width height\n2\t1\r\n15 #maxval\n15 0 0\n#mid-raster comment\n  0 5 15\n";
+    let img = parse_ppm(p3).expect("valid P3");
+    assert_eq!(img.pixel_at(0, 0), color(1.0, 0.0, 0.0));
+    assert_approx_eq!(img.pixel_at(1, 0), color(0.0, 1.0 / 3.0, 1.0));
+
+    // Binary, 8 and 16 bits per sample
+    let mut p6 = b"P6\n2 1\n255\n".to_vec();
+    p6.extend_from_slice(&[255, 0, 51, 0, 10, 255]);
+    let img = parse_ppm(&p6).expect("valid 8-bit P6");
+    assert_approx_eq!(img.pixel_at(0, 0), color(1.0, 0.0, 0.2));
+    // a raster byte that looks like whitespace is still data
+    let mut p6 = b"P6 1 1 255\n".to_vec();
+    p6.extend_from_slice(b" \n#");
+    assert_approx_eq!(parse_ppm(&p6).unwrap().pixel_at(0, 0), color(32.0 / 255.0, 10.0 / 255.0, 35.0 / 255.0));
+    let mut p6 = b"P6\n1 1\n65535\n".to_vec();
+    p6.extend_from_slice(&[0xff, 0xff, 0x80, 0x00, 0x00, 0x01]);
+    let img = parse_ppm(&p6).expect("valid 16-bit P6");
+    assert_approx_eq!(img.pixel_at(0, 0), color(1.0, 32768.0 / 65535.0, 1.0 / 65535.0));
+    let mut p6 = b"P6\n1 1\n1000\n".to_vec();
+    p6.extend_from_slice(&[0x03, 0xe8, 0x01, 0xf4, 0x00, 0x00]);
+    assert_approx_eq!(parse_ppm(&p6).unwrap().pixel_at(0, 0), color(1.0, 0.5, 0.0));
+
+    // Readers and files
+    let mut cursor = io::Cursor::new(original.to_ppm().into_bytes());
+    assert_eq!(read_ppm(&mut cursor).unwrap(), back);
+    let clock = load_ppm("renders/clock.ppm").expect("renders/clock.ppm is a valid PPM");
+    assert_eq!((clock.width(), clock.height()), (80, 80));
+    assert!(matches!(load_ppm("renders/no_such_file.ppm"), Err(PpmError::Io(_))));
+
+    // Errors say what went wrong
+    let err = |data:&[u8]| parse_ppm(data).expect_err("should be rejected");
+    assert!(matches!(err(b"P5\n1 1\n255\n"), PpmError::BadMagic(m) if m == "P5"));
+    assert!(matches!(err(b""), PpmError::BadMagic(_)));
+    assert!(matches!(err(b"P33 1 1 255"), PpmError::BadMagic(_)));
+    assert!(matches!(err(b"P3\n-1 1\n255\n"), PpmError::BadHeader{field: "width", ..}));
+    assert!(matches!(err(b"P3\n1\n"), PpmError::BadHeader{field: "height", ..}));
+    assert!(matches!(err(b"P3\n1 1 2.5\n"), PpmError::BadHeader{field: "maxval", ..}));
+    assert!(matches!(err(b"P3\n1 1\n0\n"), PpmError::BadMaxval(0)));
+    assert!(matches!(err(b"P3\n1 1\n70000\n"), PpmError::BadMaxval(70000)));
+    assert!(matches!(err(b"P3\n99999999999 99999999999\n255\n"), PpmError::TooLarge{..}));
+    assert!(matches!(err(b"P3\n1 1\n255\n1 2 x\n"), PpmError::BadSample{index: 2, ..}));
+    assert!(matches!(err(b"P3\n1 1\n15\n1 2 16\n"), PpmError::BadSample{index: 2, ..}));
+    assert!(matches!(err(b"P3\n2 1\n255\n1 2 3 4\n"), PpmError::Truncated{expected: 6, found: 4}));
+    assert!(matches!(err(b"P6\n2 1\n255\n\x01\x02\x03"), PpmError::Truncated{expected: 6, found: 3}));
+    assert!(matches!(err(b"P6\n1 1\n1000\n\x03\xe9\x00\x00\x00\x00"), PpmError::BadSample{index: 0, ..}));
+    assert_eq!(err(b"P3\n1 1 2.5\n").to_string(), "Bad PPM header: expected maxval, found \"2.5\"");
+}
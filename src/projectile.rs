@@ -4,8 +4,6 @@ The Ray Tracer Challenge (Jamis Buck)
 
 Chapter 2: Drawing on a Canvas - Projectile Drawing test
 */
-use std::fs;
-
 #[path = "tuples.rs"]
 mod tuples;
 
@@ -24,53 +22,41 @@ pub struct Environment {
     pub wind: tuples::Tuple,
 }
 
+// Environment for Projectile 1
 #[allow(dead_code)]
-pub fn projectile_vec() -> Vec<tuples::Tuple> {
-    
-    let mut projectile = Projectile{
-        position: tuples::point(0.0, 0.0, 0.0),
-        velocity: tuples::vector(30.0, 50.0, 0.0)};
-        // x_max = 260, y_max = 130
-
-
-    // Environment for Projectile 1:-
-
-    // let environ1 = Environment{
-    //     gravity: tuples::vector(0.0, -9.8, 0.0),
-    //     wind: tuples::vector(-0.012, -0.005, 0.0) // randomly chosen values
-    // };
-
-    // Environment for Projectile 2:-
+pub fn environ1() -> Environment {
+    Environment{
+        gravity: tuples::vector(0.0, -9.8, 0.0),
+        wind: tuples::vector(-0.012, -0.005, 0.0) // randomly chosen values
+    }
+}
 
-    let environ2 = Environment {
+// Environment for Projectile 2
+#[allow(dead_code)]
+pub fn environ2() -> Environment {
+    Environment {
         gravity: tuples::vector(0.0,-9.8,0.0),
         wind: tuples::vector(-0.02,-0.01,0.0) // stronger wind than environ1
-    };
+    }
+}
+
+#[allow(dead_code)]
+pub fn trajectory(environ:&Environment) -> Vec<tuples::Tuple> {
+    // positions every dt until the projectile lands
+    let mut projectile = Projectile{
+        position: tuples::point(0.0, 0.0, 0.0),
+        velocity: tuples::vector(30.0, 50.0, 0.0)};
 
     let dt = 0.01;
     let mut positions_vec:Vec<tuples::Tuple> = vec![];
 
-    // Projectile 1:-
-
-    // while projectile.position.y >= 0.0 {
-    //     // println!("{:?}", projectile.position);
-    //     positions_vec.push(projectile.position);
-
-    //     projectile.position = tuples::add(projectile.position, tuples::multiply(dt,projectile.velocity));
-    //     projectile.velocity = tuples::add(projectile.velocity, tuples::multiply(dt,environ1.gravity));
-    //     projectile.velocity = tuples::add(projectile.velocity, environ1.wind);
-
-    // }
-
-    // Projectile 2:-
-
     while projectile.position.y >= 0.0 {
         // println!("{:?}", projectile.position);
         positions_vec.push(projectile.position);
 
         projectile.position = tuples::add(projectile.position, tuples::multiply(dt,projectile.velocity));
-        projectile.velocity = tuples::add(projectile.velocity, tuples::multiply(dt,environ2.gravity));
-        projectile.velocity = tuples::add(projectile.velocity, environ2.wind);
+        projectile.velocity = tuples::add(projectile.velocity, tuples::multiply(dt,environ.gravity));
+        projectile.velocity = tuples::add(projectile.velocity, environ.wind);
 
     }
 
@@ -78,34 +64,56 @@ pub fn projectile_vec() -> Vec<tuples::Tuple> {
 }
 
 #[allow(dead_code)]
-pub fn projectile_plot() {
+pub fn projectile_vec() -> Vec<tuples::Tuple> {
+    // x_max = 260, y_max = 130
+    trajectory(&environ2())
+}
+
+#[allow(dead_code)]
+pub fn projectile_canvas() -> canvas::Canvas {
     let main_vec = projectile_vec();
     let mut canvas1 = canvas::canvas(260, 130);
     let white = canvas::color(1.0,1.0,1.0);
 
-    for i in main_vec {
-        canvas1 = canvas::write_pixel(canvas1, i.x.ceil() as usize,(129.0 - i.y.ceil()) as usize, white);
-    }
+    // world y is up, canvas y is down from the top row
+    let bottom = (canvas1.height() - 1) as tuples::Float;
 
-    let mut ppm_string = canvas::header(260, 130);
-
-    for i in canvas1 {
-        if ppm_string.len() % 70 == 69 {
-            ppm_string.push_str("\n");
-        }
-        for j in i {
-            ppm_string.push_str(&((j.red * 255.0).ceil()).to_string());
-            ppm_string.push_str(" ");
-            ppm_string.push_str(&((j.green * 255.0).ceil()).to_string());
-            ppm_string.push_str(" ");
-            ppm_string.push_str(&((j.blue * 255.0).ceil()).to_string());
-            ppm_string.push_str(" ");
-        }
-        ppm_string.push_str("\n");
+    // join successive positions so the arc is a curve, not dots
+    for pair in main_vec.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        canvas1.draw_line_aa(a.x, bottom - a.y, b.x, bottom - b.y, white);
     }
 
-    // fs::write("renders/projectile.ppm", ppm_string).expect(""); // Projectile 1 file already written
-    fs::write("renders/projectile2.ppm", ppm_string).expect("");    
+    canvas1
+}
+
+#[allow(dead_code)]
+pub fn trajectory_canvas() -> canvas::Canvas {
+    // the same launch in still air and both environments, on real axes
+    use canvas::plot::{Plot, Series};
+    let still = Environment{gravity: environ1().gravity, wind: tuples::vector(0.0, 0.0, 0.0)};
+
+    Plot::new(320, 200)
+        .title("Projectile trajectories")
+        .x_label("Distance")
+        .y_label("Height")
+        .series(Series::new("No wind", trajectory(&still)))
+        .series(Series::new("Light wind", trajectory(&environ1())))
+        .series(Series::new("Strong wind", trajectory(&environ2())))
+        .render()
+}
+
+#[allow(dead_code)]
+pub fn projectile_plot() {
+    let canvas1 = projectile_canvas();
+
+    // canvas1.save_ppm("renders/projectile.ppm", canvas::PpmFormat::Plain).expect(""); // Projectile 1 file already written
+    canvas1.save_ppm("renders/projectile2.ppm", canvas::PpmFormat::Plain).expect("");    
+    canvas1.save_png("renders/projectile2.png", canvas::png::PngFormat::Rgb8).expect("");
+
+    let plot = trajectory_canvas();
+    plot.save_ppm("renders/trajectory.ppm", canvas::PpmFormat::Plain).expect("");
+    plot.save_png("renders/trajectory.png", canvas::png::PngFormat::Rgb8).expect("");
 
 }
 
@@ -0,0 +1,248 @@
+/*
+Quaternions
+-----------
+Euler rotations (rotation_x/y/z chained together) can't be interpolated
+smoothly and lock up when two axes line up. A unit quaternion
+(w, x, y, z) = (cos(a/2), sin(a/2) * axis) stores the same rotation, so
+orientations can be composed and slerped and only turned into a Matrix at
+the end.
+
+Conventions match the matrices: from_axis_angle(x-axis, a).to_matrix()
+equals rotation_x(a), and q1 * q2 rotates by q2 first, then q1 (same as
+multiply_matrix(m1, m2)). to_matrix and rotate_vector normalize first,
+so a quaternion that has drifted from unit length still only rotates.
+*/
+
+use std::ops::{Mul, Neg};
+
+use super::tuples::{self, Float, Vector3};
+use super::Matrix;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quaternion {
+    pub w:Float,
+    pub x:Float,
+    pub y:Float,
+    pub z:Float,
+}
+
+pub fn quaternion(w:Float, x:Float, y:Float, z:Float) -> Quaternion {
+    Quaternion{w,x,y,z}
+}
+
+impl Quaternion {
+    pub fn identity() -> Quaternion {
+        quaternion(1.0,0.0,0.0,0.0)
+    }
+
+    pub fn from_axis_angle(axis:Vector3, angle:Float) -> Quaternion {
+        let k = axis.normalize();
+        let (s, c) = (angle / 2.0).sin_cos();
+        quaternion(c, k.x * s, k.y * s, k.z * s)
+    }
+
+    pub fn to_axis_angle(self) -> (Vector3, Float) {
+        // any axis will do for the identity rotation
+        let q = self.normalize();
+        let q = if q.w < 0.0 { -q } else { q };
+        let s = (1.0 - q.w * q.w).max(0.0).sqrt();
+        if s < Float::EPSILON {
+            return (tuples::vector3(1.0,0.0,0.0), 0.0);
+        }
+        (tuples::vector3(q.x / s, q.y / s, q.z / s), 2.0 * q.w.min(1.0).acos())
+    }
+
+    pub fn dot(self, other:Quaternion) -> Float {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn magnitude(self) -> Float {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Quaternion {
+        let m = self.magnitude();
+        quaternion(self.w / m, self.x / m, self.y / m, self.z / m)
+    }
+
+    pub fn conjugate(self) -> Quaternion {
+        quaternion(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn inverse(self) -> Quaternion {
+        // the conjugate, for unit quaternions
+        let m2 = self.dot(self);
+        let c = self.conjugate();
+        quaternion(c.w / m2, c.x / m2, c.y / m2, c.z / m2)
+    }
+
+    pub fn rotate_vector(self, v:Vector3) -> Vector3 {
+        // q * v * q^-1, expanded; the expansion needs a unit quaternion, so
+        // like to_matrix this normalizes first
+        let q = self.normalize();
+        let u = tuples::vector3(q.x, q.y, q.z);
+        let t = 2.0 * u.cross(v);
+        v + q.w * t + u.cross(t)
+    }
+
+    pub fn to_matrix(self) -> Matrix {
+        let q = self.normalize();
+        let (w, x, y, z) = (q.w, q.x, q.y, q.z);
+        Matrix([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+            [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+            [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0,0.0,0.0,1.0]
+        ])
+    }
+
+    pub fn from_matrix(m:Matrix) -> Quaternion {
+        // Reads the upper-left 3x3 block, which must be a pure rotation.
+        // Shepperd's method: branch on the largest diagonal term to keep
+        // the square root well away from zero.
+        let m = m.0;
+        let trace = m[0][0] + m[1][1] + m[2][2];
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            quaternion(0.25 * s, (m[2][1] - m[1][2]) / s, (m[0][2] - m[2][0]) / s, (m[1][0] - m[0][1]) / s)
+        } else if m[0][0] > m[1][1] && m[0][0] > m[2][2] {
+            let s = (1.0 + m[0][0] - m[1][1] - m[2][2]).sqrt() * 2.0;
+            quaternion((m[2][1] - m[1][2]) / s, 0.25 * s, (m[0][1] + m[1][0]) / s, (m[0][2] + m[2][0]) / s)
+        } else if m[1][1] > m[2][2] {
+            let s = (1.0 + m[1][1] - m[0][0] - m[2][2]).sqrt() * 2.0;
+            quaternion((m[0][2] - m[2][0]) / s, (m[0][1] + m[1][0]) / s, 0.25 * s, (m[1][2] + m[2][1]) / s)
+        } else {
+            let s = (1.0 + m[2][2] - m[0][0] - m[1][1]).sqrt() * 2.0;
+            quaternion((m[1][0] - m[0][1]) / s, (m[0][2] + m[2][0]) / s, (m[1][2] + m[2][1]) / s, 0.25 * s)
+        };
+        q.normalize()
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Quaternion;
+    fn mul(self, b:Quaternion) -> Quaternion {
+        // Hamilton product
+        quaternion(
+            self.w * b.w - self.x * b.x - self.y * b.y - self.z * b.z,
+            self.w * b.x + self.x * b.w + self.y * b.z - self.z * b.y,
+            self.w * b.y - self.x * b.z + self.y * b.w + self.z * b.x,
+            self.w * b.z + self.x * b.y - self.y * b.x + self.z * b.w,
+        )
+    }
+}
+
+impl Neg for Quaternion {
+    type Output = Quaternion;
+    fn neg(self) -> Quaternion {
+        // same rotation, other hemisphere
+        quaternion(-self.w, -self.x, -self.y, -self.z)
+    }
+}
+
+impl From<Quaternion> for Matrix {
+    fn from(q:Quaternion) -> Matrix {
+        q.to_matrix()
+    }
+}
+
+pub fn slerp(a:Quaternion, b:Quaternion, t:Float) -> Quaternion {
+    // Spherical linear interpolation: constant angular speed from a (t = 0)
+    // to b (t = 1), always along the shorter arc.
+    let a = a.normalize();
+    let mut b = b.normalize();
+    let mut cos_theta = a.dot(b);
+    if cos_theta < 0.0 {
+        b = -b;
+        cos_theta = -cos_theta;
+    }
+
+    let (wa, wb) = if cos_theta > 0.9995 {
+        // nearly parallel: sin(theta) is ~0, fall back to a normalized lerp
+        (1.0 - t, t)
+    } else {
+        let theta = cos_theta.acos();
+        let sin_theta = theta.sin();
+        (((1.0 - t) * theta).sin() / sin_theta, (t * theta).sin() / sin_theta)
+    };
+
+    quaternion(
+        wa * a.w + wb * b.w,
+        wa * a.x + wb * b.x,
+        wa * a.y + wb * b.y,
+        wa * a.z + wb * b.z,
+    ).normalize()
+}
+
+#[allow(dead_code)]
+pub fn quaternion_tests() {
+    use tuples::approx::{assert_approx_eq, ApproxEq, Tolerance};
+    let pi = tuples::consts::PI;
+    let tol = Tolerance::absolute(1e-5);
+    let close_q = |p:Quaternion, q:Quaternion| {
+        // q and -q are the same rotation
+        (p.dot(q).abs() - 1.0).abs() <= 1e-5
+    };
+
+    let x_axis = tuples::vector3(1.0,0.0,0.0);
+    let y_axis = tuples::vector3(0.0,1.0,0.0);
+    let z_axis = tuples::vector3(0.0,0.0,1.0);
+
+    // Axis-angle quaternions match the rotation matrices
+    assert_approx_eq!(Quaternion::from_axis_angle(x_axis, 0.7).to_matrix(), super::rotation_x(0.7), tol);
+    assert_approx_eq!(Quaternion::from_axis_angle(y_axis, -1.2).to_matrix(), super::rotation_y(-1.2), tol);
+    assert_approx_eq!(Matrix::from(Quaternion::from_axis_angle(z_axis, 2.5)), super::rotation_z(2.5), tol);
+    let axis = tuples::vector3(1.0,-2.0,0.5);
+    assert_approx_eq!(Quaternion::from_axis_angle(axis, 1.1).to_matrix(), super::rotation(axis, 1.1), tol);
+    assert_eq!(Quaternion::identity().to_matrix(), super::identity());
+
+    // Round trip through matrices, including the trace <= 0 branches
+    for (a, angle) in [(x_axis, 0.3), (y_axis, pi), (z_axis, pi - 0.01), (axis, 2.9), (axis, -0.4)] {
+        let q = Quaternion::from_axis_angle(a, angle);
+        assert!(close_q(Quaternion::from_matrix(q.to_matrix()), q));
+    }
+
+    // Axis-angle round trip
+    let (axis2, angle2) = Quaternion::from_axis_angle(axis, 1.1).to_axis_angle();
+    assert_approx_eq!(axis2, axis.normalize(), tol);
+    assert!((angle2 - 1.1).abs() < 1e-5);
+    assert_eq!(Quaternion::identity().to_axis_angle().1, 0.0);
+
+    // Composition follows matrix order: q1 * q2 applies q2 first
+    let q1 = Quaternion::from_axis_angle(x_axis, pi / 2.0);
+    let q2 = Quaternion::from_axis_angle(z_axis, pi / 2.0);
+    assert_approx_eq!(
+        (q1 * q2).to_matrix(),
+        super::multiply_matrix(super::rotation_x(pi / 2.0), super::rotation_z(pi / 2.0)),
+        tol
+    );
+    let v = tuples::vector3(1.0,2.0,3.0);
+    assert_approx_eq!((q1 * q2).rotate_vector(v), q1.rotate_vector(q2.rotate_vector(v)), tol);
+    assert_approx_eq!(q2.rotate_vector(x_axis), y_axis, tol);
+    // a non-unit quaternion rotates without scaling, like its matrix
+    let scaled = quaternion(3.0 * q1.w, 3.0 * q1.x, 3.0 * q1.y, 3.0 * q1.z);
+    assert_approx_eq!(scaled.rotate_vector(v), q1.rotate_vector(v), tol);
+    assert_approx_eq!(scaled.rotate_vector(v), scaled.to_matrix() * v, tol);
+    assert!(close_q(q1 * q1.inverse(), Quaternion::identity()));
+    assert!(close_q(q1 * q1.conjugate(), Quaternion::identity()));
+
+    // Slerp
+    let start = Quaternion::identity();
+    let end = Quaternion::from_axis_angle(y_axis, pi / 2.0);
+    assert!(close_q(slerp(start, end, 0.0), start));
+    assert!(close_q(slerp(start, end, 1.0), end));
+    assert!(close_q(slerp(start, end, 0.5), Quaternion::from_axis_angle(y_axis, pi / 4.0)));
+    assert!(close_q(slerp(start, end, 0.25), Quaternion::from_axis_angle(y_axis, pi / 8.0)));
+    // takes the short way round even when handed -end
+    assert!(close_q(slerp(start, -end, 0.5), Quaternion::from_axis_angle(y_axis, pi / 4.0)));
+    // nearly identical inputs don't blow up
+    let tiny = Quaternion::from_axis_angle(y_axis, 1e-4);
+    assert!((slerp(start, tiny, 0.5).magnitude() - 1.0).abs() < 1e-5);
+
+    // The builder accepts quaternions too
+    assert_approx_eq!(
+        super::Transform::identity().rotate_by(q2).translate(1.0,0.0,0.0).build(),
+        super::multiply_matrix(super::translation(1.0,0.0,0.0), super::rotation_z(pi / 2.0)),
+        tol
+    );
+}
@@ -0,0 +1,187 @@
+/*
+Golden-image regression
+-----------------------
+Re-renders every demo and compares it with its reference image under
+renders/, so a change that alters a render fails loudly instead of
+needing someone to eyeball the PPMs:
+
+    cargo run -- regress            check every case, exit 1 on failure
+    cargo run -- regress clock      only the named cases
+    cargo run -- bless [names]      overwrite the references (and their
+                                    PNG copies) with the current renders
+
+A case exports through the same DisplayTransform as its demo, so the
+render is compared after tone mapping, as it was saved.
+
+The references are 8-bit, so a render passes if it matches to within the
+8-bit rounding (compare::Thresholds::eight_bit). A failing case also
+writes its render and a heat map of the differences to target/regress/.
+
+The f64 build snaps a few points to different pixels (the demos ceil()
+coordinates that land near whole numbers), so it has its own references:
+renders/f64/<name>.png, checked and blessed with --features f64.
+
+Each demo module has its own copy of canvas.rs, so their Canvas types
+aren't ours; copy_canvas! copies the pixels across, at full precision.
+*/
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::canvas::compare::{self, Thresholds};
+use crate::canvas::png::{load_png, PngFormat};
+use crate::canvas::ppm::load_ppm;
+use crate::canvas::tonemap::DisplayTransform;
+use crate::canvas::{Canvas, PpmFormat};
+use crate::{canvas, clock, projectile, sphere_shadows};
+
+const OUTPUT_DIR:&str = "target/regress";
+const F64_REFERENCE_DIR:&str = "renders/f64";
+
+pub struct Case {
+    pub name:&'static str,
+    // the PPM under renders/; bless also writes a PNG next to it
+    pub reference:&'static str,
+    pub render:fn() -> Canvas,
+    // what the demo saves its render with
+    pub display:DisplayTransform,
+}
+
+// works on any copy of canvas.rs: they all have the same fields
+macro_rules! copy_canvas {
+    ($source:expr) => {{
+        let source = $source;
+        let mut out = canvas::canvas(source.width(), source.height());
+        for (x, y, p) in source.pixels() {
+            out.set_pixel(x, y, canvas::color(p.red, p.green, p.blue));
+        }
+        out
+    }};
+}
+
+pub fn cases() -> Vec<Case> {
+    vec![
+        Case {
+            name: "canvas",
+            reference: "renders/canvas.ppm",
+            render: canvas::demo_canvas,
+            display: DisplayTransform::identity(),
+        },
+        Case {
+            name: "clock",
+            reference: "renders/clock.ppm",
+            render: || copy_canvas!(clock::clock_canvas()),
+            display: DisplayTransform::identity(),
+        },
+        Case {
+            name: "projectile2",
+            reference: "renders/projectile2.ppm",
+            render: || copy_canvas!(projectile::projectile_canvas()),
+            display: DisplayTransform::identity(),
+        },
+        Case {
+            name: "trajectory",
+            reference: "renders/trajectory.ppm",
+            render: || copy_canvas!(projectile::trajectory_canvas()),
+            display: DisplayTransform::identity(),
+        },
+        Case {
+            name: "sphere_shadow1",
+            reference: "renders/sphere_shadows/sphere_shadow1.ppm",
+            render: || copy_canvas!(sphere_shadows::shadow1_canvas()),
+            display: DisplayTransform::identity().srgb(),
+        },
+    ]
+}
+
+impl Case {
+    pub fn reference_path(&self) -> PathBuf {
+        if cfg!(feature = "f64") {
+            Path::new(F64_REFERENCE_DIR).join(format!("{}.png", self.name))
+        } else {
+            PathBuf::from(self.reference)
+        }
+    }
+}
+
+fn load_reference(path:&Path) -> Result<Canvas, String> {
+    let loaded = if path.extension().is_some_and(|e| e == "png") {
+        load_png(path).map(|image| image.canvas).map_err(|e| e.to_string())
+    } else {
+        load_ppm(path).map_err(|e| e.to_string())
+    };
+    loaded.map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn save_failure(name:&str, actual:&Canvas, reference:&Canvas) -> io::Result<()> {
+    fs::create_dir_all(OUTPUT_DIR)?;
+    let dir = Path::new(OUTPUT_DIR);
+    actual.save_png(dir.join(format!("{}.png", name)), PngFormat::Rgb8)?;
+    if let Ok(heat) = compare::heat_map(actual, reference, 1.0) {
+        heat.save_png(dir.join(format!("{}-diff.png", name)), PngFormat::Rgb8)?;
+    }
+    Ok(())
+}
+
+fn check(case:&Case, actual:&Canvas) -> Result<String, String> {
+    let reference = load_reference(&case.reference_path())?;
+    let result = compare::compare(actual, &reference)
+        .map_err(|e| e.to_string())
+        .and_then(|comparison| {
+            comparison.check(&Thresholds::eight_bit()).map(|_| comparison.to_string()).map_err(|e| e.to_string())
+        });
+    if result.is_err() {
+        if let Err(e) = save_failure(case.name, actual, &reference) {
+            println!("  (couldn't write {}/{}.png: {})", OUTPUT_DIR, case.name, e);
+        }
+    }
+    result
+}
+
+pub fn bless(case:&Case, actual:&Canvas) -> io::Result<()> {
+    let path = case.reference_path();
+    if cfg!(feature = "f64") {
+        fs::create_dir_all(F64_REFERENCE_DIR)?;
+        return actual.save_png(path, PngFormat::Rgb8);
+    }
+    actual.save_ppm(&path, PpmFormat::Plain)?;
+    actual.save_png(path.with_extension("png"), PngFormat::Rgb8)
+}
+
+pub fn run(names:&[String], blessing:bool) -> bool {
+    // true if every selected case passed (or was blessed)
+    let selected:Vec<Case> = cases().into_iter()
+        .filter(|c| names.is_empty() || names.iter().any(|n| n == c.name))
+        .collect();
+    for name in names {
+        if !selected.iter().any(|c| c.name == name) {
+            println!("no regression case called {:?}", name);
+            return false;
+        }
+    }
+
+    let mut failures = 0;
+    for case in &selected {
+        let actual = (case.render)().to_display(&case.display);
+        if blessing {
+            match bless(case, &actual) {
+                Ok(()) => println!("blessed {:<16} {}", case.name, case.reference_path().display()),
+                Err(e) => {
+                    println!("FAILED  {:<16} can't write {}: {}", case.name, case.reference_path().display(), e);
+                    failures += 1;
+                }
+            }
+            continue;
+        }
+        match check(case, &actual) {
+            Ok(summary) => println!("ok      {:<16} {}", case.name, summary),
+            Err(why) => {
+                println!("FAILED  {:<16} {}", case.name, why);
+                failures += 1;
+            }
+        }
+    }
+    println!("{} of {} cases {}", selected.len() - failures, selected.len(), if blessing { "blessed" } else { "passed" });
+    failures == 0
+}
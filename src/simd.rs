@@ -0,0 +1,260 @@
+/*
+SIMD kernels
+------------
+Tuple arithmetic, dot/cross products and 4x4 matrix products on four lanes
+at once. On x86_64 with f32 this uses SSE from std::arch (SSE is part of
+the x86_64 baseline, so there is no runtime detection). Everything else,
+including `--features f64` (four doubles need AVX), uses the scalar
+versions in `scalar`.
+
+The SSE code adds terms in the same order as the scalar code and never
+fuses multiply-adds, so both paths give bit-for-bit identical results.
+simd_tests checks that.
+
+Tuples are passed as [Float; 4] in x, y, z, w order.
+*/
+
+use super::Float;
+
+pub type Lanes = [Float; 4];
+pub type Lanes4x4 = [[Float; 4]; 4];
+
+pub mod scalar {
+    use super::{Float, Lanes, Lanes4x4};
+
+    pub fn add(a:Lanes, b:Lanes) -> Lanes {
+        [a[0] + b[0], a[1] + b[1], a[2] + b[2], a[3] + b[3]]
+    }
+
+    pub fn sub(a:Lanes, b:Lanes) -> Lanes {
+        [a[0] - b[0], a[1] - b[1], a[2] - b[2], a[3] - b[3]]
+    }
+
+    pub fn neg(a:Lanes) -> Lanes {
+        [-a[0], -a[1], -a[2], -a[3]]
+    }
+
+    pub fn scale(s:Float, a:Lanes) -> Lanes {
+        [s * a[0], s * a[1], s * a[2], s * a[3]]
+    }
+
+    pub fn dot(a:Lanes, b:Lanes) -> Float {
+        a[0] * b[0] + a[1] * b[1] + a[2] * b[2] + a[3] * b[3]
+    }
+
+    pub fn cross(a:Lanes, b:Lanes) -> Lanes {
+        // w is always 0.0 (the result is a vector)
+        [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0], 0.0]
+    }
+
+    pub fn mat_vec(m:&Lanes4x4, v:Lanes) -> Lanes {
+        [dot(m[0], v), dot(m[1], v), dot(m[2], v), dot(m[3], v)]
+    }
+
+    pub fn mat_mul(a:&Lanes4x4, b:&Lanes4x4) -> Lanes4x4 {
+        let mut result = [[0.0; 4]; 4];
+        for row in 0..4 {
+            for col in 0..4 {
+                let mut sum = 0.0;
+                for n in 0..4 {
+                    sum += a[row][n] * b[n][col];
+                }
+                result[row][col] = sum;
+            }
+        }
+        result
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+mod sse {
+    use std::arch::x86_64::*;
+
+    use super::{Lanes, Lanes4x4};
+
+    // SSE is always available on x86_64, so these intrinsics are safe to
+    // call; `unsafe` is only needed for the raw loads and stores.
+
+    fn load(a:&Lanes) -> __m128 {
+        unsafe { _mm_loadu_ps(a.as_ptr()) }
+    }
+
+    fn store(v:__m128) -> Lanes {
+        let mut out = [0.0; 4];
+        unsafe { _mm_storeu_ps(out.as_mut_ptr(), v) };
+        out
+    }
+
+    pub fn add(a:Lanes, b:Lanes) -> Lanes {
+        store(unsafe { _mm_add_ps(load(&a), load(&b)) })
+    }
+
+    pub fn sub(a:Lanes, b:Lanes) -> Lanes {
+        store(unsafe { _mm_sub_ps(load(&a), load(&b)) })
+    }
+
+    pub fn neg(a:Lanes) -> Lanes {
+        // flip the sign bits, so -0.0 and NaNs match the scalar negation
+        store(unsafe { _mm_xor_ps(load(&a), _mm_set1_ps(-0.0)) })
+    }
+
+    pub fn scale(s:f32, a:Lanes) -> Lanes {
+        store(unsafe { _mm_mul_ps(_mm_set1_ps(s), load(&a)) })
+    }
+
+    fn hsum(p:__m128) -> __m128 {
+        // ((p0 + p1) + p2) + p3 in lane 0, same order as the scalar dot
+        unsafe {
+            let s = _mm_add_ss(p, _mm_shuffle_ps::<0b01_01_01_01>(p, p));
+            let s = _mm_add_ss(s, _mm_shuffle_ps::<0b10_10_10_10>(p, p));
+            _mm_add_ss(s, _mm_shuffle_ps::<0b11_11_11_11>(p, p))
+        }
+    }
+
+    pub fn dot(a:Lanes, b:Lanes) -> f32 {
+        unsafe { _mm_cvtss_f32(hsum(_mm_mul_ps(load(&a), load(&b)))) }
+    }
+
+    pub fn cross(a:Lanes, b:Lanes) -> Lanes {
+        unsafe {
+            let (a, b) = (load(&a), load(&b));
+            // (y, z, x, w) and (z, x, y, w)
+            let a_yzx = _mm_shuffle_ps::<0b11_00_10_01>(a, a);
+            let b_zxy = _mm_shuffle_ps::<0b11_01_00_10>(b, b);
+            let a_zxy = _mm_shuffle_ps::<0b11_01_00_10>(a, a);
+            let b_yzx = _mm_shuffle_ps::<0b11_00_10_01>(b, b);
+            let c = _mm_sub_ps(_mm_mul_ps(a_yzx, b_zxy), _mm_mul_ps(a_zxy, b_yzx));
+            // a.w * b.w - a.w * b.w can be NaN; w is 0.0 by definition
+            let mut out = store(c);
+            out[3] = 0.0;
+            out
+        }
+    }
+
+    pub fn mat_vec(m:&Lanes4x4, v:Lanes) -> Lanes {
+        unsafe {
+            let v = load(&v);
+            let d = |row:&Lanes| _mm_cvtss_f32(hsum(_mm_mul_ps(load(row), v)));
+            [d(&m[0]), d(&m[1]), d(&m[2]), d(&m[3])]
+        }
+    }
+
+    pub fn mat_mul(a:&Lanes4x4, b:&Lanes4x4) -> Lanes4x4 {
+        // Each result row is a[r][0] * b[0] + ... + a[r][3] * b[3], summed
+        // from 0.0 in that order, like the scalar triple loop.
+        unsafe {
+            let b_rows = [load(&b[0]), load(&b[1]), load(&b[2]), load(&b[3])];
+            let mut result = [[0.0; 4]; 4];
+            for row in 0..4 {
+                let mut sum = _mm_setzero_ps();
+                for n in 0..4 {
+                    sum = _mm_add_ps(sum, _mm_mul_ps(_mm_set1_ps(a[row][n]), b_rows[n]));
+                }
+                result[row] = store(sum);
+            }
+            result
+        }
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", not(feature = "f64")))]
+use sse as backend;
+#[cfg(not(all(target_arch = "x86_64", not(feature = "f64"))))]
+use scalar as backend;
+
+// True when the SSE kernels are in use.
+pub const ENABLED:bool = cfg!(all(target_arch = "x86_64", not(feature = "f64")));
+
+pub fn add(a:Lanes, b:Lanes) -> Lanes {
+    backend::add(a, b)
+}
+
+pub fn sub(a:Lanes, b:Lanes) -> Lanes {
+    backend::sub(a, b)
+}
+
+pub fn neg(a:Lanes) -> Lanes {
+    backend::neg(a)
+}
+
+pub fn scale(s:Float, a:Lanes) -> Lanes {
+    backend::scale(s, a)
+}
+
+pub fn dot(a:Lanes, b:Lanes) -> Float {
+    backend::dot(a, b)
+}
+
+pub fn cross(a:Lanes, b:Lanes) -> Lanes {
+    backend::cross(a, b)
+}
+
+pub fn mat_vec(m:&Lanes4x4, v:Lanes) -> Lanes {
+    backend::mat_vec(m, v)
+}
+
+pub fn mat_mul(a:&Lanes4x4, b:&Lanes4x4) -> Lanes4x4 {
+    backend::mat_mul(a, b)
+}
+
+#[allow(dead_code)]
+pub fn simd_tests() {
+    // Bit patterns, so -0.0 vs 0.0 and NaN payloads count as differences
+    let bits = |a:Lanes| a.map(|x| x.to_bits());
+    let same = |a:Lanes, b:Lanes| bits(a) == bits(b);
+
+    // Deterministic awkward inputs: mixed magnitudes, signs, zeros, denormals
+    let mut seed:u32 = 0x2545_f491;
+    let mut next = || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        let exponent = (seed % 24) as i32 - 12;
+        let mantissa = (seed >> 8) as Float / (1u32 << 24) as Float;
+        let sign = if seed & 1 == 0 { 1.0 } else { -1.0 };
+        sign * mantissa * (2.0 as Float).powi(exponent)
+    };
+    let specials:[Lanes; 4] = [
+        [0.0, -0.0, 1.0, -1.0],
+        [Float::MIN_POSITIVE / 4.0, 1e30, -1e-30, 0.1],
+        [Float::INFINITY, -0.0, 3.0, 0.0],
+        [1.0 / 3.0, 2.0 / 3.0, -5.5, 1e-7],
+    ];
+
+    for i in 0..2000 {
+        let (a, b) = if i < 16 {
+            (specials[i % 4], specials[i / 4])
+        } else {
+            ([next(), next(), next(), next()], [next(), next(), next(), next()])
+        };
+        let s = next();
+
+        assert!(same(add(a, b), scalar::add(a, b)));
+        assert!(same(sub(a, b), scalar::sub(a, b)));
+        assert!(same(neg(a), scalar::neg(a)));
+        assert!(same(scale(s, a), scalar::scale(s, a)));
+        let (d, sd) = (dot(a, b), scalar::dot(a, b));
+        assert!(d.to_bits() == sd.to_bits() || (d.is_nan() && sd.is_nan()));
+        let (c, sc) = (cross(a, b), scalar::cross(a, b));
+        assert!(c.iter().zip(sc.iter()).all(|(x, y)| x.to_bits() == y.to_bits() || (x.is_nan() && y.is_nan())));
+
+        let m = [a, b, [s, next(), next(), next()], [next(), next(), next(), next()]];
+        let n = [b, [next(), next(), next(), next()], a, [0.0, 0.0, 0.0, 1.0]];
+        let (p, sp) = (mat_mul(&m, &n), scalar::mat_mul(&m, &n));
+        for row in 0..4 {
+            assert!(p[row].iter().zip(sp[row].iter()).all(|(x, y)| x.to_bits() == y.to_bits() || (x.is_nan() && y.is_nan())));
+        }
+        let (v, sv) = (mat_vec(&m, b), scalar::mat_vec(&m, b));
+        assert!(v.iter().zip(sv.iter()).all(|(x, y)| x.to_bits() == y.to_bits() || (x.is_nan() && y.is_nan())));
+    }
+
+    // The public Tuple/Matrix API goes through these kernels
+    let t1 = super::point(3.0, -2.0, 5.0);
+    let t2 = super::vector(-2.0, 3.0, 1.0);
+    assert!(super::equal_tuples(super::add(t1, t2), super::point(1.0, 1.0, 6.0)));
+    assert!(super::equal_tuples(super::cross_product(super::vector(1.0,2.0,3.0), super::vector(2.0,3.0,4.0)), super::vector(-1.0,2.0,-1.0)));
+    assert_eq!(super::dot_product(super::vector(1.0,2.0,3.0), super::vector(2.0,3.0,4.0)), 20.0);
+    // ENABLED says which backend was picked
+    let backend_name = std::any::type_name_of_val(&backend::add);
+    assert_eq!(ENABLED, !backend_name.contains("scalar"), "{}", backend_name);
+}
@@ -1,5 +1,3 @@
-use std::fs;
-
 #[path = "matrix.rs"]
 pub mod matrix;
 
@@ -9,7 +7,10 @@ pub mod spheres;
 #[path = "canvas.rs"]
 mod canvas;
 
-pub fn shadow1_plot() {
+use spheres::matrix::tuples::Float;
+
+#[allow(dead_code)]
+pub fn shadow1_canvas() -> canvas::Canvas {
     // Definitions
     // ------------------------------------------------------
     // wall definitions
@@ -25,9 +26,7 @@ pub fn shadow1_plot() {
     // now, pixels should only be squares (otherwise the rectangular pixels are coloured,
     // leading to skewed image)
     // so let their side length be the minimum of the width and height.
-    let pixel_side = wall_side / (*vec![
-        canvas_width, canvas_height
-    ].iter().min().unwrap() as f32);
+    let pixel_side = wall_side / (canvas_width.min(canvas_height) as Float);
 
     let red = canvas::color(1.0,0.0,0.0);
     let mut canvas1 = canvas::canvas(canvas_width, canvas_height);
@@ -38,51 +37,45 @@ pub fn shadow1_plot() {
     // s = spheres::set_transform(
     //     s,
     //     spheres::matrix::shearing(0.0,0.0,0.0,0.0,1.0,0.0),
-    // );
+    // ).expect("shearing is invertible");
+
+    // all rays start from the same point behind the sphere
+    let ray_origin = spheres::matrix::tuples::point3(0.0,0.0,-6.0);
 
     // ------------------------------------------------------
 
     for i in 0..canvas_width { // x-loop from x = 0 to x = width - 1
-        let pixel_x = pixel_side * (i as f32) - half_shift;
+        let pixel_x = pixel_side * (i as Float) - half_shift;
         for j in 0..canvas_height { // y-loop from y = 0 to y = height - 1
-            let pixel_y = pixel_side * (j as f32) - half_shift;
-            let pixel_location = spheres::matrix::tuples::point(
+            let pixel_y = pixel_side * (j as Float) - half_shift;
+            let pixel_location = spheres::matrix::tuples::point3(
                 pixel_x,
                 pixel_y,
                 wall_z
             );
             let ray = spheres::Ray {
-                origin: spheres::matrix::tuples::point(0.0,0.0,-6.0),
-                direction: spheres::matrix::tuples::normalize(
-                    spheres::matrix::tuples::subtract(
-                        pixel_location,
-                        spheres::matrix::tuples::point(0.0,0.0,-6.0)
-                    )
-                )
+                origin: ray_origin.into(),
+                direction: (pixel_location - ray_origin).normalize().into()
             };
             let xs1 = spheres::intersect(s,ray);
             // colour the pixel only if it hits the sphere
-            if spheres::hit(xs1).len() != 0 {
-                canvas1 = canvas::write_pixel(canvas1,i,j,red);
+            if !spheres::hit(xs1).is_empty() {
+                canvas1.set_pixel(i,j,red);
             }
         }
     }
 
-    let mut ppm_string = canvas::header(canvas_width,canvas_height);
 
-    for i in canvas1 {
-        for j in i {
-            ppm_string.push_str(&((j.red * 255.0).ceil()).to_string());
-            ppm_string.push_str(" ");
-            ppm_string.push_str(&((j.green * 255.0).ceil()).to_string());
-            ppm_string.push_str(" ");
-            ppm_string.push_str(&((j.blue * 255.0).ceil()).to_string());
-            ppm_string.push_str(" ");
-        }
-        ppm_string.push_str("\n");
-    }
+    canvas1
+}
 
+#[allow(dead_code)]
+pub fn shadow1_plot() {
+    let canvas1 = shadow1_canvas();
+    // the render is linear light; encode it for display
+    let display = canvas::tonemap::DisplayTransform::identity().srgb();
     // fs::write("canvas.ppm", header(5,3));
-    fs::write("renders/sphere_shadows/sphere_shadow1.ppm", ppm_string).expect("");
+    canvas1.save_ppm_with("renders/sphere_shadows/sphere_shadow1.ppm", canvas::PpmFormat::Plain, &display).expect("");
+    canvas1.save_png_with("renders/sphere_shadows/sphere_shadow1.png", canvas::png::PngFormat::Rgb8, &display).expect("");
 
 }
\ No newline at end of file
@@ -10,52 +10,112 @@ use std::cmp::Ordering::Equal;
 #[path = "matrix.rs"]
 pub mod matrix;
 
+use matrix::tuples::Float;
+use matrix::tuples::approx::ApproxEq;
+
 #[derive(Debug, Clone, Copy)]
 pub struct Ray {
     pub origin: matrix::tuples::Tuple,
     pub direction: matrix::tuples::Tuple
 }
 
-pub fn position(ray: Ray, t:f32) -> matrix::tuples::Tuple {
+pub fn position(ray: Ray, t:Float) -> matrix::tuples::Tuple {
     matrix::tuples::add(ray.origin, matrix::tuples::multiply(t, ray.direction))
 }
 
 #[derive(Debug, Clone, PartialEq, Copy)]
 pub struct Sphere {
-    pub transform:matrix::Matrix
+    // Private so the cached inverses can't drift from the transform: all
+    // three are set together by sphere() and set_transform, and read
+    // through the getters below.
+    transform:matrix::Matrix,
+    inverse:matrix::Matrix,
+    inverse_transpose:matrix::Matrix
 }
 
-pub fn sphere() -> Sphere {
-    Sphere {
-        transform: matrix::Matrix(
-            [
-                [1.0,0.0,0.0,0.0],
-                [0.0,1.0,0.0,0.0],
-                [0.0,0.0,1.0,0.0],
-                [0.0,0.0,0.0,1.0]
-            ]
-        )
+impl Sphere {
+    pub fn transform(&self) -> matrix::Matrix {
+        self.transform
+    }
+
+    // Cached by set_transform, so rays and normals don't re-invert the
+    // transform on every call.
+    pub fn inverse(&self) -> matrix::Matrix {
+        self.inverse
+    }
+
+    pub fn inverse_transpose(&self) -> matrix::Matrix {
+        self.inverse_transpose
     }
 }
 
-pub fn set_transform(_s:Sphere, t:matrix::Matrix) -> Sphere {
+pub fn sphere() -> Sphere {
+    let identity = matrix::Matrix(
+        [
+            [1.0,0.0,0.0,0.0],
+            [0.0,1.0,0.0,0.0],
+            [0.0,0.0,1.0,0.0],
+            [0.0,0.0,0.0,1.0]
+        ]
+    );
     Sphere {
-        transform:t
+        transform: identity,
+        inverse: identity,
+        inverse_transpose: identity
     }
 }
 
+pub fn set_transform(_s:Sphere, t:matrix::Matrix) -> Result<Sphere, matrix::InvertError> {
+    // intersect() needs the inverse, so a transform without one is rejected here
+    let inverse = matrix::try_invert(t)?;
+    Ok(Sphere {
+        transform:t,
+        inverse,
+        inverse_transpose: matrix::transpose(inverse)
+    })
+}
+
+pub fn bounds(s:Sphere) -> matrix::bounds::Aabb {
+    // Tight box around the transformed unit sphere. Transforming the
+    // [-1, 1] cube's corners would over-size rotated spheres; instead the
+    // half-extent along each axis is the length of that row of the 3x3 part.
+    let m = s.transform.0;
+    let center = matrix::tuples::point3(m[0][3], m[1][3], m[2][3]);
+    let half = matrix::tuples::vector3(
+        matrix::tuples::vector3(m[0][0], m[0][1], m[0][2]).magnitude(),
+        matrix::tuples::vector3(m[1][0], m[1][1], m[1][2]).magnitude(),
+        matrix::tuples::vector3(m[2][0], m[2][1], m[2][2]).magnitude());
+    matrix::bounds::Aabb::new(center - half, center + half)
+}
+
 #[derive(Debug, Clone, PartialEq, Copy)]
 pub struct Intersection {
-    pub t:f32,
+    pub t:Float,
     pub object: Sphere
 }
 
+impl matrix::tuples::approx::ApproxEq for Intersection {
+    fn mismatches(
+        &self,
+        other:&Intersection,
+        tol:matrix::tuples::approx::Tolerance
+    ) -> Vec<matrix::tuples::approx::Mismatch> {
+        // t, plus the object's transform (the cached inverses follow from it)
+        let mut mismatches = matrix::tuples::approx::compare_components(&[("t", self.t, other.t)], tol);
+        for mut m in self.object.transform.mismatches(&other.object.transform, tol) {
+            m.component = format!("object.transform{}", m.component);
+            mismatches.push(m);
+        }
+        mismatches
+    }
+}
+
 // pub fn intersections(vec1:Vec<Intersection>) -> Vec<Intersection> {
 //     vec1
 // }
 
 pub fn intersect(s:Sphere, r:Ray) -> Vec<Intersection> {
-    let r = transform_ray(r, matrix::invert(s.transform));
+    let r = transform_ray(r, s.inverse);
     let sphere_to_ray = matrix::tuples::subtract(r.origin, matrix::tuples::point(0.0,0.0,0.0));
     let a = matrix::tuples::dot_product(r.direction,r.direction);
     let b = 2.0 * matrix::tuples::dot_product(r.direction, sphere_to_ray);
@@ -202,6 +262,15 @@ pub fn ray_sphere_tests() {
     println!("{:?}", hit(vec![i9,i10]));
     println!("{:?}", hit(vec![i1,i2,i3,i4]));
 
+    // approximate comparison of intersections
+    let xs = intersect(sphere(), r1);
+    matrix::tuples::approx::assert_approx_eq!(xs[0], Intersection { t: 4.0, object: sphere() });
+    assert!(xs[1].approx_eq(&Intersection { t: 6.0 + 1e-6, object: sphere() }));
+    let moved = set_transform(sphere(), matrix::translation(0.0,0.0,1e-3)).expect("translation is invertible");
+    let wrong = xs[0].mismatches(&Intersection { t: 4.5, object: moved }, Default::default());
+    assert_eq!(wrong.len(), 2);
+    assert_eq!(wrong[1].component, "object.transform[2][3]");
+
 }
 
 #[allow(dead_code)]
@@ -221,9 +290,26 @@ pub fn ray_sphere_transform_tests() {
         direction: matrix::tuples::vector(0.0,0.0,1.0),
     };
     let mut s1 = sphere();
-    s1 = set_transform(s1, matrix::scaling(2.0,2.0,2.0));
+    s1 = set_transform(s1, matrix::scaling(2.0,2.0,2.0)).expect("scaling is invertible");
     let mut s2 = sphere();
-    s2 = set_transform(s2, matrix::translation(5.0,0.0,0.0));
+    s2 = set_transform(s2, matrix::translation(5.0,0.0,0.0)).expect("translation is invertible");
+
+    let t = matrix::multiply_matrix(matrix::translation(1.0,2.0,3.0), matrix::scaling(2.0,2.0,2.0));
+    let s3 = set_transform(sphere(), t).expect("transform is invertible");
+    assert_eq!(s3.transform(), t);
+    assert_eq!(s3.inverse(), matrix::invert(t));
+    assert_eq!(s3.inverse_transpose(), matrix::transpose(matrix::invert(t)));
+    assert_eq!(sphere().inverse(), sphere().transform());
+    // Replacing the transform refreshes both caches
+    let s5 = set_transform(s3, matrix::scaling(2.0,2.0,2.0)).expect("scaling is invertible");
+    assert_eq!(s5.inverse(), matrix::scaling(0.5,0.5,0.5));
+    assert_eq!(s5.inverse_transpose(), matrix::scaling(0.5,0.5,0.5));
+
+    assert_eq!(
+        set_transform(sphere(), matrix::scaling(0.0,1.0,1.0)),
+        Err(matrix::InvertError::Singular)
+    );
+    assert!(set_transform(sphere(), matrix::scaling(1e-3,1e3,1.0)).is_ok());
 
     let xs1 = intersect(s1,r2);
     let xs2 = intersect(s2,r2);
@@ -231,4 +317,29 @@ pub fn ray_sphere_transform_tests() {
     println!("{:#?}", xs1);
     println!("{:#?}", xs2);
 
+    // Bounds follow the transform
+    let p = matrix::tuples::point3;
+    let unit = matrix::bounds::Aabb::new(p(-1.0,-1.0,-1.0), p(1.0,1.0,1.0));
+    assert_eq!(bounds(sphere()), unit);
+    assert_eq!(bounds(s1), matrix::bounds::Aabb::new(p(-2.0,-2.0,-2.0), p(2.0,2.0,2.0)));
+    assert_eq!(bounds(s3), matrix::bounds::Aabb::new(p(-1.0,0.0,1.0), p(3.0,4.0,5.0)));
+    // a rotated ellipsoid: tighter than the rotated cube, but still holds
+    // every surface point
+    let t = matrix::Transform::identity().scale(3.0,1.0,1.0).rotate_z(0.6).translate(0.0,1.0,0.0).build();
+    let s4 = set_transform(sphere(), t).expect("transform is invertible");
+    let b4 = bounds(s4);
+    assert!(unit.transform(t).contains(b4) && unit.transform(t) != b4);
+    for i in 0..64 {
+        let (theta, phi) = (i as Float * 0.37, i as Float * 0.91);
+        let surface = p(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+        let q = t * surface;
+        assert!((0..3).all(|k| b4.min[k] - 1e-5 <= q[k] && q[k] <= b4.max[k] + 1e-5));
+    }
+    // rays that hit the sphere hit its box first
+    let origin = matrix::tuples::Point3::from(r2.origin);
+    let direction = matrix::tuples::Vector3::from(r2.direction);
+    let (enter, _) = bounds(s1).intersect_ray(origin, direction).expect("ray passes through the box");
+    assert!(enter <= xs1[0].t);
+    assert_eq!(bounds(s2).intersect_ray(origin, direction), None);
+
 }
\ No newline at end of file
@@ -0,0 +1,208 @@
+/*
+Tone mapping
+------------
+The writers clamp every channel to 0..1, so anything brighter than white
+is lost. A DisplayTransform maps scene-linear colours to display values
+first, in three steps:
+
+    1. exposure: multiply by 2^stops
+    2. tone map: squeeze 0..inf into 0..1 (Clamp just cuts it off)
+    3. transfer: optionally encode with the sRGB curve
+
+Each render picks its own when it's exported, e.g.
+
+    let display = DisplayTransform::identity().exposure(-1.0).tone_map(ToneMap::Aces).srgb();
+    image.save_png_with("renders/x.png", PngFormat::Rgb8, &display)
+
+(and save_ppm_with, write_ppm_with, write_png_with, encode_png_with), or
+converts the canvas itself with to_display. DisplayTransform::identity()
+is what the plain writers do: clamp, no exposure, linear output. The operators work per channel, so saturated
+highlights shift hue a little towards white, which is usually what you
+want from a film-like curve. Negative and NaN channels map to 0.
+*/
+
+use super::tuples::Float;
+use super::{color, Canvas, Color};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ToneMap {
+    // min(x, 1)
+    Clamp,
+    // x / (1 + x): never quite reaches white
+    Reinhard,
+    // x (1 + x / white^2) / (1 + x): reaches 1 at x = white
+    ExtendedReinhard{white:Float},
+    // Narkowicz's fit to the ACES filmic curve
+    Aces,
+}
+
+impl ToneMap {
+    pub fn map(self, x:Float) -> Float {
+        if x.is_nan() || x <= 0.0 {
+            return 0.0;
+        }
+        let y = match self {
+            ToneMap::Clamp => x,
+            ToneMap::Reinhard => x / (1.0 + x),
+            ToneMap::ExtendedReinhard{white} => x * (1.0 + x / (white * white)) / (1.0 + x),
+            ToneMap::Aces => (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14),
+        };
+        // Reinhard and ACES of infinity are NaN; they tend to 1
+        if y.is_nan() { 1.0 } else { y.min(1.0) }
+    }
+}
+
+pub fn linear_to_srgb(c:Float) -> Float {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear(c:Float) -> Float {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DisplayTransform {
+    // in stops: +1 doubles the brightness
+    pub exposure:Float,
+    pub tone_map:ToneMap,
+    pub srgb:bool,
+}
+
+impl Default for DisplayTransform {
+    fn default() -> DisplayTransform {
+        DisplayTransform::identity()
+    }
+}
+
+impl DisplayTransform {
+    pub fn identity() -> DisplayTransform {
+        DisplayTransform{exposure: 0.0, tone_map: ToneMap::Clamp, srgb: false}
+    }
+
+    pub fn exposure(self, stops:Float) -> DisplayTransform {
+        DisplayTransform{exposure: stops, ..self}
+    }
+
+    pub fn tone_map(self, tone_map:ToneMap) -> DisplayTransform {
+        DisplayTransform{tone_map, ..self}
+    }
+
+    pub fn srgb(self) -> DisplayTransform {
+        DisplayTransform{srgb: true, ..self}
+    }
+
+    pub fn apply_channel(&self, c:Float) -> Float {
+        let mapped = self.tone_map.map(c * (2.0 as Float).powf(self.exposure));
+        if self.srgb { linear_to_srgb(mapped) } else { mapped }
+    }
+
+    pub fn apply(&self, c:Color) -> Color {
+        color(self.apply_channel(c.red), self.apply_channel(c.green), self.apply_channel(c.blue))
+    }
+}
+
+impl Canvas {
+    pub fn to_display(&self, transform:&DisplayTransform) -> Canvas {
+        let mut out = self.clone();
+        for pixel in out.pixels_mut() {
+            *pixel = transform.apply(*pixel);
+        }
+        out
+    }
+}
+
+#[allow(dead_code)]
+pub fn tonemap_tests() {
+    use super::tuples::approx::{assert_approx_eq, ApproxEq};
+    let close = |a:Float, b:Float| (a - b).abs() < 1e-5;
+
+    // Operators
+    for op in [ToneMap::Clamp, ToneMap::Reinhard, ToneMap::ExtendedReinhard{white: 4.0}, ToneMap::Aces] {
+        assert_eq!(op.map(0.0), 0.0);
+        assert_eq!(op.map(-2.0), 0.0);
+        assert_eq!(op.map(Float::NAN), 0.0);
+        assert!(op.map(Float::INFINITY) <= 1.0 && op.map(1e30) <= 1.0);
+        // monotonic over a wide range
+        let samples:Vec<Float> = (0..200).map(|i| op.map(i as Float * 0.1)).collect();
+        assert!(samples.windows(2).all(|w| w[0] <= w[1]), "{:?} isn't monotonic", op);
+    }
+    assert_eq!(ToneMap::Clamp.map(0.25), 0.25);
+    assert_eq!(ToneMap::Clamp.map(7.0), 1.0);
+    assert!(close(ToneMap::Reinhard.map(1.0), 0.5));
+    assert!(close(ToneMap::Reinhard.map(3.0), 0.75));
+    assert!(close(ToneMap::ExtendedReinhard{white: 4.0}.map(4.0), 1.0));
+    assert!(close(ToneMap::ExtendedReinhard{white: 4.0}.map(1.0), 0.5 * (1.0 + 1.0 / 16.0)));
+    assert!(ToneMap::Aces.map(0.18) > 0.2 && ToneMap::Aces.map(0.18) < 0.3);
+    assert_eq!(ToneMap::Aces.map(100.0), 1.0);
+
+    // sRGB transfer curve
+    assert_eq!(linear_to_srgb(0.0), 0.0);
+    assert!(close(linear_to_srgb(1.0), 1.0));
+    assert!(close(linear_to_srgb(0.5), 0.735_356_7));
+    assert!(close(linear_to_srgb(0.001), 0.012_92));
+    assert!(close(srgb_to_linear(0.5), 0.214_041_14));
+    for i in 0..=100 {
+        let c = i as Float / 100.0;
+        assert!((srgb_to_linear(linear_to_srgb(c)) - c).abs() < 1e-5);
+    }
+
+    // Display transforms
+    let identity = DisplayTransform::identity();
+    assert_eq!(DisplayTransform::default(), identity);
+    assert_eq!(identity.apply(color(0.2, 0.4, 0.6)), color(0.2, 0.4, 0.6));
+    assert_eq!(identity.apply(color(1.5, -1.0, 0.5)), color(1.0, 0.0, 0.5));
+    let brighter = identity.exposure(1.0);
+    assert_approx_eq!(brighter.apply(color(0.1, 0.2, 0.7)), color(0.2, 0.4, 1.0));
+    let darker = identity.exposure(-2.0).tone_map(ToneMap::Reinhard);
+    assert_approx_eq!(darker.apply(color(4.0, 0.0, 12.0)), color(0.5, 0.0, 0.75));
+    let film = identity.tone_map(ToneMap::Aces).srgb();
+    assert!(close(film.apply_channel(0.18), linear_to_srgb(ToneMap::Aces.map(0.18))));
+
+    // On a canvas: highlights keep their gradient instead of clipping
+    let mut image = super::canvas(5, 1);
+    for x in 0..5 {
+        image.set_pixel(x, 0, color(x as Float, x as Float, x as Float));
+    }
+    let clipped = image.to_display(&identity);
+    assert_eq!(clipped.pixel_at(2, 0), clipped.pixel_at(4, 0));
+    let mapped = image.to_display(&identity.tone_map(ToneMap::Reinhard).srgb());
+    let values:Vec<Float> = (0..5).map(|x| mapped.pixel_at(x, 0).red).collect();
+    assert!(values.windows(2).all(|w| w[0] < w[1]) && values[4] < 1.0);
+    assert_eq!(image.pixel_at(4, 0), color(4.0, 4.0, 4.0));
+
+    // On export: the writers take a transform, identity by default
+    use super::png::{encode_png, encode_png_with, PngFormat};
+    use super::PpmFormat;
+    let reinhard = identity.tone_map(ToneMap::Reinhard);
+    let ppm = |image:&Canvas, display:&DisplayTransform, format:PpmFormat| {
+        let mut out = Vec::new();
+        image.write_ppm_with(&mut out, format, display).expect("writing to a Vec can't fail");
+        out
+    };
+    for format in [PpmFormat::Plain, PpmFormat::Binary8, PpmFormat::Binary16] {
+        let mut plain = Vec::new();
+        image.write_ppm_as(&mut plain, format).unwrap();
+        assert_eq!(ppm(&image, &identity, format), plain);
+        assert_eq!(ppm(&image, &reinhard, format), ppm(&image.to_display(&reinhard), &identity, format));
+    }
+    assert!(String::from_utf8(ppm(&image, &reinhard, PpmFormat::Plain)).unwrap().ends_with("0 0 0 128 128 128 170 170 170 191 191 191 204 204 204\n"));
+    assert_eq!(encode_png_with(&image, None, PngFormat::Rgb16, &identity), encode_png(&image, None, PngFormat::Rgb16));
+    assert_eq!(
+        encode_png_with(&image, None, PngFormat::Rgb8, &film),
+        encode_png(&image.to_display(&film), None, PngFormat::Rgb8)
+    );
+    let mut written = Vec::new();
+    image.write_png_with(&mut written, PngFormat::Rgb8, &film).unwrap();
+    assert_eq!(written, encode_png(&image.to_display(&film), None, PngFormat::Rgb8));
+    let path = std::env::temp_dir().join("hyperion_tonemap_test.png");
+    image.save_png_with(&path, PngFormat::Rgb8, &film).expect("temp dir is writable");
+    assert_eq!(std::fs::read(&path).unwrap(), written);
+}
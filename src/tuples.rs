@@ -5,23 +5,49 @@ The Ray Tracer Challenge (Jamis Buck)
 Chapter 1: Tuples, Points and Vectors
 */
 
-pub fn float_equal(a:f32, b:f32) -> bool {
-    (a-b).abs() <= f32::EPSILON
+use std::ops::{Add, Sub, Mul, Div, Neg, Index};
+
+// Scalar type used by the whole math and ray stack. Build with
+// `--features f64` for double precision (e.g. reference renders).
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+#[allow(unused_imports)]
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+#[allow(unused_imports)]
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
+#[path = "approx.rs"]
+pub mod approx;
+
+#[path = "simd.rs"]
+pub mod simd;
+
+pub fn float_equal(a:Float, b:Float) -> bool {
+    // see approx.rs for what "equal" means
+    approx::floats_equal(a, b, approx::Tolerance::default())
 }
 
+// Laid out like [Float; 4] (and 16-byte aligned) so it moves in and out of
+// SIMD registers as one load/store.
 #[derive(Debug, Copy, Clone)]
+#[repr(C, align(16))]
 pub struct Tuple {
-    pub x:f32,
-    pub y:f32,
-    pub z:f32,
-    pub w:f32,
+    pub x:Float,
+    pub y:Float,
+    pub z:Float,
+    pub w:Float,
 }
 
-pub fn point(a:f32, b:f32, c:f32) -> Tuple {
+pub fn point(a:Float, b:Float, c:Float) -> Tuple {
     Tuple{x:a,y:b,z:c,w:1.0}
 }
 
-pub fn vector(a:f32, b:f32, c:f32) -> Tuple {
+pub fn vector(a:Float, b:Float, c:Float) -> Tuple {
     Tuple{x:a,y:b,z:c,w:0.0}
 }
 
@@ -32,24 +58,34 @@ pub fn equal_tuples(tup1:Tuple, tup2:Tuple) -> bool {
         && float_equal(tup1.w, tup2.w)
 }
 
+impl Tuple {
+    pub fn to_lanes(self) -> simd::Lanes {
+        [self.x, self.y, self.z, self.w]
+    }
+
+    pub fn from_lanes(l:simd::Lanes) -> Tuple {
+        Tuple{x:l[0],y:l[1],z:l[2],w:l[3]}
+    }
+}
+
 pub fn add(tup1:Tuple, tup2:Tuple) -> Tuple {
-    Tuple{x:tup1.x + tup2.x, y:tup1.y + tup2.y, z:tup1.z + tup2.z, w:tup1.w + tup2.w}
+    Tuple::from_lanes(simd::add(tup1.to_lanes(), tup2.to_lanes()))
 }
 
 pub fn subtract(tup1:Tuple, tup2:Tuple) -> Tuple {
-    Tuple{x:tup1.x - tup2.x, y:tup1.y - tup2.y, z:tup1.z - tup2.z, w:tup1.w - tup2.w}
+    Tuple::from_lanes(simd::sub(tup1.to_lanes(), tup2.to_lanes()))
 }
 
 pub fn negate(tup1:Tuple) -> Tuple {
-    Tuple{x:-tup1.x, y:-tup1.y, z:-tup1.z, w:-tup1.w}
+    Tuple::from_lanes(simd::neg(tup1.to_lanes()))
 }
 
-pub fn multiply(scalar:f32, tup1:Tuple) -> Tuple {
-    Tuple{x:scalar*tup1.x, y:scalar*tup1.y, z:scalar*tup1.z, w:scalar*tup1.w}
+pub fn multiply(scalar:Float, tup1:Tuple) -> Tuple {
+    Tuple::from_lanes(simd::scale(scalar, tup1.to_lanes()))
 }
 
-pub fn magnitude(tup1:Tuple) -> f32  {
-    (tup1.x * tup1.x + tup1.y * tup1.y + tup1.z * tup1.z + tup1.w * tup1.w).sqrt()
+pub fn magnitude(tup1:Tuple) -> Float  {
+    dot_product(tup1, tup1).sqrt()
 }
 
 pub fn normalize(tup1:Tuple) -> Tuple {
@@ -57,15 +93,288 @@ pub fn normalize(tup1:Tuple) -> Tuple {
     multiply(1.0/magnitude(tup1), tup1)
 }
 
-pub fn dot_product(vec1:Tuple, vec2:Tuple) -> f32 {
-    vec1.x * vec2.x + vec1.y * vec2.y + vec1.z * vec2.z + vec1.w * vec2.w
+pub fn dot_product(vec1:Tuple, vec2:Tuple) -> Float {
+    simd::dot(vec1.to_lanes(), vec2.to_lanes())
 }
 
 pub fn cross_product(vec1:Tuple, vec2:Tuple) -> Tuple {
-    vector(
-        vec1.y * vec2.z - vec1.z * vec2.y, 
-        vec1.z * vec2.x - vec1.x * vec2.z,
-        vec1.x * vec2.y - vec1.y * vec2.x)
+    Tuple::from_lanes(simd::cross(vec1.to_lanes(), vec2.to_lanes()))
+}
+
+/*
+Vector toolkit
+--------------
+Geometry the shading code keeps needing. Directions are Tuples with
+w = 0.0; normals are expected to be normalized.
+
+Spherical coordinates use the maths convention: theta is the polar angle
+from +z (0..pi), phi the azimuth from +x towards +y (-pi..pi).
+*/
+
+pub fn reflect(incoming:Tuple, normal:Tuple) -> Tuple {
+    // mirror `incoming` (pointing at the surface) about the normal
+    subtract(incoming, multiply(2.0 * dot_product(incoming, normal), normal))
+}
+
+pub fn refract(incoming:Tuple, normal:Tuple, eta_ratio:Float) -> Option<Tuple> {
+    // Snell's law. `incoming` points at the surface, `normal` out of it on
+    // the incoming side, eta_ratio = n1 / n2 (e.g. 1.0 / 1.5 entering glass).
+    // None means total internal reflection.
+    let cos_i = -dot_product(incoming, normal);
+    let sin2_t = eta_ratio * eta_ratio * (1.0 - cos_i * cos_i);
+    if sin2_t > 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(add(multiply(eta_ratio, incoming), multiply(eta_ratio * cos_i - cos_t, normal)))
+}
+
+pub fn orthonormal_basis(v:Tuple) -> (Tuple, Tuple, Tuple) {
+    // (tangent, bitangent, normal), right-handed, with normal = v
+    // normalized. Branchless construction from Duff et al. 2017, "Building
+    // an Orthonormal Basis, Revisited"; stable for every direction.
+    let n = normalize(v);
+    let sign = (1.0 as Float).copysign(n.z);
+    let a = -1.0 / (sign + n.z);
+    let b = n.x * n.y * a;
+    let tangent = vector(1.0 + sign * n.x * n.x * a, sign * b, -sign * n.x);
+    let bitangent = vector(b, sign + n.y * n.y * a, -n.y);
+    (tangent, bitangent, n)
+}
+
+pub fn to_spherical(v:Tuple) -> (Float, Float, Float) {
+    // (radius, theta, phi); the zero vector gives (0, 0, 0)
+    let r = (v.x * v.x + v.y * v.y + v.z * v.z).sqrt();
+    if r == 0.0 {
+        return (0.0, 0.0, 0.0);
+    }
+    (r, (v.z / r).clamp(-1.0, 1.0).acos(), v.y.atan2(v.x))
+}
+
+pub fn from_spherical(r:Float, theta:Float, phi:Float) -> Tuple {
+    let (sin_theta, cos_theta) = theta.sin_cos();
+    let (sin_phi, cos_phi) = phi.sin_cos();
+    vector(r * sin_theta * cos_phi, r * sin_theta * sin_phi, r * cos_theta)
+}
+
+pub fn lerp(tup1:Tuple, tup2:Tuple, t:Float) -> Tuple {
+    // tup1 at t = 0, tup2 at t = 1 (w too, so points stay points)
+    add(tup1, multiply(t, subtract(tup2, tup1)))
+}
+
+pub fn min_components(tup1:Tuple, tup2:Tuple) -> Tuple {
+    Tuple{x:tup1.x.min(tup2.x), y:tup1.y.min(tup2.y), z:tup1.z.min(tup2.z), w:tup1.w.min(tup2.w)}
+}
+
+pub fn max_components(tup1:Tuple, tup2:Tuple) -> Tuple {
+    Tuple{x:tup1.x.max(tup2.x), y:tup1.y.max(tup2.y), z:tup1.z.max(tup2.z), w:tup1.w.max(tup2.w)}
+}
+
+pub fn abs_components(tup1:Tuple) -> Tuple {
+    Tuple{x:tup1.x.abs(), y:tup1.y.abs(), z:tup1.z.abs(), w:tup1.w.abs()}
+}
+
+pub fn angle_between(vec1:Tuple, vec2:Tuple) -> Float {
+    // in radians, 0..pi. atan2 of |cross| and dot stays accurate for
+    // nearly (anti)parallel vectors, where acos of the dot doesn't.
+    magnitude(cross_product(vec1, vec2)).atan2(dot_product(vec1, vec2))
+}
+
+/*
+Typed points and vectors
+------------------------
+Tuple lets you add two points or cross a point with a vector without
+complaint. Point3 and Vector3 only implement the operators that make sense:
+
+    Point3  + Vector3 = Point3      Point3  - Point3  = Vector3
+    Vector3 + Point3  = Point3      Point3  - Vector3 = Point3
+    Vector3 + Vector3 = Vector3     Vector3 - Vector3 = Vector3
+    Vector3 * Float     = Vector3     -Vector3          = Vector3
+
+Both convert to and from Tuple (w = 1.0 for points, w = 0.0 for vectors;
+converting a Tuple drops its w).
+*/
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Point3 {
+    pub x:Float,
+    pub y:Float,
+    pub z:Float,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Vector3 {
+    pub x:Float,
+    pub y:Float,
+    pub z:Float,
+}
+
+pub fn point3(a:Float, b:Float, c:Float) -> Point3 {
+    Point3{x:a,y:b,z:c}
+}
+
+pub fn vector3(a:Float, b:Float, c:Float) -> Vector3 {
+    Vector3{x:a,y:b,z:c}
+}
+
+impl Point3 {
+    pub fn origin() -> Point3 {
+        point3(0.0,0.0,0.0)
+    }
+}
+
+impl Vector3 {
+    pub fn dot(self, other:Vector3) -> Float {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other:Vector3) -> Vector3 {
+        vector3(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x)
+    }
+
+    pub fn magnitude(self) -> Float {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Vector3 {
+        self / self.magnitude()
+    }
+}
+
+impl From<Point3> for Tuple {
+    fn from(p:Point3) -> Tuple {
+        point(p.x, p.y, p.z)
+    }
+}
+
+impl From<Vector3> for Tuple {
+    fn from(v:Vector3) -> Tuple {
+        vector(v.x, v.y, v.z)
+    }
+}
+
+impl From<Tuple> for Point3 {
+    fn from(t:Tuple) -> Point3 {
+        point3(t.x, t.y, t.z)
+    }
+}
+
+impl From<Tuple> for Vector3 {
+    fn from(t:Tuple) -> Vector3 {
+        vector3(t.x, t.y, t.z)
+    }
+}
+
+// (x, y), e.g. for plotting. Every copy of this module converts to the
+// same pair type, so code that takes Into<(Float, Float)> accepts any
+// copy's Tuples.
+impl From<Tuple> for (Float, Float) {
+    fn from(t:Tuple) -> (Float, Float) {
+        (t.x, t.y)
+    }
+}
+
+impl From<&Tuple> for (Float, Float) {
+    fn from(t:&Tuple) -> (Float, Float) {
+        (t.x, t.y)
+    }
+}
+
+impl Add<Vector3> for Point3 {
+    type Output = Point3;
+    fn add(self, v:Vector3) -> Point3 {
+        point3(self.x + v.x, self.y + v.y, self.z + v.z)
+    }
+}
+
+impl Add<Point3> for Vector3 {
+    type Output = Point3;
+    fn add(self, p:Point3) -> Point3 {
+        p + self
+    }
+}
+
+impl Add for Vector3 {
+    type Output = Vector3;
+    fn add(self, v:Vector3) -> Vector3 {
+        vector3(self.x + v.x, self.y + v.y, self.z + v.z)
+    }
+}
+
+impl Sub for Point3 {
+    type Output = Vector3;
+    fn sub(self, p:Point3) -> Vector3 {
+        vector3(self.x - p.x, self.y - p.y, self.z - p.z)
+    }
+}
+
+impl Sub<Vector3> for Point3 {
+    type Output = Point3;
+    fn sub(self, v:Vector3) -> Point3 {
+        point3(self.x - v.x, self.y - v.y, self.z - v.z)
+    }
+}
+
+impl Sub for Vector3 {
+    type Output = Vector3;
+    fn sub(self, v:Vector3) -> Vector3 {
+        vector3(self.x - v.x, self.y - v.y, self.z - v.z)
+    }
+}
+
+impl Mul<Float> for Vector3 {
+    type Output = Vector3;
+    fn mul(self, scalar:Float) -> Vector3 {
+        vector3(self.x * scalar, self.y * scalar, self.z * scalar)
+    }
+}
+
+impl Mul<Vector3> for Float {
+    type Output = Vector3;
+    fn mul(self, v:Vector3) -> Vector3 {
+        v * self
+    }
+}
+
+impl Div<Float> for Vector3 {
+    type Output = Vector3;
+    fn div(self, scalar:Float) -> Vector3 {
+        vector3(self.x / scalar, self.y / scalar, self.z / scalar)
+    }
+}
+
+impl Neg for Vector3 {
+    type Output = Vector3;
+    fn neg(self) -> Vector3 {
+        vector3(-self.x, -self.y, -self.z)
+    }
+}
+
+impl Index<usize> for Point3 {
+    type Output = Float;
+    fn index(&self, i:usize) -> &Float {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Point3 index out of range: {}", i),
+        }
+    }
+}
+
+impl Index<usize> for Vector3 {
+    type Output = Float;
+    fn index(&self, i:usize) -> &Float {
+        match i {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Vector3 index out of range: {}", i),
+        }
+    }
 }
 
 
@@ -105,3 +414,100 @@ pub fn tuple_tests() {
     println!("{:?}", cross_product(vec5, vec4));
 
 }
+
+#[allow(dead_code)]
+pub fn typed_tuple_tests() {
+    let p1 = point3(3.0,-2.0,5.0);
+    let p2 = point3(-2.0,3.0,1.0);
+    let v1 = vector3(1.0,2.0,3.0);
+    let v2 = vector3(2.0,3.0,4.0);
+
+    assert_eq!(p1 - p2, vector3(5.0,-5.0,4.0));
+    assert_eq!(p1 + v1, point3(4.0,0.0,8.0));
+    assert_eq!(v1 + p1, p1 + v1);
+    assert_eq!(p1 - v1, point3(2.0,-4.0,2.0));
+    assert_eq!(v2 - v1, vector3(1.0,1.0,1.0));
+    assert_eq!(-v1, vector3(-1.0,-2.0,-3.0));
+    assert_eq!(v1 * 2.0, 2.0 * v1);
+    assert_eq!(v1 / 2.0, vector3(0.5,1.0,1.5));
+    assert_eq!(v1.dot(v2), 20.0);
+    assert_eq!(v1.cross(v2), vector3(-1.0,2.0,-1.0));
+    assert_eq!(v2.cross(v1), vector3(1.0,-2.0,1.0));
+    assert!(float_equal(v1.normalize().magnitude(), 1.0));
+    assert_eq!((p1[0], p1[1], p1[2]), (3.0,-2.0,5.0));
+    assert_eq!(v1[2], 3.0);
+
+    assert!(equal_tuples(Tuple::from(p1), point(3.0,-2.0,5.0)));
+    assert!(equal_tuples(Tuple::from(v1), vector(1.0,2.0,3.0)));
+    assert_eq!(Point3::from(point(1.0,2.0,3.0)), point3(1.0,2.0,3.0));
+    assert_eq!(Vector3::from(vector(1.0,2.0,3.0)), vector3(1.0,2.0,3.0));
+    assert_eq!(Point3::origin() + v1, point3(1.0,2.0,3.0));
+}
+
+#[allow(dead_code)]
+pub fn vector_toolkit_tests() {
+    use approx::{assert_approx_eq, ApproxEq, Tolerance};
+    let half = consts::FRAC_1_SQRT_2;
+
+    // Reflection (the book's two cases)
+    assert_approx_eq!(reflect(vector(1.0,-1.0,0.0), vector(0.0,1.0,0.0)), vector(1.0,1.0,0.0));
+    assert_approx_eq!(reflect(vector(0.0,-1.0,0.0), vector(half,half,0.0)), vector(1.0,0.0,0.0));
+
+    // Refraction: straight through at normal incidence, bends towards the
+    // normal entering a denser medium, and total internal reflection
+    let down = vector(0.0,-1.0,0.0);
+    let up = vector(0.0,1.0,0.0);
+    assert_approx_eq!(refract(down, up, 1.0 / 1.5).unwrap(), down);
+    let slanted = normalize(vector(1.0,-1.0,0.0));
+    let bent = refract(slanted, up, 1.0 / 1.5).unwrap();
+    assert!(float_equal(magnitude(bent), 1.0));
+    let sin_out = bent.x;
+    assert!(float_equal(sin_out, half / 1.5));
+    assert_approx_eq!(refract(slanted, up, 1.0).unwrap(), slanted);
+    assert!(refract(slanted, up, 1.5).is_none());
+    assert!(refract(normalize(vector(0.5,-1.0,0.0)), up, 1.5).is_some());
+
+    // Orthonormal bases, including the awkward -z direction
+    for v in [vector(0.0,0.0,1.0), vector(0.0,0.0,-1.0), vector(1.0,2.0,3.0), vector(-4.0,0.1,-0.2), vector(0.0,1.0,0.0)] {
+        let (t, b, n) = orthonormal_basis(v);
+        assert_approx_eq!(n, normalize(v));
+        for (p, q) in [(t, b), (b, n), (n, t)] {
+            assert!(dot_product(p, q).approx_eq_with(&0.0, Tolerance::absolute(1e-6)));
+        }
+        assert!(float_equal(magnitude(t), 1.0) && float_equal(magnitude(b), 1.0));
+        assert_approx_eq!(cross_product(t, b), n);
+    }
+
+    // Spherical coordinates
+    let (r, theta, phi) = to_spherical(vector(0.0,2.0,0.0));
+    assert!(float_equal(r, 2.0) && float_equal(theta, consts::FRAC_PI_2) && float_equal(phi, consts::FRAC_PI_2));
+    assert_eq!(to_spherical(vector(0.0,0.0,-3.0)), (3.0, consts::PI, 0.0));
+    assert_eq!(to_spherical(vector(0.0,0.0,0.0)), (0.0, 0.0, 0.0));
+    assert_approx_eq!(from_spherical(1.0, consts::FRAC_PI_2, 0.0), vector(1.0,0.0,0.0));
+    for v in [vector(1.0,2.0,3.0), vector(-1.0,-0.5,0.25), vector(0.0,-7.0,1.0)] {
+        let (r, theta, phi) = to_spherical(v);
+        assert_approx_eq!(from_spherical(r, theta, phi), v);
+    }
+
+    // lerp keeps points as points
+    let p1 = point(0.0,10.0,-2.0);
+    let p2 = point(4.0,0.0,2.0);
+    assert_approx_eq!(lerp(p1, p2, 0.0), p1);
+    assert_approx_eq!(lerp(p1, p2, 1.0), p2);
+    assert_approx_eq!(lerp(p1, p2, 0.25), point(1.0,7.5,-1.0));
+
+    // Component-wise helpers
+    let a = vector(1.0,-5.0,3.0);
+    let b = vector(-2.0,4.0,3.5);
+    assert_approx_eq!(min_components(a, b), vector(-2.0,-5.0,3.0));
+    assert_approx_eq!(max_components(a, b), vector(1.0,4.0,3.5));
+    assert_approx_eq!(abs_components(a), vector(1.0,5.0,3.0));
+    assert_eq!(min_components(p1, a).w, 0.0);
+
+    // Angles
+    assert!(float_equal(angle_between(vector(1.0,0.0,0.0), vector(0.0,3.0,0.0)), consts::FRAC_PI_2));
+    assert!(float_equal(angle_between(vector(1.0,1.0,0.0), vector(1.0,0.0,0.0)), consts::FRAC_PI_4));
+    assert!(float_equal(angle_between(a, negate(a)), consts::PI));
+    assert_eq!(angle_between(a, multiply(2.0, a)), 0.0);
+    assert!(angle_between(vector(1.0,0.0,0.0), vector(1.0,1e-4,0.0)) > 0.0);
+}
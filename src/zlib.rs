@@ -0,0 +1,530 @@
+/*
+zlib / deflate
+--------------
+Just enough of RFC 1950 (zlib) and RFC 1951 (deflate) for PNG, with no
+dependencies.
+
+compress() does LZ77 over a 32K window (hash chains, greedy matching) and
+writes a single block with the fixed Huffman codes. That is well short of
+what zlib -9 achieves, but renders are mostly flat colour and shrink a lot
+anyway.
+
+decompress() is a complete inflater: stored, fixed and dynamic Huffman
+blocks, checked against the Adler-32 trailer. The Huffman decoding is the
+canonical count/symbol scheme from zlib's puff.c. A few bytes of deflate
+can expand to gigabytes, so anything that knows how much data to expect
+(decode_png does, from IHDR) should use decompress_limited(), which stops
+as soon as the output passes the limit.
+*/
+
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum InflateError {
+    // bad CMF/FLG bytes, unsupported method or a preset dictionary
+    BadHeader,
+    UnexpectedEof,
+    BadBlockType,
+    // stored block whose LEN and NLEN don't match
+    BadStoredLength,
+    // code lengths that don't form a valid prefix code, or an unused code
+    BadCode,
+    // back-reference further than the data decoded so far
+    BadDistance,
+    ChecksumMismatch{expected:u32, found:u32},
+    // output longer than decompress_limited() allows
+    TooLong{limit:usize},
+}
+
+impl fmt::Display for InflateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InflateError::BadHeader => write!(f, "Not a zlib stream (bad header)"),
+            InflateError::UnexpectedEof => write!(f, "Compressed data ends early"),
+            InflateError::BadBlockType => write!(f, "Invalid deflate block type"),
+            InflateError::BadStoredLength => write!(f, "Stored block length doesn't match its complement"),
+            InflateError::BadCode => write!(f, "Invalid Huffman code in deflate data"),
+            InflateError::BadDistance => write!(f, "Deflate distance reaches before the start of the data"),
+            InflateError::ChecksumMismatch{expected, found} => {
+                write!(f, "Adler-32 mismatch: expected {:08x}, found {:08x}", expected, found)
+            }
+            InflateError::TooLong{limit} => write!(f, "Decompressed data is longer than {} bytes", limit),
+        }
+    }
+}
+
+impl std::error::Error for InflateError {}
+
+pub fn adler32(data:&[u8]) -> u32 {
+    const MOD:u32 = 65521;
+    let (mut a, mut b) = (1u32, 0u32);
+    // 5552 is the most bytes that can be summed before b overflows
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+// Lengths 3..=258 and distances 1..=32768 are sent as a base code plus
+// extra bits (RFC 1951 section 3.2.5).
+const LENGTH_BASE:[u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258
+];
+const LENGTH_EXTRA:[u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0
+];
+const DIST_BASE:[u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577
+];
+const DIST_EXTRA:[u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13
+];
+
+// ---------------------------------------------------------------- compress
+
+struct BitWriter {
+    out:Vec<u8>,
+    buffer:u64,
+    count:u32,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value:u32, n:u32) {
+        // least significant bit first, as deflate packs everything but
+        // Huffman codes
+        self.buffer |= (value as u64) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn code(&mut self, code:u32, n:u32) {
+        // Huffman codes go most significant bit first
+        self.bits(code.reverse_bits() >> (32 - n), n);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+fn fixed_literal(w:&mut BitWriter, symbol:u32) {
+    match symbol {
+        0..=143 => w.code(0x30 + symbol, 8),
+        144..=255 => w.code(0x190 + symbol - 144, 9),
+        256..=279 => w.code(symbol - 256, 7),
+        _ => w.code(0xc0 + symbol - 280, 8),
+    }
+}
+
+fn fixed_match(w:&mut BitWriter, length:usize, distance:usize) {
+    let l = LENGTH_BASE.iter().rposition(|&b| b as usize <= length).expect("length >= 3");
+    fixed_literal(w, 257 + l as u32);
+    w.bits((length - LENGTH_BASE[l] as usize) as u32, LENGTH_EXTRA[l] as u32);
+    let d = DIST_BASE.iter().rposition(|&b| b as usize <= distance).expect("distance >= 1");
+    w.code(d as u32, 5);
+    w.bits((distance - DIST_BASE[d] as usize) as u32, DIST_EXTRA[d] as u32);
+}
+
+const WINDOW:usize = 32768;
+const MIN_MATCH:usize = 3;
+const MAX_MATCH:usize = 258;
+const MAX_CHAIN:usize = 64;
+const HASH_BITS:u32 = 15;
+
+fn hash(data:&[u8], i:usize) -> usize {
+    let v = (data[i] as u32) << 16 | (data[i + 1] as u32) << 8 | data[i + 2] as u32;
+    (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+fn insert(data:&[u8], i:usize, head:&mut [usize], prev:&mut [usize]) {
+    if i + MIN_MATCH <= data.len() {
+        let h = hash(data, i);
+        prev[i % WINDOW] = head[h];
+        head[h] = i;
+    }
+}
+
+pub fn compress(data:&[u8]) -> Vec<u8> {
+    let mut w = BitWriter{out: vec![0x78, 0x01], buffer: 0, count: 0};
+    // one final block, fixed Huffman codes
+    w.bits(1, 1);
+    w.bits(1, 2);
+
+    // head[h] is the latest position with hash h, prev[i % WINDOW] the one
+    // before it with the same hash; usize::MAX marks the end of a chain
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+
+    let mut i = 0;
+    while i < data.len() {
+        let mut best_len = 0;
+        let mut best_dist = 0;
+        if i + MIN_MATCH <= data.len() {
+            let max_len = MAX_MATCH.min(data.len() - i);
+            let mut candidate = head[hash(data, i)];
+            let mut chain = 0;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain < MAX_CHAIN {
+                let len = data[candidate..].iter().zip(&data[i..i + max_len]).take_while(|(a, b)| a == b).count();
+                if len > best_len {
+                    best_len = len;
+                    best_dist = i - candidate;
+                    if len == max_len {
+                        break;
+                    }
+                }
+                let next = prev[candidate % WINDOW];
+                // chains only ever point backwards; anything else is a
+                // slot that has been reused by a newer position
+                if next == usize::MAX || next >= candidate {
+                    break;
+                }
+                candidate = next;
+                chain += 1;
+            }
+        }
+
+        if best_len >= MIN_MATCH {
+            fixed_match(&mut w, best_len, best_dist);
+            for j in i..i + best_len {
+                insert(data, j, &mut head, &mut prev);
+            }
+            i += best_len;
+        } else {
+            fixed_literal(&mut w, data[i] as u32);
+            insert(data, i, &mut head, &mut prev);
+            i += 1;
+        }
+    }
+    fixed_literal(&mut w, 256);
+
+    let mut out = w.finish();
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+// -------------------------------------------------------------- decompress
+
+struct BitReader<'a> {
+    data:&'a [u8],
+    pos:usize,
+    buffer:u32,
+    count:u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, n:u32) -> Result<u32, InflateError> {
+        while self.count < n {
+            let byte = *self.data.get(self.pos).ok_or(InflateError::UnexpectedEof)?;
+            self.pos += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        // drop the rest of the current byte
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+struct Huffman {
+    // count[len] codes of each length, symbols ordered by code
+    count:[u16; 16],
+    symbol:Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths:&[u8]) -> Result<Huffman, InflateError> {
+        let mut count = [0u16; 16];
+        for &l in lengths {
+            count[l as usize] += 1;
+        }
+        count[0] = 0;
+
+        // more codes of some length than the tree has room for
+        let mut left:i32 = 1;
+        for &c in &count[1..] {
+            left = (left << 1) - c as i32;
+            if left < 0 {
+                return Err(InflateError::BadCode);
+            }
+        }
+
+        let mut offset = [0u16; 16];
+        for len in 1..15 {
+            offset[len + 1] = offset[len] + count[len];
+        }
+        let mut symbol = vec![0u16; lengths.len()];
+        for (s, &l) in lengths.iter().enumerate() {
+            if l != 0 {
+                symbol[offset[l as usize] as usize] = s as u16;
+                offset[l as usize] += 1;
+            }
+        }
+        Ok(Huffman{count, symbol})
+    }
+
+    fn decode(&self, r:&mut BitReader) -> Result<u16, InflateError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= r.bits(1)? as i32;
+            let count = self.count[len] as i32;
+            if code - count < first {
+                return Ok(self.symbol[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        // ran out of lengths: the code isn't in an incomplete tree
+        Err(InflateError::BadCode)
+    }
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    let literal = Huffman::new(&lengths).expect("fixed code is valid");
+    let distance = Huffman::new(&[5u8; 30]).expect("fixed code is valid");
+    (literal, distance)
+}
+
+fn dynamic_tables(r:&mut BitReader) -> Result<(Huffman, Huffman), InflateError> {
+    const ORDER:[usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+    let nlen = r.bits(5)? as usize + 257;
+    let ndist = r.bits(5)? as usize + 1;
+    let ncode = r.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err(InflateError::BadCode);
+    }
+
+    let mut code_lengths = [0u8; 19];
+    for &i in &ORDER[..ncode] {
+        code_lengths[i] = r.bits(3)? as u8;
+    }
+    let lencode = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut i = 0;
+    while i < nlen + ndist {
+        let symbol = lencode.decode(r)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                // repeat the previous length 3-6 times
+                let previous = *lengths[..i].last().ok_or(InflateError::BadCode)?;
+                (previous, 3 + r.bits(2)? as usize)
+            }
+            17 => (0, 3 + r.bits(3)? as usize),
+            _ => (0, 11 + r.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err(InflateError::BadCode);
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+    // no end-of-block code means the block can never end
+    if lengths[256] == 0 {
+        return Err(InflateError::BadCode);
+    }
+    Ok((Huffman::new(&lengths[..nlen])?, Huffman::new(&lengths[nlen..])?))
+}
+
+fn inflate_block(r:&mut BitReader, out:&mut Vec<u8>, limit:usize, literal:&Huffman, distance:&Huffman) -> Result<(), InflateError> {
+    loop {
+        let symbol = literal.decode(r)? as usize;
+        if symbol < 256 {
+            if out.len() >= limit {
+                return Err(InflateError::TooLong{limit});
+            }
+            out.push(symbol as u8);
+        } else if symbol == 256 {
+            return Ok(());
+        } else {
+            let l = symbol - 257;
+            if l >= 29 {
+                return Err(InflateError::BadCode);
+            }
+            let length = LENGTH_BASE[l] as usize + r.bits(LENGTH_EXTRA[l] as u32)? as usize;
+            let d = distance.decode(r)? as usize;
+            if d >= 30 {
+                return Err(InflateError::BadCode);
+            }
+            let dist = DIST_BASE[d] as usize + r.bits(DIST_EXTRA[d] as u32)? as usize;
+            if dist > out.len() {
+                return Err(InflateError::BadDistance);
+            }
+            if length > limit - out.len() {
+                return Err(InflateError::TooLong{limit});
+            }
+            // byte by byte: the source may overlap what is being written
+            let start = out.len() - dist;
+            for k in 0..length {
+                out.push(out[start + k]);
+            }
+        }
+    }
+}
+
+pub fn decompress(data:&[u8]) -> Result<Vec<u8>, InflateError> {
+    decompress_limited(data, usize::MAX)
+}
+
+pub fn decompress_limited(data:&[u8], limit:usize) -> Result<Vec<u8>, InflateError> {
+    // fails with TooLong as soon as the output would pass limit bytes
+    if data.len() < 2 {
+        return Err(InflateError::UnexpectedEof);
+    }
+    let (cmf, flg) = (data[0], data[1]);
+    // deflate, window <= 32K, header checksum, no preset dictionary
+    if cmf & 0x0f != 8 || cmf >> 4 > 7 || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) || flg & 0x20 != 0 {
+        return Err(InflateError::BadHeader);
+    }
+
+    let mut r = BitReader{data, pos: 2, buffer: 0, count: 0};
+    let mut out = Vec::new();
+    loop {
+        let last = r.bits(1)? == 1;
+        match r.bits(2)? {
+            0 => {
+                r.align();
+                let header = data.get(r.pos..r.pos + 4).ok_or(InflateError::UnexpectedEof)?;
+                let len = u16::from_le_bytes([header[0], header[1]]);
+                let nlen = u16::from_le_bytes([header[2], header[3]]);
+                if len != !nlen {
+                    return Err(InflateError::BadStoredLength);
+                }
+                let start = r.pos + 4;
+                let stored = data.get(start..start + len as usize).ok_or(InflateError::UnexpectedEof)?;
+                if stored.len() > limit - out.len() {
+                    return Err(InflateError::TooLong{limit});
+                }
+                out.extend_from_slice(stored);
+                r.pos = start + len as usize;
+            }
+            1 => {
+                let (literal, distance) = fixed_tables();
+                inflate_block(&mut r, &mut out, limit, &literal, &distance)?;
+            }
+            2 => {
+                let (literal, distance) = dynamic_tables(&mut r)?;
+                inflate_block(&mut r, &mut out, limit, &literal, &distance)?;
+            }
+            _ => return Err(InflateError::BadBlockType),
+        }
+        if last {
+            break;
+        }
+    }
+
+    // the Adler-32 trailer starts at the next byte boundary
+    r.align();
+    let trailer = data.get(r.pos..r.pos + 4).ok_or(InflateError::UnexpectedEof)?;
+    let expected = u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+    let found = adler32(&out);
+    if expected != found {
+        return Err(InflateError::ChecksumMismatch{expected, found});
+    }
+    Ok(out)
+}
+
+#[allow(dead_code)]
+pub fn zlib_tests() {
+    // Checksums (Wikipedia's example)
+    assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    assert_eq!(adler32(b""), 1);
+    assert_eq!(adler32(&vec![0xff; 100_000]), 0x149a_302c);
+
+    // Round trips, from empty to long and repetitive to incompressible
+    let mut noise = Vec::new();
+    let mut seed:u32 = 12345;
+    for _ in 0..70_000 {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        noise.push((seed >> 16) as u8);
+    }
+    let mut stripes = Vec::new();
+    for i in 0..100_000u32 {
+        stripes.push(if (i / 300) % 2 == 0 { 255 } else { (i % 7) as u8 });
+    }
+    let inputs:[&[u8]; 7] = [b"", b"a", b"abcabcabcabcabcabcabc", &[0; 1000], &noise, &stripes, b"The quick brown fox jumps over the lazy dog"];
+    for input in inputs {
+        let packed = compress(input);
+        assert_eq!(decompress(&packed).expect("own output inflates"), input);
+    }
+    assert!(compress(&[0; 100_000]).len() < 1000);
+    assert!(compress(&stripes).len() < stripes.len() / 10);
+
+    // Streams from zlib itself: stored (level 0), fixed and dynamic Huffman
+    let stored = [
+        0x78, 0x01, 0x01, 0x05, 0x00, 0xfa, 0xff, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x06, 0x2c, 0x02, 0x15
+    ];
+    assert_eq!(decompress(&stored).unwrap(), b"hello");
+    let fixed = [
+        0x78, 0x9c, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x90, 0x00, 0x3a, 0x2e, 0x06, 0x7d
+    ];
+    assert_eq!(decompress(&fixed).unwrap(), b"hello hello hello");
+    let dynamic = [
+        0x78, 0xda, 0x25, 0x8c, 0x89, 0x0d, 0x00, 0x30, 0x08, 0x02, 0x67, 0x3d, 0xb0, 0xdd, 0x7f, 0x84,
+        0x22, 0x35, 0x24, 0xf2, 0x88, 0x12, 0x19, 0xe1, 0x6c, 0x9b, 0x0a, 0xa9, 0x16, 0x1a, 0xb3, 0x3c,
+        0x98, 0x66, 0xcb, 0x45, 0xdd, 0xd6, 0xbe, 0x6c, 0x43, 0x0e, 0xf6, 0x42, 0x9c, 0x9b, 0x60, 0x38,
+        0x79, 0xb8, 0xc6, 0xf0, 0x00, 0x88, 0x23, 0x26, 0x35
+    ];
+    // 100 letters, 'a' most common, generated the same way zlib was fed
+    let mut seed:u32 = 1;
+    let text:Vec<u8> = (0..100).map(|_| {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        b'a' + ((seed >> 16) as u16).trailing_zeros().min(7) as u8
+    }).collect();
+    assert_eq!(dynamic[2] >> 1 & 3, 2);
+    assert_eq!(decompress(&dynamic).unwrap(), text);
+
+    // Errors
+    assert_eq!(decompress(&[0x78]), Err(InflateError::UnexpectedEof));
+    assert_eq!(decompress(&[0x78, 0x02, 0x03, 0x00]), Err(InflateError::BadHeader));
+    assert_eq!(decompress(&[0x78, 0x01, 0x07]), Err(InflateError::BadBlockType));
+    assert_eq!(decompress(&[0x78, 0x01, 0x01, 0x05, 0x00, 0x00, 0x00]), Err(InflateError::BadStoredLength));
+    assert_eq!(decompress(&stored[..10]), Err(InflateError::UnexpectedEof));
+    let mut corrupt = stored;
+    corrupt[8] ^= 1;
+    assert!(matches!(decompress(&corrupt), Err(InflateError::ChecksumMismatch{..})));
+    // fixed block starting with a back-reference: nothing to copy from
+    let mut w = BitWriter{out: vec![0x78, 0x01], buffer: 0, count: 0};
+    w.bits(1, 1);
+    w.bits(1, 2);
+    fixed_match(&mut w, 3, 1);
+    assert_eq!(decompress(&w.finish()), Err(InflateError::BadDistance));
+    // a limit stops literals, back-references and stored blocks alike
+    let zeros = compress(&[0u8; 100_000]);
+    assert!(zeros.len() < 1000);
+    assert_eq!(decompress_limited(&zeros, 100_000).map(|d| d.len()), Ok(100_000));
+    assert_eq!(decompress_limited(&zeros, 99_999), Err(InflateError::TooLong{limit: 99_999}));
+    assert_eq!(decompress_limited(&zeros, 0), Err(InflateError::TooLong{limit: 0}));
+    assert_eq!(decompress_limited(&stored, 4), Err(InflateError::TooLong{limit: 4}));
+    assert_eq!(decompress_limited(&stored, 5), decompress(&stored));
+    assert_eq!(InflateError::ChecksumMismatch{expected: 1, found: 0xabc}.to_string(), "Adler-32 mismatch: expected 00000001, found 00000abc");
+}